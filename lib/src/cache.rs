@@ -0,0 +1,327 @@
+// git-dit - the distributed issue tracker for git
+// Copyright (C) 2017 Matthias Beyer <mail@beyermatthias.de>
+// Copyright (C) 2017 Julian Ganz <neither@nut.email>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+
+//! Metadata caching
+//!
+//! Accumulating the metadata of an issue requires walking all the messages
+//! from the issue's head to its initial message and parsing the trailers of
+//! each of them. For repositories with many issues, this quickly becomes the
+//! dominant cost of listing and filtering issues.
+//!
+//! This module provides a persistent cache for the trailers found on the
+//! first-parent chain of an issue's head. The cache is stored in the `dit`
+//! directory inside the repository's git directory, with one entry per issue.
+//! Each entry records the head for which it was computed. Since messages are
+//! immutable, an entry is valid as long as the head doesn't move. If it does,
+//! the entry is recomputed and replaced transparently.
+//!
+
+use git2::{self, Oid};
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+
+use issue::Issue;
+use message::Message;
+use trailer::{Trailer, TrailerValue};
+use trailer::accumulation::{Accumulator, ValueAccumulator};
+use trailer::spec::{ToMap, TrailerSpec};
+use utils;
+
+use error::*;
+use error::ErrorKind as EK;
+
+
+/// Identifier for the format of cache entries
+///
+/// Entries with a different header are considered invalid.
+///
+const ENTRY_HEADER: &str = "dit-metadata-cache 1";
+
+
+/// Cached trailers of a single message
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedMessage {
+    pub id: Oid,
+    pub trailers: Vec<Trailer>,
+}
+
+
+/// Persistent cache for issue metadata
+///
+/// The cache stores the trailers of all the messages on the first-parent chain
+/// from an issue's head to its initial message, in the order in which
+/// accumulation processes them (e.g. starting with the head).
+///
+/// Failing to store an entry is not considered an error, since the cache only
+/// exists for performance reasons. Unreadable or corrupted entries are simply
+/// recomputed.
+///
+pub struct MetadataCache {
+    path: PathBuf,
+}
+
+impl MetadataCache {
+    /// Create a new handle for the cache of a repository
+    ///
+    pub fn new(repo: &git2::Repository) -> Self {
+        MetadataCache { path: utils::dit_dir(repo).join("cache") }
+    }
+
+    /// Get the cached messages for an issue with a given head
+    ///
+    /// If no valid entry exists for the issue and head, the messages are
+    /// retrieved from the repository and the entry is updated.
+    ///
+    pub fn messages(&self, issue: &Issue, head: Oid) -> Result<Vec<CachedMessage>> {
+        if let Some(messages) = self.load(issue, head) {
+            return Ok(messages);
+        }
+
+        let mut messages = Vec::new();
+        for message in issue.messages_from(head)? {
+            let message = message?;
+            messages.push(CachedMessage {
+                id: message.id(),
+                trailers: message.trailers().collect(),
+            });
+        }
+
+        // A failure to store the entry doesn't affect the result.
+        self.store(issue, head, &messages).ok();
+        Ok(messages)
+    }
+
+    /// Get all trailers for an issue with a given head
+    ///
+    /// The trailers are returned in the order in which they should be
+    /// accumulated.
+    ///
+    pub fn trailers(&self, issue: &Issue, head: Oid) -> Result<Vec<Trailer>> {
+        self.messages(issue, head)
+            .map(|messages| messages
+                .into_iter()
+                .flat_map(|message| message.trailers)
+                .collect()
+            )
+    }
+
+    /// Accumulate trailers according to the specification provided
+    ///
+    /// This function is equivalent to accumulating the trailers of the
+    /// messages from the head to the initial message of an issue, e.g. via
+    /// `MessagesExt::accumulate_trailers()`.
+    ///
+    pub fn accumulate<I, J>(&self, issue: &Issue, head: Oid, specs: I) -> Result<HashMap<String, ValueAccumulator>>
+        where I: IntoIterator<Item = J>,
+              J: Borrow<TrailerSpec>
+    {
+        let mut accumulator = specs.into_map();
+        accumulator.process_all(self.trailers(issue, head)?);
+        Ok(accumulator)
+    }
+
+    /// Remove all entries for issues not in a given set of issues
+    ///
+    pub fn prune<'a, I>(&self, issues: I) -> Result<()>
+        where I: IntoIterator<Item = &'a Issue<'a>>
+    {
+        let keep: Vec<String> = issues.into_iter().map(Issue::ref_part).collect();
+
+        let entries = match fs::read_dir(&self.path) {
+            Ok(entries) => entries,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err).chain_err(|| EK::CannotWriteCache(self.path_string())),
+        };
+
+        for entry in entries {
+            let entry = entry.chain_err(|| EK::CannotWriteCache(self.path_string()))?;
+            let obsolete = entry
+                .file_name()
+                .to_str()
+                .map(|name| !keep.iter().any(|id| id == name))
+                .unwrap_or(true);
+            if obsolete {
+                fs::remove_file(entry.path())
+                    .chain_err(|| EK::CannotWriteCache(self.path_string()))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the path of the entry for an issue
+    ///
+    fn entry_path(&self, issue: &Issue) -> PathBuf {
+        self.path.join(issue.ref_part())
+    }
+
+    /// Get the path of the cache as a string, for error reporting
+    ///
+    fn path_string(&self) -> String {
+        self.path.to_string_lossy().into_owned()
+    }
+
+    /// Load an entry, if a valid one exists
+    ///
+    fn load(&self, issue: &Issue, head: Oid) -> Option<Vec<CachedMessage>> {
+        let file = fs::File::open(self.entry_path(issue)).ok()?;
+        let mut lines = io::BufReader::new(file).lines();
+
+        if lines.next()?.ok()? != ENTRY_HEADER {
+            return None;
+        }
+        if lines.next()?.ok()? != format!("head {}", head) {
+            return None;
+        }
+
+        let mut messages: Vec<CachedMessage> = Vec::new();
+        for line in lines {
+            let line = line.ok()?;
+            if let Some(id) = line.strip_prefix("message ") {
+                let id = Oid::from_str(id).ok()?;
+                messages.push(CachedMessage { id, trailers: Vec::new() });
+            } else {
+                let mut parts = line.splitn(2, ": ");
+                let key = parts.next()?;
+                let value = unescape(parts.next()?);
+                messages
+                    .last_mut()?
+                    .trailers
+                    .push(Trailer {
+                        key: key.to_string().into(),
+                        value: TrailerValue::from_slice(&value),
+                    });
+            }
+        }
+
+        if messages.first().map(|m| m.id) == Some(head) {
+            Some(messages)
+        } else {
+            None
+        }
+    }
+
+    /// Store an entry
+    ///
+    fn store(&self, issue: &Issue, head: Oid, messages: &[CachedMessage]) -> Result<()> {
+        let path = self.entry_path(issue);
+        let tmp_path = self.path.join(format!(".{}.tmp", issue.ref_part()));
+
+        fs::create_dir_all(&self.path)
+            .and_then(|_| {
+                let mut file = io::BufWriter::new(fs::File::create(&tmp_path)?);
+                writeln!(file, "{}", ENTRY_HEADER)?;
+                writeln!(file, "head {}", head)?;
+                for message in messages {
+                    writeln!(file, "message {}", message.id)?;
+                    for trailer in message.trailers.iter() {
+                        writeln!(file, "{}: {}", trailer.key, escape(&trailer.value.to_string()))?;
+                    }
+                }
+                file.flush()
+            })
+            .and_then(|_| fs::rename(&tmp_path, &path))
+            .chain_err(|| EK::CannotWriteCache(path.to_string_lossy().into_owned()))
+    }
+}
+
+
+/// Escape a trailer value for storing it on a single line
+///
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+
+/// Reverse the escaping performed by `escape()`
+///
+fn unescape(value: &str) -> String {
+    let mut retval = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') => retval.push('\n'),
+                Some(other) => retval.push(other),
+                None => retval.push('\\'),
+            }
+        } else {
+            retval.push(c);
+        }
+    }
+    retval
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::TestingRepo;
+
+    use repository::RepositoryExt;
+    use trailer::spec;
+
+    #[test]
+    fn escaping() {
+        let value = "multi\n  line \\ value";
+        assert_eq!(unescape(&escape(value)), value);
+        assert!(!escape(value).contains('\n'));
+    }
+
+    #[test]
+    fn cached_accumulation() {
+        let mut testing_repo = TestingRepo::new("cached_accumulation");
+        let repo = testing_repo.repo();
+
+        let sig = git2::Signature::now("Foo Bar", "foo.bar@example.com")
+            .expect("Could not create signature");
+        let empty_tree = repo
+            .empty_tree()
+            .expect("Could not create empty tree");
+        let issue = repo
+            .create_issue(&sig, &sig, "Test message 1\n\nDit-status: open", &empty_tree, vec![])
+            .expect("Could not create issue");
+        let initial_message = issue
+            .initial_message()
+            .expect("Could not retrieve initial message");
+        let message = issue
+            .add_message(&sig, &sig, "Test message 2\n\nDit-status: closed", &empty_tree, vec![&initial_message])
+            .expect("Could not add message");
+
+        let cache = repo.metadata_cache();
+        let status = |head| cache
+            .accumulate(&issue, head, vec![spec::ISSUE_STATUS_SPEC])
+            .expect("Could not accumulate metadata")
+            .remove("Dit-status")
+            .expect("Could not retrieve status")
+            .into_iter()
+            .next()
+            .expect("No status found")
+            .to_string();
+
+        assert_eq!(status(issue.id()), "open");
+        // this query is served from the cache
+        assert_eq!(status(issue.id()), "open");
+        // moving the head invalidates the entry
+        assert_eq!(status(message.id()), "closed");
+        assert_eq!(
+            cache.messages(&issue, message.id()).expect("Could not retrieve messages").len(),
+            2
+        );
+
+        cache.prune(vec![]).expect("Could not prune cache");
+        assert!(cache.load(&issue, message.id()).is_none());
+    }
+}
+
@@ -0,0 +1,353 @@
+// git-dit - the distributed issue tracker for git
+// Copyright (C) 2017 Matthias Beyer <mail@beyermatthias.de>
+// Copyright (C) 2017 Julian Ganz <neither@nut.email>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+
+//! Dependencies between issues
+//!
+//! An issue may depend on other issues, e.g. because it can't be resolved
+//! before they are. Dependencies are expressed via the metadata of an issue:
+//! a `Dit-depends-on` trailer declares that the issue depends on the issue
+//! referred to and a `Dit-blocks` trailer declares that the issue referred to
+//! depends on the issue. Both trailers hold the id of an issue, which may be
+//! abbreviated, and are accumulated as lists. The keys of the trailers may be
+//! configured, hence the specifications are supplied by the user.
+//!
+//! This module provides the `DependencyGraph`, which relates issues to their
+//! dependencies and allows detecting cycles.
+//!
+
+use git2::{Oid, Repository};
+use std::cmp::min;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use issue::Issue;
+use iter::MessagesExt;
+use repository::RepositoryExt;
+use trailer::accumulation::ValueAccumulator;
+use trailer::spec::TrailerSpec;
+
+use error::*;
+
+
+/// Graph of dependencies between issues
+///
+/// Each edge of the graph leads from an issue to one of its dependencies.
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DependencyGraph {
+    dependencies: BTreeMap<Oid, BTreeSet<Oid>>,
+}
+
+impl DependencyGraph {
+    /// Create an empty dependency graph
+    ///
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Create the dependency graph for all issues in a repository
+    ///
+    /// The dependencies of each issue are taken from the metadata accumulated
+    /// from the head selected by the function supplied, e.g. according to a
+    /// remote priorization. Issues for which no head is selected are not
+    /// considered. Dependencies are declared via the trailers specified.
+    ///
+    pub fn from_repository<F>(repo: &Repository,
+                              depends_on: &TrailerSpec,
+                              blocks: &TrailerSpec,
+                              mut head: F
+    ) -> Result<Self>
+        where F: FnMut(&Issue) -> Result<Option<Oid>>
+    {
+        let mut retval = Self::new();
+        for issue in repo.issues()? {
+            let head = match head(&issue)? {
+                Some(head) => head,
+                None => continue,
+            };
+            let metadata = issue
+                .messages_from(head)?
+                .collect::<::std::result::Result<Vec<_>, _>>()?
+                .into_iter()
+                .accumulate_trailers(vec![depends_on, blocks]);
+            retval.add_issue(repo, issue.id(), &metadata, depends_on, blocks);
+        }
+        Ok(retval)
+    }
+
+    /// Add a dependency
+    ///
+    pub fn add_dependency(&mut self, dependent: Oid, dependency: Oid) {
+        self.dependencies.entry(dependency).or_default();
+        self.dependencies.entry(dependent).or_default().insert(dependency);
+    }
+
+    /// Add the dependencies declared in the metadata of an issue
+    ///
+    /// The metadata is expected to be keyed by trailer keys, as returned by
+    /// `MessagesExt::accumulate_trailers()`. Dependencies are declared via
+    /// the trailers specified. Values which don't refer to an issue are
+    /// ignored.
+    ///
+    pub fn add_issue(&mut self,
+                     repo: &Repository,
+                     issue: Oid,
+                     metadata: &HashMap<String, ValueAccumulator>,
+                     depends_on: &TrailerSpec,
+                     blocks: &TrailerSpec
+    ) {
+        self.dependencies.entry(issue).or_default();
+
+        let values = |key: &str| metadata
+            .get(key)
+            .cloned()
+            .into_iter()
+            .flat_map(|values| values.into_iter())
+            .filter_map(|value| resolve_issue(repo, value.to_string().trim()))
+            .collect::<Vec<_>>();
+
+        for dependency in values(depends_on.key.as_ref()) {
+            self.add_dependency(issue, dependency);
+        }
+        for dependent in values(blocks.key.as_ref()) {
+            self.add_dependency(dependent, issue);
+        }
+    }
+
+    /// Get all issues in the graph
+    ///
+    pub fn issues(&self) -> impl Iterator<Item = Oid> + '_ {
+        self.dependencies.keys().cloned()
+    }
+
+    /// Get the direct dependencies of an issue
+    ///
+    pub fn dependencies(&self, issue: Oid) -> Vec<Oid> {
+        self.dependencies
+            .get(&issue)
+            .map(|dependencies| dependencies.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Get the issues directly depending on an issue
+    ///
+    pub fn dependents(&self, issue: Oid) -> Vec<Oid> {
+        self.dependencies
+            .iter()
+            .filter(|(_, dependencies)| dependencies.contains(&issue))
+            .map(|(dependent, _)| *dependent)
+            .collect()
+    }
+
+    /// Get the dependencies blocking an issue
+    ///
+    /// An issue is blocked by each of its direct dependencies which is not
+    /// resolved, as determined by the predicate supplied.
+    ///
+    pub fn blockers<F>(&self, issue: Oid, is_resolved: F) -> Vec<Oid>
+        where F: Fn(Oid) -> bool
+    {
+        self.dependencies(issue)
+            .into_iter()
+            .filter(|dependency| !is_resolved(*dependency))
+            .collect()
+    }
+
+    /// Find cycles in the graph
+    ///
+    /// Each cycle is returned as the set of issues forming a strongly
+    /// connected component of the graph, ordered by id. Issues depending on
+    /// themselves form a cycle of their own.
+    ///
+    pub fn cycles(&self) -> Vec<Vec<Oid>> {
+        let mut tarjan = Tarjan { graph: self, index: 0, state: HashMap::new(), stack: Vec::new(), components: Vec::new() };
+        for issue in self.dependencies.keys() {
+            if !tarjan.state.contains_key(issue) {
+                tarjan.visit(*issue);
+            }
+        }
+
+        tarjan
+            .components
+            .into_iter()
+            .filter(|component| component.len() > 1 || self
+                .dependencies
+                .get(&component[0])
+                .is_some_and(|dependencies| dependencies.contains(&component[0])))
+            .map(|mut component| { component.sort(); component })
+            .collect()
+    }
+
+    /// Render the graph in the Graphviz dot language
+    ///
+    /// Nodes are labeled using the function supplied.
+    ///
+    pub fn to_dot<F>(&self, label: F) -> String
+        where F: Fn(Oid) -> String
+    {
+        let mut retval = String::from("digraph dependencies {\n");
+        for issue in self.dependencies.keys() {
+            retval.push_str(&format!("    \"{}\" [label=\"{}\"];\n", issue, escape(&label(*issue))));
+        }
+        for (issue, dependencies) in self.dependencies.iter() {
+            for dependency in dependencies {
+                retval.push_str(&format!("    \"{}\" -> \"{}\";\n", issue, dependency));
+            }
+        }
+        retval.push_str("}\n");
+        retval
+    }
+}
+
+
+/// State of Tarjan's algorithm for finding strongly connected components
+///
+struct Tarjan<'a> {
+    graph: &'a DependencyGraph,
+    index: usize,
+    /// Index, lowlink and whether the node is on the stack, for each node
+    state: HashMap<Oid, (usize, usize, bool)>,
+    stack: Vec<Oid>,
+    components: Vec<Vec<Oid>>,
+}
+
+impl<'a> Tarjan<'a> {
+    fn visit(&mut self, node: Oid) {
+        let index = self.index;
+        self.index += 1;
+        self.state.insert(node, (index, index, true));
+        self.stack.push(node);
+
+        for successor in self.graph.dependencies(node) {
+            match self.state.get(&successor).cloned() {
+                None => {
+                    self.visit(successor);
+                    let lowlink = self.state[&successor].1;
+                    self.lower(node, lowlink);
+                },
+                Some((index, _, true)) => self.lower(node, index),
+                Some(_) => {},
+            }
+        }
+
+        let (index, lowlink, _) = self.state[&node];
+        if index == lowlink {
+            let mut component = Vec::new();
+            while let Some(member) = self.stack.pop() {
+                if let Some(state) = self.state.get_mut(&member) {
+                    state.2 = false;
+                }
+                component.push(member);
+                if member == node {
+                    break;
+                }
+            }
+            self.components.push(component);
+        }
+    }
+
+    fn lower(&mut self, node: Oid, value: usize) {
+        if let Some(state) = self.state.get_mut(&node) {
+            state.1 = min(state.1, value);
+        }
+    }
+}
+
+
+/// Resolve a value referring to an issue
+///
+/// The value has to be the hexadecimal id of an issue, which may be
+/// abbreviated.
+///
+pub fn resolve_issue(repo: &Repository, value: &str) -> Option<Oid> {
+    if value.len() < 4 {
+        return None;
+    }
+    repo.find_issue_abbrev(value)
+        .ok()
+        .map(|issue| issue.id())
+}
+
+
+/// Escape a string for use in a quoted dot identifier
+///
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::TestingRepo;
+    use trailer::spec::{ISSUE_BLOCKS_SPEC, ISSUE_DEPENDS_ON_SPEC};
+
+    use git2;
+
+    #[test]
+    fn cycles() {
+        let ids: Vec<Oid> = (1..6)
+            .map(|n| Oid::from_str(&format!("{:040}", n)).expect("Could not create id"))
+            .collect();
+
+        let mut graph = DependencyGraph::new();
+        graph.add_dependency(ids[0], ids[1]);
+        graph.add_dependency(ids[1], ids[2]);
+        graph.add_dependency(ids[0], ids[3]);
+        assert!(graph.cycles().is_empty());
+
+        graph.add_dependency(ids[2], ids[0]);
+        graph.add_dependency(ids[4], ids[4]);
+        assert_eq!(graph.cycles(), vec![vec![ids[0], ids[1], ids[2]], vec![ids[4]]]);
+
+        assert_eq!(graph.dependents(ids[0]), vec![ids[2]]);
+        assert_eq!(graph.blockers(ids[0], |id| id == ids[1]), vec![ids[3]]);
+    }
+
+    #[test]
+    fn dependency_graph() {
+        let mut testing_repo = TestingRepo::new("dependency_graph");
+        let repo = testing_repo.repo();
+
+        let sig = git2::Signature::now("Foo Bar", "foo.bar@example.com")
+            .expect("Could not create signature");
+        let empty_tree = repo
+            .empty_tree()
+            .expect("Could not create empty tree");
+
+        let issue1 = repo
+            .create_issue(&sig, &sig, "Test message 1", &empty_tree, vec![])
+            .expect("Could not create issue");
+        let text = format!("Test message 2\n\nDit-depends-on: {:.8}", issue1.id());
+        let issue2 = repo
+            .create_issue(&sig, &sig, &text, &empty_tree, vec![])
+            .expect("Could not create issue");
+        let text = format!("Test message 3\n\nDit-blocks: {}\nDit-depends-on: foo", issue2.id());
+        let issue3 = repo
+            .create_issue(&sig, &sig, &text, &empty_tree, vec![])
+            .expect("Could not create issue");
+
+        let local_head = |issue: &Issue| Ok(issue.local_head()?.target());
+        let graph = DependencyGraph::from_repository(repo, &ISSUE_DEPENDS_ON_SPEC, &ISSUE_BLOCKS_SPEC, local_head)
+            .expect("Could not create graph");
+        assert!(graph.dependencies(issue1.id()).is_empty());
+        let mut expected = vec![issue1.id(), issue3.id()];
+        expected.sort();
+        assert_eq!(graph.dependencies(issue2.id()), expected);
+        assert!(graph.dependencies(issue3.id()).is_empty());
+        assert_eq!(graph.dependents(issue3.id()), vec![issue2.id()]);
+
+        let dot = graph.to_dot(|id| format!("\"{}\"", id));
+        assert!(dot.starts_with("digraph dependencies {\n"));
+        assert!(dot.contains(&format!("    \"{}\" -> \"{}\";\n", issue2.id(), issue1.id())));
+        assert!(dot.contains(&format!("    \"{}\" [label=\"\\\"{}\\\"\"];\n", issue3.id(), issue3.id())));
+    }
+}
+
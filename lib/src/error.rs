@@ -65,6 +65,20 @@ error_chain! {
             display("Cannot find issue HEAD for {}", id)
         }
 
+        UnknownIssue(id: String) {
+            description("Cannot find an issue with the id supplied")
+            display("Cannot find an issue with the id '{}'", id)
+        }
+
+        AmbiguousIssueId(id: String, candidates: Vec<Oid>) {
+            description("The issue id supplied is ambiguous")
+            display("The issue id '{}' is ambiguous, candidates are: {}", id, candidates
+                .iter()
+                .map(Oid::to_string)
+                .collect::<Vec<_>>()
+                .join(", "))
+        }
+
         CannotSetReference(refname: String) {
             description("Cannot set some reference")
             display("Cannot update or create reference '{}'", refname)
@@ -90,6 +104,110 @@ error_chain! {
             display("Malformed trailer: {}", trailer)
         }
 
+        UnknownAccumulationPolicy(policy: String) {
+            description("Unknown accumulation policy")
+            display("Unknown accumulation policy: '{}'", policy)
+        }
+
+        UnknownValueType(value_type: String) {
+            description("Unknown value type")
+            display("Unknown value type: '{}'", value_type)
+        }
+
+        InvalidTrailerValue(value: String, value_type: String) {
+            description("Invalid trailer value")
+            display("Invalid value '{}', expected a value of type '{}'", value, value_type)
+        }
+
+        MalformedQuery(query: String) {
+            description("Malformed query")
+            display("Malformed query: '{}'", query)
+        }
+
+        UnexpectedToken(token: String) {
+            description("Unexpected token in query")
+            display("Unexpected token: '{}'", token)
+        }
+
+        UnexpectedEndOfQuery {
+            description("Unexpected end of query")
+            display("Unexpected end of query")
+        }
+
+        UnterminatedQuote {
+            description("Unterminated quote in query")
+            display("Unterminated quote")
+        }
+
+        MalformedRegex(regex: String) {
+            description("Malformed regular expression")
+            display("Malformed regular expression: '{}'", regex)
+        }
+
+        MalformedDate(date: String) {
+            description("Malformed date")
+            display("Malformed date: '{}'", date)
+        }
+
+        MessageNotInIssue(message: Oid, issue: Oid) {
+            description("The message is not part of the issue")
+            display("Message {} is not part of issue {}", message, issue)
+        }
+
+        CannotAmendInitialMessage(id: Oid) {
+            description("The initial message of an issue cannot be amended")
+            display("Cannot amend {}, which is the initial message of an issue", id)
+        }
+
+        NoPatch(id: Oid) {
+            description("The message does not carry a patch")
+            display("Message {} does not carry a patch", id)
+        }
+
+        UnknownMailAuthor(id: String) {
+            description("The author of an email could not be determined")
+            display("Cannot determine the author of the email '{}'", id)
+        }
+
+        MalformedPolicy(entry: String) {
+            description("Malformed policy entry")
+            display("Malformed policy entry: '{}'", entry)
+        }
+
+        PolicyViolation(reason: String) {
+            description("The operation is not permitted by the policy")
+            display("Policy violation: {}", reason)
+        }
+
+        MalformedWorkflow(entry: String) {
+            description("Malformed workflow entry")
+            display("Malformed workflow entry: '{}'", entry)
+        }
+
+        IllegalStatusTransition(from: String, to: String, legal: Vec<String>) {
+            description("The status transition is not part of the workflow")
+            display("Cannot change the status from '{}' to '{}', legal transitions are: {}", from, to, if legal.is_empty() {
+                "none".to_owned()
+            } else {
+                legal.join(", ")
+            })
+        }
+
+        UnknownSignatureFormat(format: String) {
+            description("Unknown signature format")
+            display("Unknown signature format: '{}'", format)
+        }
+
+        CannotRunProgram(program: String) {
+            description("Cannot run an external program")
+            display("Cannot run '{}'", program)
+        }
+
+        CannotSign {
+            description("Cannot create a signature")
+            display("Cannot create a signature")
+        }
+
         EmptyMessage {
             description("An empty message was supplied")
             display("The message is empty")
@@ -100,6 +218,16 @@ error_chain! {
             display("Empty subject line")
         }
 
+        CannotWriteCache(path: String) {
+            description("Cannot write to the metadata cache")
+            display("Cannot write to the metadata cache at '{}'", path)
+        }
+
+        CannotWriteSearchIndex(path: String) {
+            description("Cannot write to the search index")
+            display("Cannot write to the search index at '{}'", path)
+        }
+
         MalformedMessage {
             description("The message supplied is malformed")
             display("The message supplied is malformed")
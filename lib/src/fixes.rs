@@ -0,0 +1,141 @@
+// git-dit - the distributed issue tracker for git
+// Copyright (C) 2017 Matthias Beyer <mail@beyermatthias.de>
+// Copyright (C) 2017 Julian Ganz <neither@nut.email>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+
+//! Fixes of issues in the regular history
+//!
+//! Ordinary commits, e.g. commits on a project's branches, may declare that
+//! they fix an issue via a `Dit-fixes` trailer in their commit message. The
+//! trailer holds the id of the issue, which may be abbreviated. A commit may
+//! fix multiple issues.
+//!
+//! This module provides functions for finding such commits.
+//!
+
+use git2::{Commit, Oid, Repository, Sort};
+
+use dependency::resolve_issue;
+use message::Message;
+use trailer::spec::COMMIT_FIXES_SPEC;
+
+use error::*;
+
+
+/// A commit declaring to fix an issue
+///
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Fix {
+    /// The fixing commit
+    pub commit: Oid,
+    /// The issue fixed
+    pub issue: Oid,
+}
+
+
+/// Get the ids of the issues a commit declares to fix
+///
+/// Trailer values which don't refer to an issue are ignored.
+///
+pub fn fixed_issues(repo: &Repository, commit: &Commit) -> Vec<Oid> {
+    let mut retval: Vec<Oid> = commit
+        .trailers()
+        .filter(|trailer| trailer.key.as_ref() == COMMIT_FIXES_SPEC.key.as_ref())
+        .filter_map(|trailer| resolve_issue(repo, trailer.value.to_string().trim()))
+        .collect();
+    retval.sort();
+    retval.dedup();
+    retval
+}
+
+
+/// Get the fixes reachable from a set of commits
+///
+/// All commits reachable from the commits supplied, e.g. the tips of some
+/// branches, are scanned for fixes. The fixes are returned in topological
+/// order, starting with the most recent ones.
+///
+pub fn fixes<I>(repo: &Repository, tips: I) -> Result<Vec<Fix>>
+    where I: IntoIterator<Item = Oid>
+{
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(Sort::TOPOLOGICAL)?;
+    for tip in tips {
+        revwalk.push(tip)?;
+    }
+
+    let mut retval = Vec::new();
+    for id in revwalk {
+        let commit = repo.find_commit(id?)?;
+        retval.extend(fixed_issues(repo, &commit)
+            .into_iter()
+            .map(|issue| Fix { commit: commit.id(), issue }));
+    }
+    Ok(retval)
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::TestingRepo;
+
+    use git2;
+    use repository::RepositoryExt;
+
+    #[test]
+    fn branch_fixes() {
+        let mut testing_repo = TestingRepo::new("branch_fixes");
+        let repo = testing_repo.repo();
+
+        let sig = git2::Signature::now("Foo Bar", "foo.bar@example.com")
+            .expect("Could not create signature");
+        let empty_tree = repo
+            .empty_tree()
+            .expect("Could not create empty tree");
+
+        let issue1 = repo
+            .create_issue(&sig, &sig, "Test message 1", &empty_tree, vec![])
+            .expect("Could not create issue");
+        let issue2 = repo
+            .create_issue(&sig, &sig, "Test message 2", &empty_tree, vec![])
+            .expect("Could not create issue");
+
+        let text = format!("Some change\n\nDit-fixes: {:.8}\nDit-fixes: bar", issue1.id());
+        let commit1 = repo
+            .commit(None, &sig, &sig, &text, &empty_tree, &[])
+            .and_then(|id| repo.find_commit(id))
+            .expect("Could not create commit");
+        let commit2 = repo
+            .commit(None, &sig, &sig, "Another change", &empty_tree, &[&commit1])
+            .and_then(|id| repo.find_commit(id))
+            .expect("Could not create commit");
+        let text = format!("Yet another change\n\nDit-fixes: {}", issue2.id());
+        let commit3 = repo
+            .commit(None, &sig, &sig, &text, &empty_tree, &[&commit2])
+            .and_then(|id| repo.find_commit(id))
+            .expect("Could not create commit");
+
+        assert_eq!(fixed_issues(repo, &commit1), vec![issue1.id()]);
+        assert!(fixed_issues(repo, &commit2).is_empty());
+
+        assert_eq!(
+            fixes(repo, vec![commit3.id()]).expect("Could not retrieve fixes"),
+            vec![
+                Fix { commit: commit3.id(), issue: issue2.id() },
+                Fix { commit: commit1.id(), issue: issue1.id() },
+            ]
+        );
+        assert_eq!(
+            fixes(repo, vec![commit2.id()]).expect("Could not retrieve fixes"),
+            vec![Fix { commit: commit1.id(), issue: issue1.id() }]
+        );
+    }
+}
+
@@ -20,6 +20,11 @@ use std::result::Result as RResult;
 use error::*;
 use error::ErrorKind as EK;
 use iter::Messages;
+use message::{Message, append_trailer};
+use repository::RepositoryExt;
+use signature::{self, Signer};
+use trailer::Trailer;
+use trailer::spec::{MESSAGE_PATCH_SPEC, MESSAGE_SUPERSEDES_SPEC};
 
 
 #[derive(PartialEq)]
@@ -205,14 +210,21 @@ impl<'r> Issue<'r> {
     pub fn messages(&self) -> Result<Messages<'r>> {
         self.terminated_messages()
             .and_then(|mut messages| {
-                let glob = format!("**/dit/{}/**", self.ref_part());
-
                 // The iterator will iterate over all the messages in the tree
                 // spanned but it will halt at the initial message.
-                messages
-                    .revwalk
-                    .push_glob(glob.as_ref())
-                    .chain_err(|| EK::CannotGetReferences(glob))?;
+                // NOTE: we don't use `Revwalk::push_glob()` since it prepends
+                //       "refs/" to globs, which makes it impossible to match
+                //       both local and remote references with one glob.
+                for reference in self.all_refs(IssueRefType::Any)? {
+                    let id = reference?
+                        .peel(git2::ObjectType::Commit)
+                        .chain_err(|| EK::CannotGetCommit)?
+                        .id();
+                    messages
+                        .revwalk
+                        .push(id)
+                        .chain_err(|| EK::CannotConstructRevwalk)?;
+                }
 
                 Ok(messages)
             })
@@ -235,6 +247,20 @@ impl<'r> Issue<'r> {
             })
     }
 
+    /// Check whether a message is part of the issue
+    ///
+    /// A message is considered part of the issue if the issue's initial message
+    /// is the first initial message encountered when following the first
+    /// parents of the message.
+    ///
+    pub fn contains_message(&self, message: Oid) -> Result<bool> {
+        let mut initial = None;
+        for item in self.repo.first_parent_messages(message)?.until_any_initial() {
+            initial = Some(item?.id());
+        }
+        Ok(initial == Some(self.id()))
+    }
+
     /// Prepare a Messages iterator which will terminate at the initial message
     ///
     pub fn terminated_messages(&self) -> Result<Messages<'r>> {
@@ -276,6 +302,200 @@ impl<'r> Issue<'r> {
             .and_then(|message| self.add_leaf(message.id()).map(|_| message))
     }
 
+    /// Add a new signed message to the issue
+    ///
+    /// Like `add_message()`, but the message is signed using the signer
+    /// supplied.
+    ///
+    pub fn add_signed_message<'a, A, I, J, S>(&self,
+                                              author: &git2::Signature,
+                                              committer: &git2::Signature,
+                                              message: A,
+                                              tree: &git2::Tree,
+                                              parents: I,
+                                              signer: &S
+    ) -> Result<Commit<'r>>
+        where A: AsRef<str>,
+              I: IntoIterator<Item = &'a Commit<'a>, IntoIter = J>,
+              J: Iterator<Item = &'a Commit<'a>>,
+              S: Signer + ?Sized
+    {
+        let parent_vec : Vec<&Commit> = parents.into_iter().collect();
+
+        signature::create_signed_commit(self.repo, author, committer, message.as_ref(), tree, &parent_vec, signer)
+            .and_then(|id| self.repo.find_commit(id).chain_err(|| EK::CannotCreateMessage))
+            .and_then(|message| self.add_leaf(message.id()).map(|_| message))
+    }
+
+    /// Amend a message of the issue
+    ///
+    /// Adds a new message with the same parents and tree as the original
+    /// message, superseding the original. A `Dit-supersedes` trailer referring
+    /// to the original message is appended to the text supplied.
+    ///
+    /// A leaf reference is created for the new message. If the original message
+    /// is referred to by the local head, the head is moved to the new message
+    /// and a leaf reference is created for the original message. Leaf
+    /// references to the original message are kept, so the original message
+    /// stays reachable as a revision of the new one. Remote references as well
+    /// as replies to the original message will, naturally, still refer to the
+    /// original message.
+    ///
+    /// The initial message of an issue cannot be amended. If a signer is
+    /// supplied, the new message is signed.
+    ///
+    pub fn amend_message<A>(&self,
+                            author: &git2::Signature,
+                            committer: &git2::Signature,
+                            message: A,
+                            original: &Commit,
+                            signer: Option<&dyn Signer>
+    ) -> Result<Commit<'r>>
+        where A: AsRef<str>
+    {
+        if original.id() == self.id() {
+            return Err(Error::from_kind(EK::CannotAmendInitialMessage(original.id())));
+        }
+
+        let trailer = Trailer::new(MESSAGE_SUPERSEDES_SPEC.key.as_ref(), original.id().to_string().as_str());
+        let text = append_trailer(message.as_ref(), &trailer);
+
+        let tree = original.tree().chain_err(|| EK::CannotGetCommit)?;
+        let parents: Vec<Commit> = original.parents().collect();
+        let amended = match signer {
+            Some(signer) => self.add_signed_message(author, committer, text, &tree, parents.iter(), signer)?,
+            None => self.add_message(author, committer, text, &tree, parents.iter())?,
+        };
+
+        // move the local head, keeping the original message reachable
+        let head = self.local_head().ok().and_then(|head| head.target());
+        if head == Some(original.id()) {
+            let leafname = format!("refs/dit/{}/leaves/{}", self.ref_part(), original.id());
+            if self.repo.find_reference(&leafname).is_err() {
+                self.add_leaf(original.id())?;
+            }
+            self.update_head(amended.id(), true)?;
+        }
+
+        Ok(amended)
+    }
+
+    /// Add a series of patches to the issue
+    ///
+    /// For each of the commits supplied, a message carrying the commit is
+    /// created. The message has the same author and tree as the commit and
+    /// refers to the commit via its second parent as well as a `Dit-patch`
+    /// trailer. Its text is the commit's message, with the subject prefixed by
+    /// `[PATCH <i>/<n>]`. The first message replies to the message supplied and
+    /// each subsequent message replies to its predecessor.
+    ///
+    /// A leaf reference is created for the last message. If a signer is
+    /// supplied, the messages are signed. The messages are returned in the
+    /// order of the commits supplied.
+    ///
+    pub fn add_patches<'a, I>(&self,
+                              committer: &git2::Signature,
+                              parent: &Commit,
+                              commits: I,
+                              signer: Option<&dyn Signer>
+    ) -> Result<Vec<Commit<'r>>>
+        where I: IntoIterator<Item = &'a Commit<'a>>
+    {
+        let commits: Vec<&Commit> = commits.into_iter().collect();
+
+        let mut messages: Vec<Commit<'r>> = Vec::new();
+        for (number, commit) in commits.iter().enumerate() {
+            let prefix = if commits.len() == 1 {
+                "[PATCH]".to_owned()
+            } else {
+                format!("[PATCH {}/{}]", number + 1, commits.len())
+            };
+            let mut lines = commit.message_lines();
+            let subject = lines.next().unwrap_or_default();
+            let body: Vec<String> = lines.skip_while(|line| line.trim().is_empty()).collect();
+            let text = format!("{} {}\n\n{}", prefix, subject, body.join("\n"));
+            let trailer = Trailer::new(MESSAGE_PATCH_SPEC.key.as_ref(), commit.id().to_string().as_str());
+
+            let tree = commit.tree().chain_err(|| EK::CannotGetCommit)?;
+            let previous: &Commit = messages.last().unwrap_or(parent);
+            let text = append_trailer(&text, &trailer);
+            let message = self.create_commit(&commit.author(), committer, &text, &tree, &[previous, commit], signer)?;
+            messages.push(message);
+        }
+
+        if let Some(last) = messages.last() {
+            self.add_leaf(last.id())?;
+        }
+        Ok(messages)
+    }
+
+    /// Set the local head reference of the issue to a message
+    ///
+    /// Unlike `update_head()`, this function makes sure that the message is
+    /// part of the issue.
+    ///
+    pub fn set_head(&self, message: Oid) -> Result<Reference<'r>> {
+        if !self.contains_message(message)? {
+            return Err(Error::from_kind(EK::MessageNotInIssue(message, self.id())));
+        }
+        self.update_head(message, true)
+    }
+
+    /// Merge a message into the head of the issue
+    ///
+    /// Adds a new message with the head supplied as first parent and the
+    /// message to merge as second parent and sets the local head reference to
+    /// the new message. This way, a maintainer may maintain a sequence of
+    /// status messages independent from the discussion, referring to the
+    /// messages inspiring the status changes.
+    ///
+    /// Both the head and the message to merge have to be part of the issue.
+    /// Note that only the trailers of the new message and its first parents
+    /// are considered for the issue's metadata. Hence, the new message should
+    /// carry any metadata to adopt from the merged message. If a signer is
+    /// supplied, the new message is signed.
+    ///
+    pub fn merge_into_head<A>(&self,
+                              author: &git2::Signature,
+                              committer: &git2::Signature,
+                              message: A,
+                              head: &Commit,
+                              merged: &Commit,
+                              signer: Option<&dyn Signer>
+    ) -> Result<Commit<'r>>
+        where A: AsRef<str>
+    {
+        for &id in &[head.id(), merged.id()] {
+            if !self.contains_message(id)? {
+                return Err(Error::from_kind(EK::MessageNotInIssue(id, self.id())));
+            }
+        }
+
+        let tree = self.repo.empty_tree()?;
+        let new = self.create_commit(author, committer, message.as_ref(), &tree, &[head, merged], signer)?;
+        self.update_head(new.id(), true)?;
+        Ok(new)
+    }
+
+    /// Create a commit, signed if a signer is supplied
+    ///
+    /// No reference is created or updated.
+    ///
+    fn create_commit(&self,
+                     author: &git2::Signature,
+                     committer: &git2::Signature,
+                     message: &str,
+                     tree: &git2::Tree,
+                     parents: &[&Commit],
+                     signer: Option<&dyn Signer>
+    ) -> Result<Commit<'r>> {
+        let id = match signer {
+            Some(signer) => signature::create_signed_commit(self.repo, author, committer, message, tree, parents, signer)?,
+            None => self.repo.commit(None, author, committer, message, tree, parents).chain_err(|| EK::CannotCreateMessage)?,
+        };
+        self.repo.find_commit(id).chain_err(|| EK::CannotCreateMessage)
+    }
+
     /// Update the local head reference of the issue
     ///
     /// Updates the local head reference of the issue to the provided message.
@@ -537,5 +757,158 @@ mod tests {
             .expect("Could not update head reference");
         assert_eq!(issue.local_head().unwrap().target().unwrap(), message.id());
     }
+
+    #[test]
+    fn set_head() {
+        let mut testing_repo = TestingRepo::new("set_head");
+        let repo = testing_repo.repo();
+
+        let sig = git2::Signature::now("Foo Bar", "foo.bar@example.com")
+            .expect("Could not create signature");
+        let empty_tree = repo
+            .empty_tree()
+            .expect("Could not create empty tree");
+
+        let issue1 = repo
+            .create_issue(&sig, &sig, "Test message 1", &empty_tree, vec![])
+            .expect("Could not create issue");
+        let issue2 = repo
+            .create_issue(&sig, &sig, "Test message 2", &empty_tree, vec![])
+            .expect("Could not create issue");
+        let initial_message = issue1
+            .initial_message()
+            .expect("Could not retrieve initial message");
+        let message1 = issue1
+            .add_message(&sig, &sig, "Test message 3", &empty_tree, vec![&initial_message])
+            .expect("Could not add message");
+        let message2 = issue1
+            .add_message(&sig, &sig, "Test message 4\n\nDit-status: closed", &empty_tree, vec![&initial_message])
+            .expect("Could not add message");
+
+        assert!(issue1.contains_message(message1.id()).unwrap());
+        assert!(!issue2.contains_message(message1.id()).unwrap());
+
+        assert!(issue2.set_head(message1.id()).is_err());
+        issue1
+            .set_head(message1.id())
+            .expect("Could not set head reference");
+        assert_eq!(issue1.local_head().unwrap().target().unwrap(), message1.id());
+
+        let issue2_initial = issue2
+            .initial_message()
+            .expect("Could not retrieve initial message");
+        assert!(issue1
+            .merge_into_head(&sig, &sig, "Test message 5", &message1, &issue2_initial, None)
+            .is_err());
+        let merge = issue1
+            .merge_into_head(&sig, &sig, "Test message 5\n\nDit-status: closed", &message1, &message2, None)
+            .expect("Could not merge message");
+        assert_eq!(merge.parent_ids().collect::<Vec<_>>(), vec![message1.id(), message2.id()]);
+        assert_eq!(issue1.local_head().unwrap().target().unwrap(), merge.id());
+    }
+
+    #[test]
+    fn amend_message() {
+        use message::Message;
+
+        let mut testing_repo = TestingRepo::new("amend_message");
+        let repo = testing_repo.repo();
+
+        let sig = git2::Signature::now("Foo Bar", "foo.bar@example.com")
+            .expect("Could not create signature");
+        let empty_tree = repo
+            .empty_tree()
+            .expect("Could not create empty tree");
+
+        let issue = repo
+            .create_issue(&sig, &sig, "Test message 1", &empty_tree, vec![])
+            .expect("Could not create issue");
+        let initial_message = issue
+            .initial_message()
+            .expect("Could not retrieve initial message");
+        let message = issue
+            .add_message(&sig, &sig, "Test message 2\n\nDit-status: open", &empty_tree, vec![&initial_message])
+            .expect("Could not add message");
+        issue
+            .update_head(message.id(), true)
+            .expect("Could not update head reference");
+
+        assert!(issue.amend_message(&sig, &sig, "Test message 3", &initial_message, None).is_err());
+
+        let amended = issue
+            .amend_message(&sig, &sig, "Test message 2\n\nDit-status: closed", &message, None)
+            .expect("Could not amend message");
+        assert_eq!(amended.parent_ids().collect::<Vec<_>>(), vec![issue.id()]);
+        assert_eq!(
+            amended.trailers().map(|t| t.to_string()).collect::<Vec<_>>(),
+            vec![
+                "Dit-status: closed".to_owned(),
+                format!("Dit-supersedes: {}", message.id()),
+            ]
+        );
+
+        assert_eq!(issue.local_head().unwrap().target().unwrap(), amended.id());
+        let mut leaves: Vec<Oid> = issue
+            .local_refs(IssueRefType::Leaf)
+            .expect("Could not retrieve issue leaves")
+            .map(|reference| reference.unwrap().target().unwrap())
+            .collect();
+        leaves.sort();
+        let mut expected = vec![message.id(), amended.id()];
+        expected.sort();
+        assert_eq!(leaves, expected);
+    }
+
+    #[test]
+    fn amend_message_keeps_original() {
+        use revision::Revisions;
+
+        let mut testing_repo = TestingRepo::new("amend_message_keeps_original");
+        let repo = testing_repo.repo();
+
+        let sig = git2::Signature::now("Foo Bar", "foo.bar@example.com")
+            .expect("Could not create signature");
+        let empty_tree = repo
+            .empty_tree()
+            .expect("Could not create empty tree");
+
+        let issue = repo
+            .create_issue(&sig, &sig, "Test message 1", &empty_tree, vec![])
+            .expect("Could not create issue");
+        let initial_message = issue
+            .initial_message()
+            .expect("Could not retrieve initial message");
+        let message = issue
+            .add_message(&sig, &sig, "Test message 2", &empty_tree, vec![&initial_message])
+            .expect("Could not add message");
+        issue
+            .update_head(message.id(), true)
+            .expect("Could not update head reference");
+
+        // remove the leaf, leaving the original referred to only by the head
+        issue
+            .local_refs(IssueRefType::Leaf)
+            .expect("Could not retrieve issue leaves")
+            .for_each(|reference| reference.unwrap().delete().expect("Could not delete leaf"));
+
+        let amended = issue
+            .amend_message(&sig, &sig, "Test message 3", &message, None)
+            .expect("Could not amend message");
+
+        let messages: Vec<Commit> = issue
+            .messages()
+            .expect("Could not retrieve messages")
+            .collect::<RResult<_, _>>()
+            .expect("Could not retrieve message");
+        assert!(messages.iter().any(|m| m.id() == message.id()));
+
+        let revisions = Revisions::new(&messages);
+        assert_eq!(revisions.original(amended.id()), message.id());
+        let mut expected = vec![message.id(), amended.id()];
+        expected.sort();
+        let mut found = revisions.revisions(amended.id());
+        found.sort();
+        assert_eq!(found, expected);
+    }
 }
 
@@ -132,9 +132,23 @@ pub trait MessagesExt {
     /// This function accumulates all specified trailers from the messages
     /// returned by the iterator.
     ///
-    fn accumulate_trailers<'a, I, J>(self, specs: I) -> Self::Output
+    fn accumulate_trailers<I, J>(self, specs: I) -> Self::Output
         where I: IntoIterator<Item = J>,
-              J: Borrow<spec::TrailerSpec<'a>>;
+              J: Borrow<spec::TrailerSpec>;
+
+    /// Accumulate trailers of authorized messages
+    ///
+    /// This function accumulates the specified trailers from those messages
+    /// returned by the iterator which are authorized by the authorization
+    /// supplied. Like `accumulate_trailers()`, this function expects the
+    /// messages to be returned starting with the most recent one, e.g. by
+    /// `Issue::messages_from()`. The assignee is tracked using trailers with
+    /// the key supplied.
+    ///
+    fn accumulate_authorized_trailers<I, J, A>(self, specs: I, authorization: A, assignee_key: &str) -> Result<Self::Output>
+        where I: IntoIterator<Item = J>,
+              J: Borrow<spec::TrailerSpec>,
+              A: accumulation::Authorization;
 }
 
 impl<'a, I> MessagesExt for I
@@ -142,9 +156,9 @@ impl<'a, I> MessagesExt for I
 {
     type Output = HashMap<String, accumulation::ValueAccumulator>;
 
-    fn accumulate_trailers<'b, J, K>(self, specs: J) -> Self::Output
+    fn accumulate_trailers<J, K>(self, specs: J) -> Self::Output
         where J: IntoIterator<Item = K>,
-              K: Borrow<spec::TrailerSpec<'b>>
+              K: Borrow<spec::TrailerSpec>
     {
         use message::Message;
         use trailer::accumulation::Accumulator;
@@ -154,6 +168,28 @@ impl<'a, I> MessagesExt for I
         accumulator.process_all(self.flat_map(|message| message.trailers()));
         accumulator
     }
+
+    fn accumulate_authorized_trailers<J, K, A>(self, specs: J, authorization: A, assignee_key: &str) -> Result<Self::Output>
+        where J: IntoIterator<Item = K>,
+              K: Borrow<spec::TrailerSpec>,
+              A: accumulation::Authorization
+    {
+        use message::Message;
+        use trailer::spec::ToMap;
+
+        let mut messages: Vec<_> = self.collect();
+        messages.reverse();
+
+        let mut accumulator = accumulation::AuthorizingAccumulator::new(
+            specs.into_map(),
+            authorization,
+            assignee_key.to_owned()
+        );
+        for message in messages {
+            accumulator.process_message(&message, message.trailers())?;
+        }
+        Ok(accumulator.into_inner())
+    }
 }
 
 
@@ -458,5 +494,145 @@ mod tests {
         refs_to_report.sort();
         assert_eq!(reported, refs_to_report);
     }
+
+    // MessagesExt tests
+
+    #[test]
+    fn authorized_accumulation() {
+        use trailer::accumulation::{AccumulationPolicy, AuthorizedPeople};
+        use trailer::spec::TrailerSpec;
+        use utils::ResultIterExt;
+
+        let mut testing_repo = TestingRepo::new("authorized_accumulation");
+        let repo = testing_repo.repo();
+
+        let reporter = git2::Signature::now("Foo Bar", "foo.bar@example.com")
+            .expect("Could not create signature");
+        let assignee = git2::Signature::now("Baz Qux", "baz.qux@example.com")
+            .expect("Could not create signature");
+        let maintainer = git2::Signature::now("Mee Seeks", "meeseeks@rm.com")
+            .expect("Could not create signature");
+        let other = git2::Signature::now("Rick Sanchez", "rick@rm.com")
+            .expect("Could not create signature");
+        let empty_tree = repo
+            .empty_tree()
+            .expect("Could not create empty tree");
+
+        let issue = repo
+            .create_issue(&reporter, &reporter, "Test message 1\n\nDit-status: open", &empty_tree, vec![])
+            .expect("Could not create issue");
+        let mut head = issue
+            .initial_message()
+            .expect("Could not retrieve initial message");
+        for &(sig, text) in &[
+            (&other, "Test message 2\n\nDit-status: closed"),
+            (&maintainer, "Test message 3\n\nDit-assignee: Baz Qux <baz.qux@example.com>"),
+            (&assignee, "Test message 4\n\nDit-status: in-progress"),
+            (&other, "Test message 5\n\nDit-assignee: Rick Sanchez <rick@rm.com>"),
+        ] {
+            head = issue
+                .add_message(sig, sig, text, &empty_tree, vec![&head])
+                .expect("Could not add message");
+        }
+
+        let specs = vec![
+            TrailerSpec::new("Dit-status", AccumulationPolicy::List),
+            TrailerSpec::new("Dit-assignee", AccumulationPolicy::Latest),
+        ];
+        let values = |authorization: AuthorizedPeople| -> Vec<Vec<String>> {
+            let mut acc = issue
+                .messages_from(head.id())
+                .expect("Could not retrieve messages")
+                .collect_result::<Vec<_>>()
+                .expect("Could not retrieve messages")
+                .into_iter()
+                .accumulate_authorized_trailers(&specs, authorization, "Dit-assignee")
+                .expect("Could not accumulate trailers");
+            vec!["Dit-status", "Dit-assignee"]
+                .into_iter()
+                .map(|key| acc
+                    .remove(key)
+                    .expect("Could not retrieve values")
+                    .into_iter()
+                    .map(|value| value.to_string())
+                    .collect())
+                .collect()
+        };
+
+        assert_eq!(values(AuthorizedPeople::new(vec!["Mee Seeks <meeseeks@rm.com>"])), vec![
+            vec![],
+            vec!["Baz Qux <baz.qux@example.com>".to_owned()],
+        ]);
+        assert_eq!(values(AuthorizedPeople::new(vec!["Mee Seeks <meeseeks@rm.com>"]).with_reporter()), vec![
+            vec!["open".to_owned()],
+            vec!["Baz Qux <baz.qux@example.com>".to_owned()],
+        ]);
+        assert_eq!(values(AuthorizedPeople::new(vec!["Mee Seeks <meeseeks@rm.com>"]).with_assignee()), vec![
+            vec!["in-progress".to_owned()],
+            vec!["Baz Qux <baz.qux@example.com>".to_owned()],
+        ]);
+        assert_eq!(values(AuthorizedPeople::new(vec!["Rick Sanchez <rick@rm.com>"])), vec![
+            vec!["closed".to_owned()],
+            vec!["Rick Sanchez <rick@rm.com>".to_owned()],
+        ]);
+    }
+
+    #[test]
+    fn unset_accumulation() {
+        use query::Expr;
+        use std::str::FromStr;
+        use trailer::Trailer;
+        use trailer::accumulation::AccumulationPolicy;
+        use trailer::spec::TrailerSpec;
+        use trailer::types::ValueType;
+        use utils::ResultIterExt;
+
+        let mut testing_repo = TestingRepo::new("unset_accumulation");
+        let repo = testing_repo.repo();
+
+        let sig = git2::Signature::now("Foo Bar", "foo.bar@example.com")
+            .expect("Could not create signature");
+        let empty_tree = repo
+            .empty_tree()
+            .expect("Could not create empty tree");
+
+        let spec = TrailerSpec::new("Dit-assignee", AccumulationPolicy::Latest)
+            .with_type(ValueType::Identity);
+        assert!(spec.parse_value("Foo Bar").is_err());
+
+        let issue = repo
+            .create_issue(&sig, &sig, "Test message 1", &empty_tree, vec![])
+            .expect("Could not create issue");
+        let mut head = issue
+            .initial_message()
+            .expect("Could not retrieve initial message");
+
+        // Whether the issue matches the filters "assignee" and "!assignee"
+        let matches = |head: git2::Oid| -> (bool, bool) {
+            let acc = issue
+                .messages_from(head)
+                .expect("Could not retrieve messages")
+                .collect_result::<Vec<_>>()
+                .expect("Could not retrieve messages")
+                .into_iter()
+                .accumulate_trailers(vec![&spec]);
+            let evaluate = |query: &str| Expr::from_str(query)
+                .expect("Could not parse query")
+                .evaluate(&mut |c| acc
+                    .get(spec.key.as_ref())
+                    .is_some_and(|values| c.matcher.matches_any(values.clone())));
+            (evaluate("assignee"), evaluate("!assignee"))
+        };
+        assert_eq!(matches(head.id()), (false, true));
+
+        for &(value, expected) in &[("Foo Bar <foo.bar@example.com>", (true, false)), ("", (false, true))] {
+            let value = spec.parse_value(value).expect("Could not parse value");
+            let text = format!("Test message\n\n{}", Trailer::new(spec.key.as_ref(), &value.to_string()));
+            head = issue
+                .add_message(&sig, &sig, text, &empty_tree, vec![&head])
+                .expect("Could not add message");
+            assert_eq!(matches(head.id()), expected);
+        }
+    }
 }
 
@@ -36,17 +36,33 @@
 
 #[macro_use] extern crate error_chain;
 #[macro_use] extern crate lazy_static;
+extern crate base64;
+extern crate charset;
+extern crate chrono;
 extern crate git2;
+extern crate quoted_printable;
 extern crate regex;
 
+pub mod cache;
+pub mod dependency;
 pub mod error;
+pub mod fixes;
 pub mod gc;
 pub mod issue;
 pub mod iter;
+pub mod links;
+pub mod mbox;
 pub mod message;
+pub mod patch;
+pub mod policy;
+pub mod query;
 pub mod remote;
 pub mod repository;
+pub mod revision;
+pub mod search;
+pub mod signature;
 pub mod trailer;
+pub mod workflow;
 
 mod utils;
 
@@ -0,0 +1,225 @@
+// git-dit - the distributed issue tracker for git
+// Copyright (C) 2017 Matthias Beyer <mail@beyermatthias.de>
+// Copyright (C) 2017 Julian Ganz <neither@nut.email>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+
+//! Links between issues
+//!
+//! A message may refer to other commits, e.g. messages of other issues. Such
+//! references are either expressed via parents beyond the first one or via
+//! `Dit-ref` trailers holding the id of the commit referred to. The commit
+//! carried by a message with a `Dit-patch` trailer is not considered a
+//! reference.
+//!
+//! This module provides functions for resolving references to messages of
+//! other issues, e.g. the issues referred to by an issue, and for finding
+//! backlinks, e.g. the issues referring to an issue.
+//!
+
+use git2::{Commit, Oid, Repository};
+use std::collections::{HashMap, HashSet};
+
+use issue::Issue;
+use message::Message;
+use repository::RepositoryExt;
+use trailer::spec::{MESSAGE_PATCH_SPEC, MESSAGE_REF_SPEC};
+use utils::ResultIterExt;
+
+use error::*;
+
+
+/// A link from a message of one issue to a message of another issue
+///
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Link {
+    /// The issue containing the referring message
+    pub source: Oid,
+    /// The referring message
+    pub message: Oid,
+    /// The issue referred to
+    pub target: Oid,
+    /// The message referred to
+    pub target_message: Oid,
+}
+
+
+/// Get the ids of the commits referred to by a message
+///
+/// The commits returned may or may not be messages. Trailer values which do
+/// not consist of a hexadecimal id or don't refer to a commit are ignored.
+///
+pub fn referenced_commits(repo: &Repository, message: &Commit) -> Vec<Oid> {
+    let trailers: Vec<_> = message.trailers().collect();
+    let patch = trailers
+        .iter()
+        .find(|trailer| trailer.key.as_ref() == MESSAGE_PATCH_SPEC.key.as_ref())
+        .map(|trailer| trailer.value.to_string().trim().to_owned());
+
+    let parents = message
+        .parent_ids()
+        .skip(1)
+        .filter(|id| patch.as_ref() != Some(&id.to_string()));
+    let referred = trailers
+        .iter()
+        .filter(|trailer| trailer.key.as_ref() == MESSAGE_REF_SPEC.key.as_ref())
+        .filter_map(|trailer| resolve_id(repo, trailer.value.to_string().trim()));
+
+    let mut retval: Vec<Oid> = parents.chain(referred).collect();
+    retval.sort();
+    retval.dedup();
+    retval
+}
+
+
+/// Get the links from an issue to other issues
+///
+/// All messages of the issue are considered. References to messages of the
+/// same issue and to commits which are not messages are ignored.
+///
+pub fn links(repo: &Repository, issue: &Issue) -> Result<Vec<Link>> {
+    let messages: Vec<Commit> = issue.messages()?.collect_result()?;
+    let own: HashSet<Oid> = messages.iter().map(Commit::id).collect();
+
+    // resolving a commit to an issue may be expensive, hence we cache results
+    let mut issues: HashMap<Oid, Option<Oid>> = HashMap::new();
+    let mut retval = Vec::new();
+    for message in messages.iter() {
+        for id in referenced_commits(repo, message) {
+            if own.contains(&id) {
+                continue;
+            }
+            let target = match issues.get(&id) {
+                Some(target) => *target,
+                None => {
+                    let target = repo
+                        .find_commit(id)
+                        .ok()
+                        .and_then(|commit| repo.issue_with_message(&commit).ok())
+                        .map(|issue| issue.id());
+                    issues.insert(id, target);
+                    target
+                },
+            };
+            if let Some(target) = target {
+                retval.push(Link { source: issue.id(), message: message.id(), target, target_message: id });
+            }
+        }
+    }
+    retval.sort();
+    Ok(retval)
+}
+
+
+/// Get the links from other issues to an issue
+///
+/// Only the messages of the issues supplied are considered as sources of
+/// links, e.g. all issues of a repository.
+///
+pub fn backlinks<'a, 'r: 'a, I>(repo: &Repository, issue: &Issue, issues: I) -> Result<Vec<Link>>
+    where I: IntoIterator<Item = &'a Issue<'r>>
+{
+    let targets: HashSet<Oid> = issue.messages()?.map(|m| m.map(|m| m.id())).collect_result()?;
+
+    let mut retval = Vec::new();
+    for source in issues.into_iter().filter(|source| source.id() != issue.id()) {
+        for message in source.messages()? {
+            let message = message?;
+            for id in referenced_commits(repo, &message) {
+                if targets.contains(&id) {
+                    retval.push(Link {
+                        source: source.id(),
+                        message: message.id(),
+                        target: issue.id(),
+                        target_message: id,
+                    });
+                }
+            }
+        }
+    }
+    retval.sort();
+    Ok(retval)
+}
+
+
+/// Resolve a hexadecimal, possibly abbreviated id to the id of a commit
+///
+fn resolve_id(repo: &Repository, id: &str) -> Option<Oid> {
+    if id.len() < 4 || !id.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    repo.revparse_single(id)
+        .and_then(|object| object.peel_to_commit())
+        .map(|commit| commit.id())
+        .ok()
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::TestingRepo;
+
+    use git2;
+
+    #[test]
+    fn issue_links() {
+        let mut testing_repo = TestingRepo::new("issue_links");
+        let repo = testing_repo.repo();
+
+        let sig = git2::Signature::now("Foo Bar", "foo.bar@example.com")
+            .expect("Could not create signature");
+        let empty_tree = repo
+            .empty_tree()
+            .expect("Could not create empty tree");
+
+        let issue1 = repo
+            .create_issue(&sig, &sig, "Test message 1", &empty_tree, vec![])
+            .expect("Could not create issue");
+        let initial1 = issue1
+            .initial_message()
+            .expect("Could not retrieve initial message");
+        let issue2 = repo
+            .create_issue(&sig, &sig, "Test message 2", &empty_tree, vec![])
+            .expect("Could not create issue");
+        let initial2 = issue2
+            .initial_message()
+            .expect("Could not retrieve initial message");
+        let issue3 = repo
+            .create_issue(&sig, &sig, "Test message 3", &empty_tree, vec![])
+            .expect("Could not create issue");
+        let initial3 = issue3
+            .initial_message()
+            .expect("Could not retrieve initial message");
+
+        // issue 2 refers to issue 1 via a parent and to issue 3 via a trailer
+        let text = format!("Test message 4\n\nDit-ref: {:.10}", initial3.id());
+        let reply = issue2
+            .add_message(&sig, &sig, &text, &empty_tree, vec![&initial2, &initial1])
+            .expect("Could not add message");
+        let mut expected = vec![initial1.id(), initial3.id()];
+        expected.sort();
+        assert_eq!(referenced_commits(repo, &reply), expected);
+
+        let mut expected = vec![
+            Link { source: issue2.id(), message: reply.id(), target: issue1.id(), target_message: initial1.id() },
+            Link { source: issue2.id(), message: reply.id(), target: issue3.id(), target_message: initial3.id() },
+        ];
+        expected.sort();
+        assert_eq!(links(repo, &issue2).expect("Could not retrieve links"), expected);
+        assert!(links(repo, &issue1).expect("Could not retrieve links").is_empty());
+
+        let issues = [&issue1, &issue2, &issue3];
+        assert_eq!(
+            backlinks(repo, &issue1, issues.iter().cloned()).expect("Could not retrieve backlinks"),
+            vec![Link { source: issue2.id(), message: reply.id(), target: issue1.id(), target_message: initial1.id() }]
+        );
+        assert!(backlinks(repo, &issue2, issues.iter().cloned()).expect("Could not retrieve backlinks").is_empty());
+    }
+}
+
@@ -0,0 +1,940 @@
+// git-dit - the distributed issue tracker for git
+// Copyright (C) 2017 Matthias Beyer <mail@beyermatthias.de>
+// Copyright (C) 2017 Julian Ganz <neither@nut.email>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+
+//! Mailing-list import and export
+//!
+//! This module provides utilities for importing emails, e.g. from the archive
+//! of a mailing list, as issues and messages, and for exporting messages as
+//! emails. Emails are read from and written to mailboxes in the mbox format,
+//! which is also produced by `git format-patch` and accepted by
+//! `git send-email`.
+//!
+//! The threading of emails, expressed via the `In-Reply-To` and `References`
+//! headers, is mapped to the reply structure of messages: an email replying to
+//! another email is imported as a message whose first parent is the message
+//! imported from that email. Emails which don't reply to a known email are
+//! imported as new issues. The author and date of an email are preserved.
+//!
+//! Each imported message carries a `Message-Id` trailer holding the id of the
+//! original email. Emails which were already imported are recognized by this
+//! trailer and skipped, which makes imports idempotent. Emails without a
+//! `Message-Id` header are imported nonetheless, but cannot be recognized.
+//!
+//! Exported emails carry the id of the email a message was imported from or,
+//! if the message was not imported, an id derived from the message's id. The
+//! `In-Reply-To` and `References` headers are derived from the message's first
+//! parents. Hence, exporting an issue repeatedly yields the same emails.
+//!
+//! Only the first `text/plain` part of a multipart email is imported. Bodies
+//! which are neither quoted-printable nor base64 encoded are assumed to be
+//! UTF-8 encoded.
+//!
+
+use charset::Charset;
+use git2::{self, Commit, Oid, Signature, Time};
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::mem;
+
+use error::*;
+use error::ErrorKind as EK;
+use issue::Issue;
+use message::{Message, append_trailer};
+use repository::RepositoryExt;
+use signature::Signer;
+use trailer::Trailer;
+use trailer::spec::MESSAGE_ID_SPEC;
+use utils::{format_mail_date, format_mbox_date, parse_mail_date};
+
+
+/// Email
+///
+/// This type represents the parts of an email relevant for importing it as a
+/// message.
+///
+#[derive(Clone)]
+pub struct Mail {
+    /// The id of the email, including the angle brackets
+    pub message_id: Option<String>,
+    /// The id of the email this email replies to
+    pub in_reply_to: Option<String>,
+    /// The ids of the email's ancestors in the thread, oldest first
+    pub references: Vec<String>,
+    pub author_name: Option<String>,
+    pub author_email: Option<String>,
+    pub date: Option<Time>,
+    pub subject: Option<String>,
+    /// The decoded body
+    pub body: String,
+}
+
+impl Mail {
+    /// Parse a single email
+    ///
+    /// Headers are decoded, including MIME encoded-words. Malformed or missing
+    /// headers result in the respective fields being `None` or empty.
+    ///
+    pub fn parse(text: &str) -> Self {
+        let (headers, body) = split_headers(text);
+        let header = |name| find_header(&headers, name);
+
+        let (author_name, author_email) = header("From")
+            .map(|from| parse_address(&decode_words(from)))
+            .unwrap_or((None, None));
+
+        Mail {
+            message_id: header("Message-Id").and_then(|ids| message_ids(ids).into_iter().next()),
+            in_reply_to: header("In-Reply-To").and_then(|ids| message_ids(ids).into_iter().next()),
+            references: header("References").map(message_ids).unwrap_or_default(),
+            author_name,
+            author_email,
+            date: header("Date")
+                .and_then(parse_mail_date)
+                .map(|(seconds, offset)| Time::new(seconds, offset)),
+            subject: header("Subject").map(|subject| decode_words(subject).trim().to_owned()),
+            body: decode_body(&headers, body),
+        }
+    }
+
+    /// Create an email from a message of an issue
+    ///
+    /// The email replies to the email created from the message's first parent,
+    /// unless the message is the issue's initial message. A `Message-Id`
+    /// trailer carried by the message is used as the email's id and removed
+    /// from the body.
+    ///
+    pub fn from_message(issue: &Issue, message: &Commit) -> Result<Self> {
+        let message_id = mail_id(message);
+
+        // collect the ids of the ancestors, up to the issue's initial message
+        let mut references = Vec::new();
+        let mut current = message.clone();
+        while current.id() != issue.id() {
+            current = match current.parents().next() {
+                Some(parent) => parent,
+                None => break,
+            };
+            references.push(mail_id(&current));
+        }
+        references.reverse();
+
+        let trailer = Trailer::new(MESSAGE_ID_SPEC.key.as_ref(), message_id.as_str()).to_string();
+        let body: Vec<String> = message
+            .body_lines()
+            .filter(|line| *line != trailer)
+            .collect();
+
+        let author = message.author();
+        Ok(Mail {
+            in_reply_to: references.last().cloned(),
+            message_id: Some(message_id),
+            references,
+            author_name: author.name().map(String::from),
+            author_email: author.email().map(String::from),
+            date: Some(author.when()),
+            subject: message.message_lines().next(),
+            body: body.join("\n").trim_end().to_owned(),
+        })
+    }
+
+    /// Render the email as an entry of a mailbox in the mbox format
+    ///
+    /// The entry consists of a separator line, the headers and the body, which
+    /// is transferred as 8bit UTF-8 text. Lines in the body which would be
+    /// mistaken for separators are escaped.
+    ///
+    pub fn to_mbox(&self) -> String {
+        let date = self.date.unwrap_or_else(|| Time::new(0, 0));
+        let email = self.author_email.as_deref().unwrap_or("MAILER-DAEMON");
+
+        let mut retval = format!("From {} {}\n", email, format_mbox_date(date.seconds()));
+        match self.author_name {
+            Some(ref name) => retval.push_str(&format!("From: {} <{}>\n", encode_phrase(name), email)),
+            None => retval.push_str(&format!("From: {}\n", email)),
+        }
+        retval.push_str(&format!("Date: {}\n", format_mail_date(date.seconds(), date.offset_minutes())));
+        retval.push_str(&format!("Subject: {}\n", encode_words(self.subject.as_deref().unwrap_or(""))));
+        if let Some(ref id) = self.message_id {
+            retval.push_str(&format!("Message-Id: {}\n", id));
+        }
+        if let Some(ref id) = self.in_reply_to {
+            retval.push_str(&format!("In-Reply-To: {}\n", id));
+        }
+        if !self.references.is_empty() {
+            retval.push_str(&format!("References: {}\n", self.references.join("\n ")));
+        }
+        retval.push_str("MIME-Version: 1.0\n");
+        retval.push_str("Content-Type: text/plain; charset=utf-8\n");
+        retval.push_str("Content-Transfer-Encoding: 8bit\n");
+        retval.push('\n');
+
+        for line in self.body.lines() {
+            if line.trim_start_matches('>').starts_with("From ") {
+                retval.push('>');
+            }
+            retval.push_str(line);
+            retval.push('\n');
+        }
+        retval.push('\n');
+        retval
+    }
+
+    /// Get the id of the email this email replies to
+    ///
+    /// If the email has no `In-Reply-To` header, the last of its references
+    /// is returned.
+    ///
+    pub fn parent(&self) -> Option<&str> {
+        self.in_reply_to
+            .as_deref()
+            .or_else(|| self.references.last().map(String::as_str))
+    }
+
+    /// Get the author of the email
+    ///
+    /// If the email has no date, the time supplied is used instead.
+    ///
+    pub fn author(&self, default_time: &Time) -> Result<Signature<'static>> {
+        let email = self
+            .author_email
+            .as_ref()
+            .ok_or_else(|| Error::from_kind(EK::UnknownMailAuthor(self.message_id.clone().unwrap_or_default())))?;
+        let name: String = self.author_name
+            .as_deref()
+            .unwrap_or_else(|| email.split('@').next().unwrap_or(email))
+            .chars()
+            .filter(|c| *c != '<' && *c != '>')
+            .collect();
+        Signature::new(&name, email, self.date.as_ref().unwrap_or(default_time))
+            .map_err(From::from)
+    }
+
+    /// Get the text of the message to create for the email
+    ///
+    /// The text consists of the subject and the body. If the email has an id,
+    /// a `Message-Id` trailer is appended.
+    ///
+    pub fn message_text(&self) -> String {
+        let subject = self.subject
+            .as_deref()
+            .filter(|subject| !subject.is_empty())
+            .unwrap_or("(no subject)");
+        let body: Vec<&str> = self.body.lines().map(str::trim_end).collect();
+        let body = body.join("\n");
+        let body = body.trim_matches('\n');
+
+        let text = if body.is_empty() {
+            subject.to_owned()
+        } else {
+            format!("{}\n\n{}", subject, body)
+        };
+
+        match self.message_id {
+            Some(ref id) => append_trailer(&text, &Trailer::new(MESSAGE_ID_SPEC.key.as_ref(), id.as_str())),
+            None => text,
+        }
+    }
+}
+
+
+/// Get the id of the email corresponding to a message
+///
+/// If the message carries a `Message-Id` trailer, e.g. if it was imported from
+/// an email, its value is returned. Otherwise, an id is derived from the
+/// message's id.
+///
+pub fn mail_id(message: &Commit) -> String {
+    message
+        .trailers()
+        .find(|trailer| trailer.key.as_ref() == MESSAGE_ID_SPEC.key.as_ref())
+        .map(|trailer| trailer.value.to_string().trim().to_owned())
+        .unwrap_or_else(|| format!("<{}@git-dit>", message.id()))
+}
+
+
+/// Parse a mailbox in the mbox format
+///
+/// Emails are separated by lines starting with `From `. Lines in the body of an
+/// email escaped by prepending `>` are unescaped. If the text doesn't start
+/// with a separator, e.g. if it is a single email, the text preceding the first
+/// separator is considered an email, too.
+///
+pub fn parse_mbox(text: &str) -> Vec<Mail> {
+    let text = text.replace("\r\n", "\n");
+
+    let mut mails = Vec::new();
+    let mut current = String::new();
+    for line in text.lines() {
+        if line.starts_with("From ") {
+            mails.push(mem::take(&mut current));
+            continue;
+        }
+
+        let line = if line.starts_with('>') && line.trim_start_matches('>').starts_with("From ") {
+            &line[1..]
+        } else {
+            line
+        };
+        current.push_str(line);
+        current.push('\n');
+    }
+    mails.push(current);
+
+    mails.iter()
+        .filter(|mail| !mail.trim().is_empty())
+        .map(|mail| Mail::parse(mail))
+        .collect()
+}
+
+
+/// Result of importing an email
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Imported {
+    /// The email was imported as the initial message of a new issue
+    Issue(Oid),
+    /// The email was imported as a reply to another message
+    Reply(Oid),
+    /// The email was already imported as the message with the given id
+    Skipped(Oid),
+}
+
+impl Imported {
+    /// Get the id of the message associated with the email
+    ///
+    pub fn id(&self) -> Oid {
+        match *self {
+            Imported::Issue(id) | Imported::Reply(id) | Imported::Skipped(id) => id,
+        }
+    }
+}
+
+
+/// Importer for emails
+///
+/// An importer keeps track of the emails imported into a repository, e.g. the
+/// messages carrying a `Message-Id` trailer.
+///
+pub struct Importer<'r> {
+    repo: &'r git2::Repository,
+    known: HashMap<String, Oid>,
+    signer: Option<&'r dyn Signer>,
+}
+
+impl<'r> Importer<'r> {
+    /// Create a new importer for a repository
+    ///
+    /// The messages of all issues in the repository are scanned for emails
+    /// imported previously.
+    ///
+    pub fn new(repo: &'r git2::Repository) -> Result<Self> {
+        let mut known = HashMap::new();
+        for issue in repo.issues()? {
+            for message in issue.messages()? {
+                let message = message?;
+                let ids = message
+                    .trailers()
+                    .filter(|trailer| trailer.key.as_ref() == MESSAGE_ID_SPEC.key.as_ref())
+                    .map(|trailer| trailer.value.to_string().trim().to_owned());
+                for id in ids {
+                    known.insert(id, message.id());
+                }
+            }
+        }
+
+        Ok(Importer { repo, known, signer: None })
+    }
+
+    /// Sign the messages created using the signer supplied
+    ///
+    pub fn with_signer(mut self, signer: Option<&'r dyn Signer>) -> Self {
+        self.signer = signer;
+        self
+    }
+
+    /// Get the message imported from an email
+    ///
+    pub fn find(&self, message_id: &str) -> Option<Oid> {
+        self.known.get(message_id).cloned()
+    }
+
+    /// Import a single email
+    ///
+    /// If the email replies to an email already imported, it is imported as a
+    /// reply to the respective message. Otherwise, a new issue is created.
+    ///
+    pub fn import(&mut self, committer: &Signature, mail: &Mail) -> Result<Imported> {
+        if let Some(id) = mail.message_id.as_ref().and_then(|id| self.find(id)) {
+            return Ok(Imported::Skipped(id));
+        }
+
+        let author = mail.author(&committer.when())?;
+        let text = mail.message_text();
+
+        let imported = match mail.parent().and_then(|parent| self.find(parent)) {
+            Some(parent) => {
+                let parent = self.repo.find_commit(parent).chain_err(|| EK::CannotGetCommit)?;
+                let issue = self.repo.issue_with_message(&parent)?;
+                let tree = parent.tree().chain_err(|| EK::CannotGetCommit)?;
+                let message = match self.signer {
+                    Some(signer) => issue.add_signed_message(&author, committer, text, &tree, vec![&parent], signer)?,
+                    None => issue.add_message(&author, committer, text, &tree, vec![&parent])?,
+                };
+                Imported::Reply(message.id())
+            },
+            None => {
+                let tree = self.repo.empty_tree()?;
+                let issue = match self.signer {
+                    Some(signer) => self.repo.create_signed_issue(&author, committer, text, &tree, vec![], signer)?,
+                    None => self.repo.create_issue(&author, committer, text, &tree, vec![])?,
+                };
+                Imported::Issue(issue.id())
+            },
+        };
+
+        if let Some(ref id) = mail.message_id {
+            self.known.insert(id.clone(), imported.id());
+        }
+        Ok(imported)
+    }
+
+    /// Import a sequence of emails
+    ///
+    /// The emails are imported in the order supplied, except that an email is
+    /// always imported after the email it replies to if both are part of the
+    /// sequence. Emails replying to each other in a cycle are imported as new
+    /// issues as needed. The emails are returned along with the results of
+    /// their import.
+    ///
+    pub fn import_all<I>(&mut self, committer: &Signature, mails: I) -> Result<Vec<(Mail, Imported)>>
+        where I: IntoIterator<Item = Mail>
+    {
+        let mut retval = Vec::new();
+        let mut pending: Vec<Mail> = mails.into_iter().collect();
+        while !pending.is_empty() {
+            let pending_ids: HashSet<String> = pending
+                .iter()
+                .filter_map(|mail| mail.message_id.clone())
+                .collect();
+            let (ready, mut deferred): (Vec<Mail>, Vec<Mail>) = pending
+                .into_iter()
+                .partition(|mail| match mail.parent() {
+                    Some(parent) => !pending_ids.contains(parent) || mail.message_id.as_deref() == Some(parent),
+                    None => true,
+                });
+
+            // If no email is ready for import, the remaining ones form cycles.
+            // We break them by importing an arbitrary email.
+            let ready = if ready.is_empty() {
+                vec![deferred.remove(0)]
+            } else {
+                ready
+            };
+
+            for mail in ready {
+                let imported = self.import(committer, &mail)?;
+                retval.push((mail, imported));
+            }
+            pending = deferred;
+        }
+        Ok(retval)
+    }
+}
+
+
+/// Split an email into its headers and its body
+///
+/// Folded header lines are unfolded.
+///
+fn split_headers(text: &str) -> (Vec<(String, String)>, &str) {
+    let (head, body) = match text.strip_prefix('\n') {
+        Some(body) => ("", body),
+        None => match text.find("\n\n") {
+            Some(pos) => (&text[..pos], &text[pos + 2..]),
+            None => (text, ""),
+        },
+    };
+
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for line in head.lines() {
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push_str(line);
+            }
+        } else if let Some(pos) = line.find(':') {
+            headers.push((line[..pos].trim().to_owned(), line[pos + 1..].trim().to_owned()));
+        }
+    }
+    (headers, body)
+}
+
+
+/// Get the value of the first header with the given name
+///
+/// Header names are compared case-insensitively.
+///
+fn find_header<'h>(headers: &'h [(String, String)], name: &str) -> Option<&'h str> {
+    headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+
+/// Get a parameter from a header value, e.g. the charset of a `Content-Type`
+///
+fn header_parameter<'a>(value: &'a str, name: &str) -> Option<&'a str> {
+    value
+        .split(';')
+        .skip(1)
+        .filter_map(|param| {
+            let mut parts = param.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some(key), Some(value)) => Some((key.trim(), value.trim().trim_matches('"'))),
+                _ => None,
+            }
+        })
+        .find(|&(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value)
+}
+
+
+/// Extract message ids from a header value
+///
+/// The ids are returned including their angle brackets. A header value
+/// consisting of a single id lacking angle brackets is accepted, too.
+///
+fn message_ids(value: &str) -> Vec<String> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"<[^<>\s]+>").unwrap();
+    }
+
+    let ids: Vec<String> = RE.find_iter(value).map(|id| id.as_str().to_owned()).collect();
+    let value = value.trim();
+    if ids.is_empty() && !value.is_empty() && !value.contains(char::is_whitespace) {
+        vec![format!("<{}>", value)]
+    } else {
+        ids
+    }
+}
+
+
+/// Parse an address of the form `Name <email>` or `email (Name)`
+///
+fn parse_address(value: &str) -> (Option<String>, Option<String>) {
+    let value = value.trim();
+    let (name, email) = match (value.find('<'), value.rfind('>')) {
+        (Some(start), Some(end)) if start < end => (&value[..start], &value[start + 1..end]),
+        _ => match (value.find('('), value.rfind(')')) {
+            (Some(start), Some(end)) if start < end => (&value[start + 1..end], &value[..start]),
+            _ => ("", value),
+        },
+    };
+
+    let name = name
+        .trim()
+        .trim_matches('"')
+        .replace("\\\"", "\"")
+        .replace("\\\\", "\\");
+    let email = email.trim();
+    (
+        Some(name).filter(|name| !name.is_empty()),
+        Some(email.to_owned()).filter(|email| !email.is_empty()),
+    )
+}
+
+
+/// Decode MIME encoded-words in a header value
+///
+fn decode_words(value: &str) -> String {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"=\?([^?]+)\?([bBqQ])\?([^?]*)\?=").unwrap();
+    }
+
+    let mut retval = String::new();
+    let mut last = 0;
+    for word in RE.captures_iter(value) {
+        // whitespace separating adjacent encoded-words is not part of the text
+        let whole = word.get(0).unwrap();
+        let gap = &value[last..whole.start()];
+        if last == 0 || !gap.trim().is_empty() {
+            retval.push_str(gap);
+        }
+
+        let bytes = match &word[2] {
+            "b" | "B" => decode_base64(&word[3]),
+            _ => decode_quoted_printable(&word[3], true),
+        };
+        let charset = word[1].split('*').next().unwrap_or("");
+        retval.push_str(&decode_charset(&bytes, charset));
+        last = whole.end();
+    }
+    retval.push_str(&value[last..]);
+    retval
+}
+
+
+/// Decode the body of an email or of a part of a multipart email
+///
+fn decode_body(headers: &[(String, String)], body: &str) -> String {
+    let header = |name| find_header(headers, name);
+
+    let content_type = header("Content-Type").unwrap_or("text/plain");
+    let mime_type = content_type.split(';').next().unwrap_or("").trim().to_lowercase();
+
+    if mime_type.starts_with("multipart/") {
+        let delimiter = match header_parameter(content_type, "boundary") {
+            Some(boundary) => format!("--{}", boundary),
+            None => return String::new(),
+        };
+
+        // Parts are separated by delimiter lines. The text preceding the first
+        // delimiter is not part of any part.
+        let mut parts = Vec::new();
+        let mut current: Option<String> = None;
+        for line in body.lines() {
+            if line.trim_end().starts_with(delimiter.as_str()) {
+                parts.extend(current.take());
+                if line.trim_end()[delimiter.len()..].starts_with("--") {
+                    break;
+                }
+                current = Some(String::new());
+            } else if let Some(ref mut part) = current {
+                part.push_str(line);
+                part.push('\n');
+            }
+        }
+        parts.extend(current);
+
+        return parts
+            .iter()
+            .map(|part| split_headers(part))
+            .filter(|(headers, _)| {
+                let content_type = find_header(headers, "Content-Type")
+                    .unwrap_or("text/plain")
+                    .to_lowercase();
+                content_type.starts_with("text/plain") || content_type.starts_with("multipart/")
+            })
+            .map(|(headers, body)| decode_body(&headers, body))
+            .find(|text| !text.trim().is_empty())
+            .unwrap_or_default();
+    }
+
+    let bytes = match header("Content-Transfer-Encoding").map(str::to_lowercase) {
+        Some(ref encoding) if encoding == "quoted-printable" => decode_quoted_printable(body, false),
+        Some(ref encoding) if encoding == "base64" => decode_base64(body),
+        _ => body.as_bytes().to_vec(),
+    };
+    decode_charset(&bytes, header_parameter(content_type, "charset").unwrap_or("utf-8"))
+}
+
+
+/// Encode text for a header as MIME encoded-words, if necessary
+///
+/// Text consisting of printable ASCII characters only is returned as is.
+/// Otherwise, the text is encoded as a sequence of quoted-printable encoded
+/// words, each of which is placed on a line of its own.
+///
+fn encode_words(text: &str) -> String {
+    if text.chars().all(|c| c == ' ' || c.is_ascii_graphic()) {
+        return text.to_owned();
+    }
+
+    // An encoded-word may not be longer than 75 characters, including the
+    // 12 characters making up its prefix and suffix.
+    let mut words = vec![String::new()];
+    for c in text.chars() {
+        let mut buffer = [0; 4];
+        let encoded: String = c
+            .encode_utf8(&mut buffer)
+            .bytes()
+            .map(|byte| match byte {
+                b' ' => "_".to_owned(),
+                b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'!' | b'*' | b'+' | b'-' | b'/' => (byte as char).to_string(),
+                _ => format!("={:02X}", byte),
+            })
+            .collect();
+        if words.last().map_or(0, String::len) + encoded.len() > 63 {
+            words.push(String::new());
+        }
+        if let Some(word) = words.last_mut() {
+            word.push_str(&encoded);
+        }
+    }
+
+    words
+        .iter()
+        .map(|word| format!("=?utf-8?q?{}?=", word))
+        .collect::<Vec<_>>()
+        .join("\n ")
+}
+
+
+/// Encode a display name for an address header
+///
+/// Names containing special characters are quoted. Names containing non-ASCII
+/// characters are encoded as MIME encoded-words.
+///
+fn encode_phrase(name: &str) -> String {
+    if !name.is_ascii() {
+        encode_words(name)
+    } else if name.contains(|c: char| "()<>[]:;@\\,.\"".contains(c)) {
+        format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        name.to_owned()
+    }
+}
+
+
+/// Decode text in a given charset
+///
+/// Text in a charset not known is decoded as if it was UTF-8.
+///
+fn decode_charset(bytes: &[u8], charset: &str) -> String {
+    match Charset::for_label(charset.trim().as_bytes()) {
+        Some(charset) => charset.decode_without_bom_handling(bytes).0.into_owned(),
+        None => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+
+/// Decode base64 encoded data
+///
+/// Whitespace, e.g. line breaks, is ignored. Missing padding is tolerated.
+/// Malformed data is returned as is.
+///
+fn decode_base64(text: &str) -> Vec<u8> {
+    use base64::Engine;
+    use base64::alphabet::STANDARD;
+    use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+
+    const ENGINE: GeneralPurpose = GeneralPurpose::new(&STANDARD, GeneralPurposeConfig::new()
+        .with_decode_padding_mode(DecodePaddingMode::Indifferent)
+        .with_decode_allow_trailing_bits(true));
+
+    let data: String = text.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+    ENGINE.decode(data).unwrap_or_else(|_| text.as_bytes().to_vec())
+}
+
+
+/// Decode quoted-printable data
+///
+/// In headers, an underscore represents a space. Line breaks are returned as
+/// LF. Malformed data is returned as is.
+///
+fn decode_quoted_printable(text: &str, header: bool) -> Vec<u8> {
+    use quoted_printable::ParseMode;
+
+    let text = if header {
+        text.replace('_', "=20")
+    } else {
+        text.to_owned()
+    };
+    let decoded = match quoted_printable::decode(text.as_bytes(), ParseMode::Robust) {
+        Ok(decoded) => decoded,
+        Err(_) => return text.into_bytes(),
+    };
+
+    // The decoder emits line breaks as CRLF.
+    decoded
+        .iter()
+        .enumerate()
+        .filter(|&(i, byte)| *byte != b'\r' || decoded.get(i + 1) != Some(&b'\n'))
+        .map(|(_, byte)| *byte)
+        .collect()
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::TestingRepo;
+
+    use git2;
+    use repository::RepositoryExt;
+
+    const MBOX: &str = concat!(
+        "From 1234 Mon Sep 17 00:00:00 2001\n",
+        "From: \"Bar, Foo\" <foo.bar@example.com>\n",
+        "Date: Mon, 2 Jan 2017 15:04:05 +0100\n",
+        "Subject: =?utf-8?q?Caf=C3=A9?= is\n",
+        " =?iso-8859-1?q?=FCber?= broken\n",
+        "Message-Id: <1@example.com>\n",
+        "Content-Type: text/plain; charset=utf-8\n",
+        "Content-Transfer-Encoding: quoted-printable\n",
+        "\n",
+        "It's all bro=\n",
+        "ken.\n",
+        ">From the looks of it.\n",
+        "\n",
+        "From 5678 Mon Sep 17 00:00:00 2001\n",
+        "From: baz@example.com (Baz)\n",
+        "Subject: Re: Cafe\n",
+        "Message-Id: <3@example.com>\n",
+        "In-Reply-To: <2@example.com>\n",
+        "References: <1@example.com> <2@example.com>\n",
+        "\n",
+        "Indeed.\n",
+        "\n",
+        "Signed-off-by: Baz <baz@example.com>\n",
+        "\n",
+        "From 9012 Mon Sep 17 00:00:00 2001\n",
+        "From: Foo Bar <foo.bar@example.com>\n",
+        "Subject: Re: Cafe\n",
+        "Message-Id: <2@example.com>\n",
+        "References: <1@example.com>\n",
+        "Content-Type: multipart/alternative; boundary=\"xyz\"\n",
+        "\n",
+        "preamble\n",
+        "--xyz\n",
+        "Content-Type: text/plain\n",
+        "Content-Transfer-Encoding: base64\n",
+        "\n",
+        "U3RpbGwgYnJva2VuPw==\n",
+        "--xyz\n",
+        "Content-Type: text/html\n",
+        "\n",
+        "<p>Still broken?</p>\n",
+        "--xyz--\n",
+    );
+
+    #[test]
+    fn parse_mails() {
+        let mails = parse_mbox(MBOX);
+        assert_eq!(mails.len(), 3);
+
+        let mail = &mails[0];
+        assert_eq!(mail.message_id, Some("<1@example.com>".to_owned()));
+        assert_eq!(mail.parent(), None);
+        assert_eq!(mail.author_name, Some("Bar, Foo".to_owned()));
+        assert_eq!(mail.author_email, Some("foo.bar@example.com".to_owned()));
+        assert_eq!(mail.date.map(|t| (t.seconds(), t.offset_minutes())), Some((1_483_365_845, 60)));
+        assert_eq!(mail.subject, Some("Café is über broken".to_owned()));
+        assert_eq!(mail.body, "It's all broken.\nFrom the looks of it.\n\n");
+
+        let mail = &mails[1];
+        assert_eq!(mail.parent(), Some("<2@example.com>"));
+        assert_eq!(mail.author_name, Some("Baz".to_owned()));
+        assert_eq!(mail.author_email, Some("baz@example.com".to_owned()));
+        assert!(mail.date.is_none());
+        assert_eq!(
+            mail.message_text(),
+            "Re: Cafe\n\nIndeed.\n\nSigned-off-by: Baz <baz@example.com>\nMessage-Id: <3@example.com>"
+        );
+
+        let mail = &mails[2];
+        assert_eq!(mail.parent(), Some("<1@example.com>"));
+        assert_eq!(mail.body, "Still broken?");
+    }
+
+    #[test]
+    fn decode_headers() {
+        assert_eq!(decode_words("=?utf-8?q?Caf=C3=A9_au_lait?="), "Café au lait");
+        assert_eq!(decode_words("=?UTF-8?B?Q2Fmw6k?= =?koi8-r?b?9MXT1A==?="), "CaféТест");
+        assert_eq!(decode_words("Re: =?unknown?q?plain?= text"), "Re: plain text");
+    }
+
+    #[test]
+    fn export_mails() {
+        let mut testing_repo = TestingRepo::new("export_mails");
+        let repo = testing_repo.repo();
+
+        let author = git2::Signature::new("Bär, Foo", "foo.bar@example.com", &Time::new(1_483_365_845, 60))
+            .expect("Could not create signature");
+        let empty_tree = repo
+            .empty_tree()
+            .expect("Could not create empty tree");
+
+        let issue = repo
+            .create_issue(&author, &author, "Test message 1\n\nFrom here on.", &empty_tree, vec![])
+            .expect("Could not create issue");
+        let initial_message = issue
+            .initial_message()
+            .expect("Could not retrieve initial message");
+        let message1 = issue
+            .add_message(&author, &author, "Test message 2\n\nMessage-Id: <2@example.com>", &empty_tree, vec![&initial_message])
+            .expect("Could not add message");
+        let message2 = issue
+            .add_message(&author, &author, "Test message 3", &empty_tree, vec![&message1])
+            .expect("Could not add message");
+
+        let initial_id = format!("<{}@git-dit>", initial_message.id());
+        assert_eq!(mail_id(&initial_message), initial_id);
+        assert_eq!(mail_id(&message1), "<2@example.com>");
+
+        let mail = Mail::from_message(&issue, &message2).expect("Could not create email");
+        assert_eq!(mail.in_reply_to, Some("<2@example.com>".to_owned()));
+        assert_eq!(mail.references, vec![initial_id.clone(), "<2@example.com>".to_owned()]);
+
+        let mail = Mail::from_message(&issue, &message1).expect("Could not create email");
+        assert_eq!(mail.body, "");
+
+        let mail = Mail::from_message(&issue, &initial_message).expect("Could not create email");
+        assert!(mail.in_reply_to.is_none());
+        let text = mail.to_mbox();
+        assert!(text.starts_with("From foo.bar@example.com Mon Jan  2 14:04:05 2017\n"));
+        assert!(text.contains("\nFrom: =?utf-8?q?B=C3=A4r=2C_Foo?= <foo.bar@example.com>\n"));
+        assert!(text.contains("\nDate: Mon, 2 Jan 2017 15:04:05 +0100\n"));
+        assert!(text.contains("\n>From here on.\n"));
+
+        // parsing the mailbox yields the original email
+        let parsed = parse_mbox(&text);
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].message_id, Some(initial_id));
+        assert_eq!(parsed[0].author_name, mail.author_name);
+        assert_eq!(parsed[0].subject, mail.subject);
+        assert_eq!(parsed[0].message_text(), mail.message_text());
+    }
+
+    #[test]
+    fn import_mails() {
+        let mut testing_repo = TestingRepo::new("import_mails");
+        let repo = testing_repo.repo();
+
+        let sig = git2::Signature::now("Foo Bar", "foo.bar@example.com")
+            .expect("Could not create signature");
+
+        let imported = Importer::new(repo)
+            .expect("Could not create importer")
+            .import_all(&sig, parse_mbox(MBOX))
+            .expect("Could not import mails");
+        let ids: Vec<Option<&str>> = imported
+            .iter()
+            .map(|(mail, _)| mail.message_id.as_deref())
+            .collect();
+        assert_eq!(ids, vec![Some("<1@example.com>"), Some("<2@example.com>"), Some("<3@example.com>")]);
+
+        let issue = match imported[0].1 {
+            Imported::Issue(id) => repo.find_issue(id).expect("Could not find issue"),
+            _ => panic!("Mail was not imported as an issue"),
+        };
+        let reply = repo
+            .find_commit(imported[2].1.id())
+            .expect("Could not find message");
+        assert_eq!(reply.parent_ids().collect::<Vec<_>>(), vec![imported[1].1.id()]);
+        assert_eq!(repo.issue_with_message(&reply).expect("Could not find issue").id(), issue.id());
+
+        let initial = issue.initial_message().expect("Could not retrieve initial message");
+        assert_eq!(initial.author().name(), Some("Bar, Foo"));
+        assert_eq!(initial.author().when().seconds(), 1_483_365_845);
+
+        // importing the same mails again doesn't create any new messages
+        let reimported = Importer::new(repo)
+            .expect("Could not create importer")
+            .import_all(&sig, parse_mbox(MBOX))
+            .expect("Could not import mails");
+        for (old, new) in imported.iter().zip(reimported.iter()) {
+            assert_eq!(new.1, Imported::Skipped(old.1.id()));
+        }
+        assert_eq!(repo.issues().expect("Could not retrieve issues").len(), 1);
+    }
+}
+
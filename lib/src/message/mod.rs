@@ -27,7 +27,9 @@ use std;
 pub mod block;
 pub mod line_processor;
 
+use self::block::Block;
 use self::line_processor::{Quoted, StrippingIter};
+use trailer::Trailer;
 
 
 /// Special iterator extension for messages
@@ -135,6 +137,29 @@ impl<L, S> LineIteratorExt<S> for L
 }
 
 
+/// Append a trailer to the text of a message
+///
+/// If the body of the message ends with a block of trailers, the trailer is
+/// added to that block. Otherwise, it is appended as a new paragraph. Trailing
+/// whitespace is removed from the text supplied.
+///
+pub fn append_trailer(text: &str, trailer: &Trailer) -> String {
+    let text = text.trim_end();
+    let ends_with_trailers = text
+        .lines()
+        .skip(2)
+        .line_blocks()
+        .last()
+        .map(|block| matches!(block, Block::Trailer(_)))
+        .unwrap_or(false);
+    if ends_with_trailers {
+        format!("{}\n{}", text, trailer)
+    } else {
+        format!("{}\n\n{}", text, trailer)
+    }
+}
+
+
 /// Type representing the lines composing the body part of a commit message
 ///
 pub type BodyLines = std::iter::Skip<std::vec::IntoIter<String>>;
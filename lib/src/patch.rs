@@ -0,0 +1,147 @@
+// git-dit - the distributed issue tracker for git
+// Copyright (C) 2017 Matthias Beyer <mail@beyermatthias.de>
+// Copyright (C) 2017 Julian Ganz <neither@nut.email>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+
+//! Patches
+//!
+//! Messages may carry code changes in the form of commits, e.g. as part of a
+//! patch set. Such a message refers to the commit via its second parent and a
+//! `Dit-patch` trailer. Messages carrying patches are created using
+//! `Issue::add_patches()`.
+//!
+//! A series of patches is represented by a sequence of messages, each replying
+//! to its predecessor and carrying a commit whose first parent is the commit
+//! carried by the predecessor. Hence, the commit carried by the last message
+//! of a series contains all the changes of the series.
+//!
+
+use git2::Commit;
+
+use error::*;
+use error::ErrorKind as EK;
+use issue::Issue;
+use message::Message;
+use trailer::spec::MESSAGE_PATCH_SPEC;
+use utils::ResultIterExt;
+
+
+/// Get the commit carried by a message
+///
+/// Returns `None` if the message doesn't carry a commit. The commit referred to
+/// by a `Dit-patch` trailer is only considered if it is a parent of the message.
+///
+pub fn patch<'r>(message: &Commit<'r>) -> Option<Commit<'r>> {
+    let id = message
+        .trailers()
+        .find(|trailer| trailer.key.as_ref() == MESSAGE_PATCH_SPEC.key.as_ref())
+        .map(|trailer| trailer.value.to_string())?;
+    message
+        .parents()
+        .find(|parent| parent.id().to_string() == id.trim())
+}
+
+
+/// Get the last commit of the series of patches a message is part of
+///
+/// The series is followed from the message supplied through the replies
+/// continuing it. If a patch is continued by multiple replies, e.g. because
+/// the same series was submitted more than once, the most recent reply is
+/// followed.
+///
+pub fn series_tip<'r>(issue: &Issue<'r>, message: &Commit<'r>) -> Result<Commit<'r>> {
+    let mut tip = patch(message).ok_or_else(|| Error::from_kind(EK::NoPatch(message.id())))?;
+    let mut current = message.id();
+
+    let messages: Vec<Commit<'r>> = issue.messages()?.collect_result()?;
+    loop {
+        let next = messages
+            .iter()
+            .filter(|message| message.parent_id(0).ok() == Some(current))
+            .filter_map(|message| patch(message).map(|commit| (message, commit)))
+            .filter(|(_, commit)| commit.parent_id(0).ok() == Some(tip.id()))
+            .max_by_key(|(message, _)| message.time().seconds());
+        match next {
+            Some((message, commit)) => {
+                current = message.id();
+                tip = commit;
+            },
+            None => return Ok(tip),
+        }
+    }
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::TestingRepo;
+
+    use git2;
+    use repository::RepositoryExt;
+
+    #[test]
+    fn patches() {
+        let mut testing_repo = TestingRepo::new("patches");
+        let repo = testing_repo.repo();
+
+        let sig = git2::Signature::now("Foo Bar", "foo.bar@example.com")
+            .expect("Could not create signature");
+        let empty_tree = repo
+            .empty_tree()
+            .expect("Could not create empty tree");
+
+        // create a history of code
+        let mut commits: Vec<Commit> = Vec::new();
+        for content in &["foo", "bar", "baz"] {
+            let blob = repo.blob(content.as_bytes()).expect("Could not create blob");
+            let mut builder = repo.treebuilder(None).expect("Could not create tree builder");
+            builder.insert("file", blob, 0o100644).expect("Could not insert blob");
+            let tree = repo
+                .find_tree(builder.write().expect("Could not write tree"))
+                .expect("Could not find tree");
+            let parents: Vec<&Commit> = commits.last().into_iter().collect();
+            let text = format!("Change to {}\n\nSigned-off-by: Foo Bar <foo.bar@example.com>", content);
+            let id = repo
+                .commit(None, &sig, &sig, &text, &tree, &parents)
+                .expect("Could not create commit");
+            commits.push(repo.find_commit(id).expect("Could not find commit"));
+        }
+
+        let issue = repo
+            .create_issue(&sig, &sig, "Test message 1", &empty_tree, vec![])
+            .expect("Could not create issue");
+        let initial_message = issue
+            .initial_message()
+            .expect("Could not retrieve initial message");
+        assert!(patch(&initial_message).is_none());
+        assert!(series_tip(&issue, &initial_message).is_err());
+
+        let messages = issue
+            .add_patches(&sig, &initial_message, &commits[1..], None)
+            .expect("Could not add patches");
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].parent_ids().collect::<Vec<_>>(), vec![initial_message.id(), commits[1].id()]);
+        assert_eq!(messages[1].parent_ids().collect::<Vec<_>>(), vec![messages[0].id(), commits[2].id()]);
+        assert_eq!(messages[1].tree_id(), commits[2].tree_id());
+        assert_eq!(messages[0].summary(), Some("[PATCH 1/2] Change to bar"));
+        assert_eq!(
+            messages[0].trailers().map(|t| t.to_string()).collect::<Vec<_>>(),
+            vec![
+                "Signed-off-by: Foo Bar <foo.bar@example.com>".to_owned(),
+                format!("Dit-patch: {}", commits[1].id()),
+            ]
+        );
+        assert_eq!(patch(&messages[1]).map(|commit| commit.id()), Some(commits[2].id()));
+
+        assert_eq!(series_tip(&issue, &messages[0]).unwrap().id(), commits[2].id());
+        assert_eq!(series_tip(&issue, &messages[1]).unwrap().id(), commits[2].id());
+    }
+}
+
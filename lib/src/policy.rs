@@ -0,0 +1,688 @@
+// git-dit - the distributed issue tracker for git
+// Copyright (C) 2017 Matthias Beyer <mail@beyermatthias.de>
+// Copyright (C) 2017 Julian Ganz <neither@nut.email>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+
+//! Access control policies
+//!
+//! Repositories with public push access need to restrict which references may
+//! be pushed by whom. This module provides the `Policy` type, which decides
+//! whether a reference update, e.g. as reported to a server side `update`
+//! hook, is permitted.
+//!
+//! Policies are declared in a file in the git configuration format. Each
+//! variable in the `allow` section names an action and lists the subjects who
+//! may perform it. Variables may be given multiple times. For example, the
+//! following policy allows anyone to report issues and to push new messages,
+//! while only the assignee of an issue or a moderator may update its head:
+//!
+//! ```ignore
+//! [role]
+//!     admin = Foo Bar <foo.bar@example.com>
+//!     moderator = Baz Qux <baz.qux@example.com>
+//! [allow]
+//!     create-issue = anyone
+//!     push-leaf = anyone
+//!     update-head = assignee
+//!     update-head = moderators
+//! [require]
+//!     signature = true
+//! ```
+//!
+//! The following actions are distinguished:
+//!
+//!  * `create-issue`: pushing the head of an issue not yet present
+//!  * `update-head`: moving the head of an issue
+//!  * `push-leaf`: pushing a new leaf, e.g. a new message
+//!  * `update-leaf`: moving an existing leaf
+//!  * `delete-ref`: deleting any reference
+//!  * `push-other`: creating or moving a reference not related to git-dit
+//!
+//! An action may be allowed for `anyone`, `nobody`, the issue's `reporter`,
+//! its `assignee`, the `moderators` or specific people, identified as
+//! `Name <email>`. Admins may perform any action. Actions not mentioned in
+//! the policy are governed by defaults, which allow anyone to create issues
+//! and push leaves and allow the assignee to update the head of an issue.
+//!
+//! Regardless of the permissions, references related to an issue must point
+//! to messages of that very issue. If required, pushed messages must also
+//! carry a valid signature. Since the verification of signatures is beyond
+//! the scope of this module, it is left to the user of the policy, who should
+//! also identify the person performing an update via the signature, e.g. using
+//! `signature::Trust::identity()`, or via the authenticated pushing user.
+//! The author of a message is never used for identifying a person, since it
+//! may be set to anything. Updates for which no person is known are only
+//! permitted if the action is allowed for `anyone`.
+//!
+//! The subjects defined here are also used for restricting the transitions of
+//! a status `Workflow`. Updates of an issue's head may be checked against a
+//! workflow via `Policy::check_workflow()`.
+//!
+
+use git2::{self, Commit, Oid, Repository};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::result::Result as RResult;
+use std::str::FromStr;
+
+use issue::IssueRefType;
+use message::Message;
+use repository::RepositoryExt;
+use utils;
+use workflow::Workflow;
+
+use error::*;
+use error::ErrorKind as EK;
+
+
+/// Actions subject to a policy
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    CreateIssue,
+    UpdateHead,
+    PushLeaf,
+    UpdateLeaf,
+    DeleteRef,
+    PushOther,
+}
+
+impl Action {
+    /// Get the name of the action, as used in policy files
+    ///
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Action::CreateIssue => "create-issue",
+            Action::UpdateHead  => "update-head",
+            Action::PushLeaf    => "push-leaf",
+            Action::UpdateLeaf  => "update-leaf",
+            Action::DeleteRef   => "delete-ref",
+            Action::PushOther   => "push-other",
+        }
+    }
+
+    /// Get all actions
+    ///
+    pub fn all() -> [Action; 6] {
+        [
+            Action::CreateIssue,
+            Action::UpdateHead,
+            Action::PushLeaf,
+            Action::UpdateLeaf,
+            Action::DeleteRef,
+            Action::PushOther,
+        ]
+    }
+}
+
+
+/// Subjects which may be allowed to perform an action
+///
+#[derive(Clone, Debug, PartialEq)]
+pub enum Subject {
+    Anyone,
+    Reporter,
+    Assignee,
+    Moderators,
+    Person(String),
+}
+
+impl FromStr for Subject {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim() {
+            "anyone"     => Ok(Subject::Anyone),
+            "reporter"   => Ok(Subject::Reporter),
+            "assignee"   => Ok(Subject::Assignee),
+            "moderators" => Ok(Subject::Moderators),
+            person if person.contains('<') && person.ends_with('>') => Ok(Subject::Person(person.to_owned())),
+            _ => Err(Error::from_kind(EK::MalformedPolicy(s.to_owned()))),
+        }
+    }
+}
+
+impl fmt::Display for Subject {
+    fn fmt(&self, f: &mut fmt::Formatter) -> RResult<(), fmt::Error> {
+        match *self {
+            Subject::Anyone             => write!(f, "anyone"),
+            Subject::Reporter           => write!(f, "reporter"),
+            Subject::Assignee           => write!(f, "assignee"),
+            Subject::Moderators         => write!(f, "moderators"),
+            Subject::Person(ref person) => write!(f, "{}", person),
+        }
+    }
+}
+
+
+/// Reference update
+///
+/// This type represents the update of a reference from an old to a new value,
+/// as reported e.g. to an `update` hook. A reference which is about to be
+/// created has no old value. A reference which is about to be deleted has no
+/// new value.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct RefUpdate {
+    pub refname: String,
+    pub old: Option<Oid>,
+    pub new: Option<Oid>,
+}
+
+impl RefUpdate {
+    /// Create a reference update from the values passed to an `update` hook
+    ///
+    /// Null ids are interpreted as absent values.
+    ///
+    pub fn new(refname: &str, old: Oid, new: Oid) -> Self {
+        RefUpdate {
+            refname: refname.to_owned(),
+            old: Some(old).filter(|id| !id.is_zero()),
+            new: Some(new).filter(|id| !id.is_zero()),
+        }
+    }
+
+    /// Determine the action performed by the update
+    ///
+    pub fn action(&self) -> Action {
+        if self.new.is_none() {
+            return Action::DeleteRef;
+        }
+        match (IssueRefType::of_ref(&self.refname), self.old) {
+            (Some((_, IssueRefType::Head)), None)    => Action::CreateIssue,
+            (Some((_, IssueRefType::Head)), Some(_)) => Action::UpdateHead,
+            (Some((_, IssueRefType::Leaf)), None)    => Action::PushLeaf,
+            (Some((_, IssueRefType::Leaf)), Some(_)) => Action::UpdateLeaf,
+            _ => Action::PushOther,
+        }
+    }
+}
+
+
+/// Access control policy
+///
+pub struct Policy {
+    admins: Vec<String>,
+    moderators: Vec<String>,
+    rules: HashMap<Action, Vec<Subject>>,
+    require_signature: bool,
+}
+
+impl Policy {
+    /// Read a policy from a configuration
+    ///
+    pub fn from_config(config: &git2::Config) -> Result<Self> {
+        let mut retval = Self::default();
+        let mut rules: HashMap<Action, Vec<Subject>> = HashMap::new();
+
+        for entry in &config.entries(Some(r"^(role|allow|require)\."))? {
+            let entry = entry?;
+            let (name, value) = match (entry.name(), entry.value()) {
+                (Some(name), Some(value)) => (name, value),
+                _ => continue,
+            };
+
+            match name {
+                "role.admin"        => retval.admins.push(value.trim().to_owned()),
+                "role.moderator"    => retval.moderators.push(value.trim().to_owned()),
+                "require.signature" => retval.require_signature = config.get_bool(name)?,
+                _ => {
+                    let action = Action::all()
+                        .iter()
+                        .cloned()
+                        .find(|action| name == format!("allow.{}", action.name()))
+                        .ok_or_else(|| Error::from_kind(EK::MalformedPolicy(name.to_owned())))?;
+                    let subjects = rules.entry(action).or_default();
+                    if value.trim() != "nobody" {
+                        subjects.push(value.parse()?);
+                    }
+                },
+            }
+        }
+
+        retval.rules.extend(rules);
+        Ok(retval)
+    }
+
+    /// Read a policy from a file
+    ///
+    pub fn from_file(path: &Path) -> Result<Self> {
+        git2::Config::open(path)
+            .map_err(Error::from)
+            .and_then(|config| Self::from_config(&config))
+    }
+
+    /// Read the policy of a repository
+    ///
+    /// The policy is read from the file configured via the `dit.policy`
+    /// option or, by default, from the file `dit/policy` inside the
+    /// repository's git directory. If the file does not exist, the default
+    /// policy is returned.
+    ///
+    pub fn for_repository(repo: &Repository) -> Result<Self> {
+        let path = repo
+            .dit_config()?
+            .get_path("dit.policy")
+            .unwrap_or_else(|_| utils::dit_dir(repo).join("policy"));
+        if path.is_file() {
+            Self::from_file(&path)
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    /// Check whether pushed messages need to carry a valid signature
+    ///
+    pub fn requires_signature(&self) -> bool {
+        self.require_signature
+    }
+
+    /// Get the subjects allowed to perform an action
+    ///
+    /// Admins are not included explicitly.
+    ///
+    pub fn allowed(&self, action: Action) -> &[Subject] {
+        self.rules.get(&action).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Check whether a reference update is permitted
+    ///
+    /// The update is performed by the person supplied, identified as
+    /// `Name <email>`. The person should be authenticated by the caller, e.g.
+    /// via the signature of the new commit. If no person is supplied, the
+    /// update is only permitted if the action is allowed for anyone. The
+    /// assignee of an issue is determined using the key supplied.
+    ///
+    /// An error of the kind `PolicyViolation` is returned if the update is not
+    /// permitted.
+    ///
+    pub fn check(&self,
+                 repo: &Repository,
+                 update: &RefUpdate,
+                 person: Option<&str>,
+                 assignee_key: &str
+    ) -> Result<()> {
+        let action = update.action();
+        let new = match update.new {
+            Some(id) => Some(repo.find_commit(id)?),
+            None => None,
+        };
+
+        // references related to an issue must point to one of its messages
+        let issue = IssueRefType::of_ref(&update.refname).map(|(id, _)| id);
+        if let (Some(issue), Some(ref new)) = (issue, &new) {
+            if !belongs_to_issue(repo, new, issue)? {
+                return Err(violation("The pushed reference appears to be a rogue hash"));
+            }
+        }
+
+        let person = person.map(str::trim);
+
+        let permitted = self.permits(repo, self.allowed(action), person, issue, || match issue {
+            Some(issue) => assignee(repo, issue, update, assignee_key),
+            None => Ok(None),
+        })?;
+        if permitted {
+            return Ok(());
+        }
+
+        Err(violation(&match person {
+            Some(person) => format!("{} is not allowed to {} via {}", person, action.name(), update.refname),
+            None => format!("Not allowed to {} via {}", action.name(), update.refname),
+        }))
+    }
+
+    /// Check whether a person is one of the subjects supplied
+    ///
+    /// The person is identified as `Name <email>`. Roles relative to an issue,
+    /// e.g. the reporter, are determined for the issue supplied. The assignee
+    /// is only retrieved via the function supplied if required. Admins are
+    /// considered to be any subject.
+    ///
+    pub fn permits<F>(&self,
+                      repo: &Repository,
+                      subjects: &[Subject],
+                      person: Option<&str>,
+                      issue: Option<Oid>,
+                      assignee: F
+    ) -> Result<bool>
+        where F: Fn() -> Result<Option<String>>
+    {
+        if person.is_some_and(|person| self.admins.iter().any(|admin| admin == person)) {
+            return Ok(true);
+        }
+
+        for subject in subjects {
+            let permitted = match (subject, person) {
+                (Subject::Anyone, _) => true,
+                (_, None) => false,
+                (Subject::Moderators, Some(person)) => self.moderators.iter().any(|m| m == person),
+                (Subject::Person(p), Some(person)) => p == person,
+                (Subject::Reporter, Some(person)) => match issue {
+                    Some(issue) => repo.find_commit(issue)?.author().to_string() == person,
+                    None => false,
+                },
+                (Subject::Assignee, Some(_)) if issue.is_none() => false,
+                (Subject::Assignee, Some(person)) => assignee()?.is_some_and(|a| a == person),
+            };
+            if permitted {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Check the status changes introduced by a reference update
+    ///
+    /// Only updates of an issue's head are considered. The messages added to
+    /// the first-parent chain of the head are checked in chronological order,
+    /// each against the status preceding it. Each change of the status is
+    /// attributed to the person returned by the function supplied for the
+    /// message changing it, e.g. the verified signer of that very message.
+    /// Roles are determined as for `check()`, with the assignee preceding the
+    /// message.
+    ///
+    pub fn check_workflow<F>(&self,
+                             repo: &Repository,
+                             workflow: &Workflow,
+                             update: &RefUpdate,
+                             identify: F,
+                             status_key: &str,
+                             assignee_key: &str
+    ) -> Result<()>
+        where F: Fn(&Commit) -> Result<Option<String>>
+    {
+        let (issue, new) = match (IssueRefType::of_ref(&update.refname), update.new) {
+            (Some((issue, IssueRefType::Head)), Some(new)) if workflow.is_enabled() => (issue, new),
+            _ => return Ok(()),
+        };
+
+        // messages already part of the history are not checked again
+        let base = match update.old {
+            Some(old) => repo.merge_base(old, new).ok(),
+            None => None,
+        };
+        let mut messages = Vec::new();
+        for message in repo.issue_messages_iter(repo.find_commit(new)?)? {
+            let message = message?;
+            if Some(message.id()) == base {
+                break;
+            }
+            messages.push(message);
+        }
+
+        let mut status = match base {
+            Some(base) => latest_value(repo, base, status_key)?,
+            None => None,
+        };
+        for message in messages.into_iter().rev() {
+            let to = match message
+                .trailers()
+                .filter(|trailer| trailer.key.as_ref() == status_key)
+                .last() {
+                Some(trailer) => trailer.value.to_string().trim().to_owned(),
+                None => continue,
+            };
+
+            let person = identify(&message)?;
+            let person = person.as_ref().map(|person| person.trim());
+            let parent = message.parent_id(0).ok();
+            workflow.check(status.as_deref(), &to, |subjects| {
+                self.permits(repo, subjects, person, Some(issue), || match parent {
+                    Some(parent) => latest_value(repo, parent, assignee_key),
+                    None => Ok(None),
+                })
+            })?;
+            status = Some(to);
+        }
+        Ok(())
+    }
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        let mut rules = HashMap::new();
+        rules.insert(Action::CreateIssue, vec![Subject::Anyone]);
+        rules.insert(Action::UpdateHead, vec![Subject::Assignee]);
+        rules.insert(Action::PushLeaf, vec![Subject::Anyone]);
+
+        Policy {
+            admins: Vec::new(),
+            moderators: Vec::new(),
+            rules,
+            require_signature: false,
+        }
+    }
+}
+
+
+/// Create a policy violation error
+///
+fn violation(reason: &str) -> Error {
+    Error::from_kind(EK::PolicyViolation(reason.to_owned()))
+}
+
+
+/// Check whether a message belongs to an issue
+///
+/// The message is considered part of the issue if the issue's initial message
+/// is found on its first-parent chain before any other issue's initial
+/// message.
+///
+fn belongs_to_issue(repo: &Repository, message: &Commit, issue: Oid) -> Result<bool> {
+    for id in repo.first_parent_messages(message.id())?.revwalk {
+        let id = id?;
+        if id == issue {
+            return Ok(true);
+        }
+        if repo.find_issue(id).is_ok() {
+            return Ok(false);
+        }
+    }
+    Ok(false)
+}
+
+
+/// Determine the assignee of an issue prior to an update
+///
+/// The assignee is the latest value of the assignee trailer on the
+/// first-parent chain of the issue's current head. For updates of the head
+/// itself, the head's old value is used.
+///
+fn assignee(repo: &Repository, issue: Oid, update: &RefUpdate, key: &str) -> Result<Option<String>> {
+    let head = match (update.action(), update.old) {
+        (Action::UpdateHead, Some(old)) => old,
+        _ => match repo.find_issue(issue).and_then(|issue| issue.local_head()) {
+            Ok(head) => head.peel(git2::ObjectType::Commit)?.id(),
+            Err(_) => return Ok(None),
+        },
+    };
+    latest_value(repo, head, key)
+}
+
+
+/// Determine the latest value of a trailer on the first-parent chain of a message
+///
+fn latest_value(repo: &Repository, head: Oid, key: &str) -> Result<Option<String>> {
+    for message in repo.issue_messages_iter(repo.find_commit(head)?)? {
+        let value = message?
+            .trailers()
+            .find(|trailer| trailer.key.as_ref() == key)
+            .map(|trailer| trailer.value.to_string().trim().to_owned());
+        if value.is_some() {
+            return Ok(value);
+        }
+    }
+    Ok(None)
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::TestingRepo;
+
+    use git2;
+
+    #[test]
+    fn parse_policy() {
+        let mut testing_repo = TestingRepo::new("parse_policy");
+        let repo = testing_repo.repo();
+
+        let mut config = repo.config().expect("Could not get config");
+        config.set_str("role.admin", "Foo Bar <foo.bar@example.com>").unwrap();
+        config.set_str("allow.update-head", "moderators").unwrap();
+        config.set_str("allow.delete-ref", "nobody").unwrap();
+        config.set_bool("require.signature", true).unwrap();
+
+        let policy = Policy::from_config(&config).expect("Could not read policy");
+        assert!(policy.requires_signature());
+        assert_eq!(policy.allowed(Action::UpdateHead), &[Subject::Moderators]);
+        assert_eq!(policy.allowed(Action::DeleteRef), &[]);
+        assert_eq!(policy.allowed(Action::CreateIssue), &[Subject::Anyone]);
+        assert_eq!(policy.allowed(Action::PushOther), &[]);
+
+        config.set_str("allow.foo", "anyone").unwrap();
+        assert!(Policy::from_config(&config).is_err());
+        config.remove("allow.foo").unwrap();
+        config.set_str("allow.push-leaf", "someone").unwrap();
+        assert!(Policy::from_config(&config).is_err());
+    }
+
+    #[test]
+    fn check_updates() {
+        let mut testing_repo = TestingRepo::new("check_updates");
+        let repo = testing_repo.repo();
+
+        let reporter = git2::Signature::now("Foo Bar", "foo.bar@example.com")
+            .expect("Could not create signature");
+        let other = git2::Signature::now("Baz Qux", "baz.qux@example.com")
+            .expect("Could not create signature");
+        let empty_tree = repo
+            .empty_tree()
+            .expect("Could not create empty tree");
+
+        let issue1 = repo
+            .create_issue(&reporter, &reporter, "Test message 1", &empty_tree, vec![])
+            .expect("Could not create issue");
+        let initial1 = issue1.initial_message().expect("Could not retrieve initial message");
+        let issue2 = repo
+            .create_issue(&other, &other, "Test message 2", &empty_tree, vec![])
+            .expect("Could not create issue");
+        let assign = issue1
+            .add_message(&reporter, &reporter, "Assign\n\nDit-assignee: Baz Qux <baz.qux@example.com>", &empty_tree, vec![&initial1])
+            .expect("Could not add message");
+        let reply = issue1
+            .add_message(&other, &other, "Reply", &empty_tree, vec![&assign])
+            .expect("Could not add message");
+
+        let head1 = format!("refs/dit/{}/head", issue1.id());
+        let leaf1 = format!("refs/dit/{}/leaves/{}", issue1.id(), reply.id());
+        let zero = Oid::zero();
+        let policy = Policy::default();
+        let check = |update: &RefUpdate, person: Option<&str>| policy
+            .check(repo, update, person, "Dit-assignee")
+            .is_ok();
+
+        let update = RefUpdate::new(&leaf1, zero, reply.id());
+        assert_eq!(update.action(), Action::PushLeaf);
+        assert!(check(&update, None));
+
+        // rogue hashes are rejected
+        let update = RefUpdate::new(&format!("refs/dit/{}/leaves/{}", issue2.id(), reply.id()), zero, reply.id());
+        assert!(!check(&update, None));
+
+        // only the assignee may update the head
+        let update = RefUpdate::new(&head1, assign.id(), reply.id());
+        assert_eq!(update.action(), Action::UpdateHead);
+        assert!(check(&update, Some("Baz Qux <baz.qux@example.com>")));
+        assert!(!check(&update, Some("Foo Bar <foo.bar@example.com>")));
+
+        // the author of the new message is not trusted
+        assert!(!check(&update, None));
+
+        let update = RefUpdate::new(&head1, reply.id(), zero);
+        assert_eq!(update.action(), Action::DeleteRef);
+        assert!(!check(&update, Some("Baz Qux <baz.qux@example.com>")));
+
+        let update = RefUpdate::new("refs/heads/master", zero, reply.id());
+        assert_eq!(update.action(), Action::PushOther);
+        assert!(!check(&update, None));
+
+        // admins may do anything
+        let mut config = repo.config().expect("Could not get config");
+        config.set_str("role.admin", "Foo Bar <foo.bar@example.com>").unwrap();
+        let policy = Policy::from_config(&config).expect("Could not read policy");
+        assert!(policy.check(repo, &update, None, "Dit-assignee").is_err());
+        assert!(policy.check(repo, &update, Some("Foo Bar <foo.bar@example.com>"), "Dit-assignee").is_ok());
+    }
+
+    #[test]
+    fn check_workflow() {
+        let mut testing_repo = TestingRepo::new("check_workflow");
+        let repo = testing_repo.repo();
+
+        let reporter = git2::Signature::now("Foo Bar", "foo.bar@example.com")
+            .expect("Could not create signature");
+        let other = git2::Signature::now("Baz Qux", "baz.qux@example.com")
+            .expect("Could not create signature");
+        let empty_tree = repo
+            .empty_tree()
+            .expect("Could not create empty tree");
+
+        let mut config = repo.config().expect("Could not get config");
+        config.set_multivar("dit.workflow.state", "^$", "open").unwrap();
+        config.set_multivar("dit.workflow.state", "^$", "closed").unwrap();
+        config.set_multivar("dit.workflow.transition", "^$", "open -> closed: assignee").unwrap();
+        let workflow = Workflow::from_config(&config).expect("Could not read workflow");
+
+        let issue = repo
+            .create_issue(&reporter, &reporter, "Test message 1", &empty_tree, vec![])
+            .expect("Could not create issue");
+        let initial = issue.initial_message().expect("Could not retrieve initial message");
+        let assign = issue
+            .add_message(&reporter, &reporter, "Assign\n\nDit-assignee: Baz Qux <baz.qux@example.com>", &empty_tree, vec![&initial])
+            .expect("Could not add message");
+        let close = issue
+            .add_message(&other, &other, "Close\n\nDit-status: closed", &empty_tree, vec![&assign])
+            .expect("Could not add message");
+        let reopen = issue
+            .add_message(&other, &other, "Reopen\n\nDit-status: open", &empty_tree, vec![&close])
+            .expect("Could not add message");
+
+        let head = format!("refs/dit/{}/head", issue.id());
+        let policy = Policy::default();
+        let check = |update: &RefUpdate, person: Option<&str>| policy
+            .check_workflow(repo, &workflow, update, |_| Ok(person.map(ToOwned::to_owned)), "Dit-status", "Dit-assignee")
+            .is_ok();
+
+        // only the assignee may close the issue
+        let update = RefUpdate::new(&head, initial.id(), close.id());
+        assert!(check(&update, Some("Baz Qux <baz.qux@example.com>")));
+        assert!(!check(&update, Some("Foo Bar <foo.bar@example.com>")));
+        assert!(!check(&update, None));
+
+        // each message is attributed to its own identity
+        let by_author = |message: &Commit| Ok(Some(message.author().to_string()));
+        assert!(policy
+            .check_workflow(repo, &workflow, &update, by_author, "Dit-status", "Dit-assignee")
+            .is_ok());
+
+        // the issue may not be reopened, regardless of the old head
+        let assignee = Some("Baz Qux <baz.qux@example.com>");
+        assert!(!check(&RefUpdate::new(&head, close.id(), reopen.id()), assignee));
+        assert!(!check(&RefUpdate::new(&head, initial.id(), reopen.id()), assignee));
+
+        // other references are not checked
+        let leaf = format!("refs/dit/{}/leaves/{}", issue.id(), reopen.id());
+        assert!(check(&RefUpdate::new(&leaf, Oid::zero(), reopen.id()), None));
+    }
+}
+
@@ -0,0 +1,476 @@
+// git-dit - the distributed issue tracker for git
+// Copyright (C) 2017 Matthias Beyer <mail@beyermatthias.de>
+// Copyright (C) 2017 Julian Ganz <neither@nut.email>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+
+//! Issue queries
+//!
+//! This module provides a representation of boolean filter expressions over
+//! issue metadata as well as a parser for their textual representation.
+//!
+//! A query consists of conditions, which may be combined using the `and`, `or`
+//! and `not` operators as well as parentheses. `!` is an alias for `not`.
+//! `not` binds strongest, followed by `and` and `or`. A condition consists of
+//! a key, optionally followed by an operator and a value. A condition without
+//! an operator requires the presence of the piece of metadata. The following
+//! operators are supported:
+//!
+//!  * `=` and `!=` for (in)equality,
+//!  * `~` for matching a substring,
+//!  * `=~` for matching a regular expression and
+//!  * `<`, `<=`, `>` and `>=` for comparisons.
+//!
+//! Values may be quoted using single or double quotes. Within double quotes,
+//! a backslash escapes the following character. Unquoted values end at the
+//! first whitespace, parenthesis, quote or operator character.
+//!
+//! For example, the following query matches all open bugs with a priority of at
+//! least 3 or assigned to someone at "example.com":
+//!
+//! ```ignore
+//! status=open and type=bug and (priority>=3 or assignee~"@example.com")
+//! ```
+//!
+//! The interpretation of keys is left to the user of this module.
+//!
+
+use regex::Regex;
+use std::fmt;
+use std::iter::Peekable;
+use std::result::Result as RResult;
+use std::str::{Chars, FromStr};
+
+use trailer::TrailerValue;
+use trailer::filter::ValueMatcher;
+use utils;
+
+use error::*;
+use error::ErrorKind as EK;
+
+
+/// A single condition of a query
+///
+pub struct Condition {
+    pub key: String,
+    pub matcher: ValueMatcher,
+}
+
+
+/// Query expression
+///
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Condition(Condition),
+}
+
+impl Expr {
+    /// Combine two expressions using a logical "and"
+    ///
+    pub fn and(self, other: Expr) -> Expr {
+        Expr::And(Box::new(self), Box::new(other))
+    }
+
+    /// Combine two expressions using a logical "or"
+    ///
+    pub fn or(self, other: Expr) -> Expr {
+        Expr::Or(Box::new(self), Box::new(other))
+    }
+
+    /// Evaluate the expression
+    ///
+    /// The predicate supplied is used for evaluating the expression's
+    /// conditions. Evaluation is short-circuiting, e.g. the predicate will
+    /// only be invoked for conditions which are relevant for the result.
+    ///
+    pub fn evaluate<F>(&self, predicate: &mut F) -> bool
+        where F: FnMut(&Condition) -> bool
+    {
+        match *self {
+            Expr::And(ref l, ref r)     => l.evaluate(predicate) && r.evaluate(predicate),
+            Expr::Or(ref l, ref r)      => l.evaluate(predicate) || r.evaluate(predicate),
+            Expr::Not(ref e)            => !e.evaluate(predicate),
+            Expr::Condition(ref c)      => predicate(c),
+        }
+    }
+
+    /// Retrieve all the conditions in the expression
+    ///
+    pub fn conditions(&self) -> Vec<&Condition> {
+        match *self {
+            Expr::And(ref l, ref r) | Expr::Or(ref l, ref r) => {
+                let mut retval = l.conditions();
+                retval.append(&mut r.conditions());
+                retval
+            },
+            Expr::Not(ref e)            => e.conditions(),
+            Expr::Condition(ref c)      => vec![c],
+        }
+    }
+
+    /// Transform the conditions of the expression
+    ///
+    /// The function supplied is applied to each condition in the expression.
+    /// The first error returned by the function is returned.
+    ///
+    pub fn map_conditions<F>(self, func: &mut F) -> Result<Expr>
+        where F: FnMut(Condition) -> Result<Condition>
+    {
+        Ok(match self {
+            Expr::And(l, r)     => l.map_conditions(func)?.and(r.map_conditions(func)?),
+            Expr::Or(l, r)      => l.map_conditions(func)?.or(r.map_conditions(func)?),
+            Expr::Not(e)        => Expr::Not(Box::new(e.map_conditions(func)?)),
+            Expr::Condition(c)  => Expr::Condition(func(c)?),
+        })
+    }
+}
+
+impl FromStr for Expr {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parser = Parser { tokens: Tokens { chars: s.chars().peekable() }.peekable() };
+        parser
+            .parse_or()
+            .and_then(|expr| match parser.tokens.next() {
+                None        => Ok(expr),
+                Some(token) => Err(Error::from_kind(EK::UnexpectedToken(token?.to_string()))),
+            })
+            .chain_err(|| EK::MalformedQuery(s.to_owned()))
+    }
+}
+
+
+/// Convert a matcher for dates to a matcher for timestamps
+///
+/// Values in queries are parsed as plain `TrailerValue`s. For matching dates,
+/// which are usually represented as the number of seconds since the epoch,
+/// they have to be converted. This function converts the values of equality
+/// matchers and comparisons. Dates may be specified in the formats described
+/// for `utils::parse_date()` or as plain numbers of seconds since the epoch.
+///
+pub fn date_matcher(matcher: ValueMatcher) -> Result<ValueMatcher> {
+    let convert = |value: TrailerValue| match value {
+        TrailerValue::Int(i)    => Ok(TrailerValue::Int(i)),
+        TrailerValue::Date(d)   => Ok(TrailerValue::Int(d)),
+        TrailerValue::String(s) => utils::parse_date(&s)
+            .map(TrailerValue::Int)
+            .ok_or_else(|| Error::from_kind(EK::MalformedDate(s))),
+        other                   => Err(Error::from_kind(EK::MalformedDate(other.to_string()))),
+    };
+
+    Ok(match matcher {
+        ValueMatcher::Equals(v)         => ValueMatcher::Equals(convert(v)?),
+        ValueMatcher::Less(v)           => ValueMatcher::Less(convert(v)?),
+        ValueMatcher::LessOrEqual(v)    => ValueMatcher::LessOrEqual(convert(v)?),
+        ValueMatcher::Greater(v)        => ValueMatcher::Greater(convert(v)?),
+        ValueMatcher::GreaterOrEqual(v) => ValueMatcher::GreaterOrEqual(convert(v)?),
+        other                           => other,
+    })
+}
+
+
+/// Tokens of a query
+///
+enum Token {
+    Open,
+    Close,
+    Not,
+    Operator(String),
+    Word(String),
+    Quoted(String),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> RResult<(), fmt::Error> {
+        match *self {
+            Token::Open             => write!(f, "("),
+            Token::Close            => write!(f, ")"),
+            Token::Not              => write!(f, "!"),
+            Token::Operator(ref s)  => write!(f, "{}", s),
+            Token::Word(ref s)      => write!(f, "{}", s),
+            Token::Quoted(ref s)    => write!(f, "\"{}\"", s),
+        }
+    }
+}
+
+
+/// Tokenizer for queries
+///
+struct Tokens<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl<'a> Tokens<'a> {
+    /// Check whether a character terminates an unquoted word
+    ///
+    fn is_delimiter(c: char) -> bool {
+        c.is_whitespace() || "()!=~<>\"'".contains(c)
+    }
+
+    /// Consume the next character if it is the one supplied
+    ///
+    fn consume(&mut self, c: char) -> bool {
+        if self.chars.peek() == Some(&c) {
+            self.chars.next();
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Result<Token>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.chars.peek().is_some_and(|c| c.is_whitespace()) {
+            self.chars.next();
+        }
+
+        let c = self.chars.next()?;
+        Some(Ok(match c {
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '!' => if self.consume('=') {
+                Token::Operator("!=".to_owned())
+            } else {
+                Token::Not
+            },
+            '=' => if self.consume('~') {
+                Token::Operator("=~".to_owned())
+            } else {
+                Token::Operator("=".to_owned())
+            },
+            '<' | '>' => if self.consume('=') {
+                Token::Operator(format!("{}=", c))
+            } else {
+                Token::Operator(c.to_string())
+            },
+            '~' => Token::Operator("~".to_owned()),
+            '"' | '\'' => {
+                let mut value = String::new();
+                loop {
+                    match self.chars.next() {
+                        Some(d) if d == c       => break,
+                        Some('\\') if c == '"'  => match self.chars.next() {
+                            Some(e) => value.push(e),
+                            None    => return Some(Err(Error::from_kind(EK::UnterminatedQuote))),
+                        },
+                        Some(d)                 => value.push(d),
+                        None                    => return Some(Err(Error::from_kind(EK::UnterminatedQuote))),
+                    }
+                }
+                Token::Quoted(value)
+            },
+            _ => {
+                let mut word = c.to_string();
+                while let Some(&d) = self.chars.peek() {
+                    if Self::is_delimiter(d) {
+                        break;
+                    }
+                    word.push(d);
+                    self.chars.next();
+                }
+                Token::Word(word)
+            },
+        }))
+    }
+}
+
+
+/// Recursive descent parser for queries
+///
+struct Parser<'a> {
+    tokens: Peekable<Tokens<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    /// Check whether the next token is a specific keyword
+    ///
+    fn next_is_keyword(&mut self, keyword: &str) -> bool {
+        match self.tokens.peek() {
+            Some(&Ok(Token::Word(ref w))) => w.eq_ignore_ascii_case(keyword),
+            _ => false,
+        }
+    }
+
+    /// Retrieve the next token, failing if there is none
+    ///
+    fn next_token(&mut self) -> Result<Token> {
+        self.tokens
+            .next()
+            .unwrap_or_else(|| Err(Error::from_kind(EK::UnexpectedEndOfQuery)))
+    }
+
+    /// Parse a disjunction
+    ///
+    fn parse_or(&mut self) -> Result<Expr> {
+        let mut retval = self.parse_and()?;
+        while self.next_is_keyword("or") {
+            self.tokens.next();
+            retval = retval.or(self.parse_and()?);
+        }
+        Ok(retval)
+    }
+
+    /// Parse a conjunction
+    ///
+    fn parse_and(&mut self) -> Result<Expr> {
+        let mut retval = self.parse_unary()?;
+        while self.next_is_keyword("and") {
+            self.tokens.next();
+            retval = retval.and(self.parse_unary()?);
+        }
+        Ok(retval)
+    }
+
+    /// Parse a negation, a parenthesized expression or a condition
+    ///
+    fn parse_unary(&mut self) -> Result<Expr> {
+        match self.next_token()? {
+            Token::Not => self.parse_unary().map(|e| Expr::Not(Box::new(e))),
+            Token::Word(ref w) if w.eq_ignore_ascii_case("not") => {
+                self.parse_unary().map(|e| Expr::Not(Box::new(e)))
+            },
+            Token::Open => {
+                let retval = self.parse_or()?;
+                match self.next_token()? {
+                    Token::Close => Ok(retval),
+                    token => Err(Error::from_kind(EK::UnexpectedToken(token.to_string()))),
+                }
+            },
+            Token::Word(key) => self.parse_condition(key),
+            token => Err(Error::from_kind(EK::UnexpectedToken(token.to_string()))),
+        }
+    }
+
+    /// Parse the remainder of a condition, following its key
+    ///
+    fn parse_condition(&mut self, key: String) -> Result<Expr> {
+        let op = match self.tokens.peek() {
+            Some(&Ok(Token::Operator(_))) => match self.next_token()? {
+                Token::Operator(op) => op,
+                _ => unreachable!(),
+            },
+            _ => return Ok(Expr::Condition(Condition { key, matcher: ValueMatcher::Any })),
+        };
+
+        let value = match self.next_token()? {
+            Token::Word(value) | Token::Quoted(value) => value,
+            token => return Err(Error::from_kind(EK::UnexpectedToken(token.to_string()))),
+        };
+
+        let matcher = match op.as_ref() {
+            "=" | "!="  => ValueMatcher::Equals(TrailerValue::from_slice(&value)),
+            "~"         => ValueMatcher::Contains(value),
+            "=~"        => ValueMatcher::Regex(Regex::new(&value)
+                .chain_err(|| EK::MalformedRegex(value.clone()))?),
+            "<"         => ValueMatcher::Less(TrailerValue::from_slice(&value)),
+            "<="        => ValueMatcher::LessOrEqual(TrailerValue::from_slice(&value)),
+            ">"         => ValueMatcher::Greater(TrailerValue::from_slice(&value)),
+            ">="        => ValueMatcher::GreaterOrEqual(TrailerValue::from_slice(&value)),
+            _           => return Err(Error::from_kind(EK::UnexpectedToken(op))),
+        };
+
+        let condition = Expr::Condition(Condition { key, matcher });
+        if op == "!=" {
+            Ok(Expr::Not(Box::new(condition)))
+        } else {
+            Ok(condition)
+        }
+    }
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    fn matches(query: &str, values: &[(&str, TrailerValue)]) -> bool {
+        let values: HashMap<_, _> = values.iter().cloned().collect();
+        Expr::from_str(query)
+            .expect("Could not parse query")
+            .evaluate(&mut |c| values
+                .get(c.key.as_str())
+                .is_some_and(|v| c.matcher.matches(v))
+            )
+    }
+
+    #[test]
+    fn simple_conditions() {
+        let values = [
+            ("status", TrailerValue::String("open".to_owned())),
+            ("priority", TrailerValue::Int(3)),
+        ];
+
+        assert!(matches("status", &values));
+        assert!(!matches("assignee", &values));
+        assert!(matches("status=open", &values));
+        assert!(matches("status = 'open'", &values));
+        assert!(!matches("status!=open", &values));
+        assert!(matches("status~pe", &values));
+        assert!(matches("status=~\"^o.*n$\"", &values));
+        assert!(!matches("status=~^c", &values));
+        assert!(matches("priority>=3", &values));
+        assert!(!matches("priority>3", &values));
+        assert!(matches("priority<10", &values));
+        assert!(!matches("priority<abc", &values));
+    }
+
+    #[test]
+    fn operators() {
+        let values = [
+            ("status", TrailerValue::String("open".to_owned())),
+            ("type", TrailerValue::String("bug".to_owned())),
+        ];
+
+        assert!(matches("status=open and type=bug", &values));
+        assert!(!matches("status=open and not type=bug", &values));
+        assert!(matches("status=closed or type=bug", &values));
+        assert!(matches("!status=closed", &values));
+        assert!(matches("status=closed or status=open and type=bug", &values));
+        assert!(!matches("(status=closed or status=open) and type=feature", &values));
+        assert!(matches("NOT (status=closed OR type=feature)", &values));
+    }
+
+    #[test]
+    fn malformed_queries() {
+        assert!(Expr::from_str("").is_err());
+        assert!(Expr::from_str("status=").is_err());
+        assert!(Expr::from_str("status=open and").is_err());
+        assert!(Expr::from_str("(status=open").is_err());
+        assert!(Expr::from_str("status=open)").is_err());
+        assert!(Expr::from_str("status='open").is_err());
+        assert!(Expr::from_str("status=~(").is_err());
+        assert!(Expr::from_str("status=open type=bug").is_err());
+    }
+
+    #[test]
+    fn dates() {
+        let matcher = |query: &str| match Expr::from_str(query).expect("Could not parse query") {
+            Expr::Condition(c) => date_matcher(c.matcher).expect("Could not convert matcher"),
+            _ => panic!("Expected a condition"),
+        };
+
+        let date = TrailerValue::Int(1504224000); // 2017-09-01T00:00:00Z
+        assert!(matcher("created=2017-09-01").matches(&date));
+        assert!(matcher("created=2017-09-01T02:00+02:00").matches(&date));
+        assert!(matcher("created=\"2017-08-31 20:00:00-0400\"").matches(&date));
+        assert!(matcher("created<2017-09-01T00:00:01Z").matches(&date));
+        assert!(matcher("created>=1504224000").matches(&date));
+        assert!(!matcher("created>2017-09-01").matches(&date));
+        assert!(matcher("created>1970-01-01").matches(&date));
+
+        assert!(date_matcher(ValueMatcher::Less(TrailerValue::String("yesterday".to_owned()))).is_err());
+    }
+}
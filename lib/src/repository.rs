@@ -14,12 +14,17 @@
 //!
 
 use git2::{self, Commit, Oid, Tree};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
+use cache;
 use gc;
 use issue::Issue;
 use iter;
+use search;
+use signature::{self, Signer};
+use trailer::spec::{self, TrailerSpec};
 use utils::ResultIterExt;
+use workflow::Workflow;
 
 use error::*;
 use error::ErrorKind as EK;
@@ -43,6 +48,14 @@ pub trait RepositoryExt {
     ///
     fn find_issue(&self, id: Oid) -> Result<Issue>;
 
+    /// Retrieve an issue by a possibly abbreviated id
+    ///
+    /// Returns the issue whose id starts with the hexadecimal prefix supplied.
+    /// If the prefix matches the ids of multiple issues, an error listing all
+    /// of them is returned.
+    ///
+    fn find_issue_abbrev(&self, id: &str) -> Result<Issue<'_>>;
+
     /// Retrieve an issue by its head ref
     ///
     /// Returns the issue associated with a head reference.
@@ -82,6 +95,24 @@ pub trait RepositoryExt {
               I: IntoIterator<Item = &'a Commit<'a>, IntoIter = J>,
               J: Iterator<Item = &'a Commit<'a>>;
 
+    /// Create a new issue with a signed initial message
+    ///
+    /// Like `create_issue()`, but the initial message is signed using the
+    /// signer supplied.
+    ///
+    fn create_signed_issue<'a, A, I, J, S>(&self,
+             author: &git2::Signature,
+             committer: &git2::Signature,
+             message: A,
+             tree: &Tree,
+             parents: I,
+             signer: &S
+    ) -> Result<Issue<'_>>
+        where A: AsRef<str>,
+              I: IntoIterator<Item = &'a Commit<'a>, IntoIter = J>,
+              J: Iterator<Item = &'a Commit<'a>>,
+              S: Signer + ?Sized;
+
     /// Get an revwalk configured as a first parent iterator
     ///
     /// This is a convenience function. It returns an iterator over messages in
@@ -100,6 +131,40 @@ pub trait RepositoryExt {
     ///
     fn collectable_refs<'a>(&'a self) -> gc::CollectableRefs<'a>;
 
+    /// Get a handle for the repository's metadata cache
+    ///
+    fn metadata_cache(&self) -> cache::MetadataCache;
+
+    /// Get the repository's search index
+    ///
+    fn search_index(&self) -> search::SearchIndex;
+
+    /// Get the configuration relevant for git-dit
+    ///
+    /// The configuration returned contains the repository's configuration,
+    /// layered on top of the project's `.dit-config` file. The latter is
+    /// located at the root of the repository's working directory and may be
+    /// committed, allowing projects to share their configuration. Options in
+    /// the `.dit-config` file are overridden by the system's, the user's and
+    /// the repository's configuration.
+    ///
+    fn dit_config(&self) -> Result<git2::Config>;
+
+    /// Get the trailer specifications for the repository
+    ///
+    /// This function returns a map from names to trailer specifications,
+    /// declared in the configuration returned by `dit_config()`.
+    ///
+    fn trailer_specs(&self) -> Result<HashMap<String, TrailerSpec>>;
+
+    /// Get the status workflow for the repository
+    ///
+    /// The workflow is declared in the configuration returned by
+    /// `dit_config()`. If no workflow is declared, a disabled workflow is
+    /// returned.
+    ///
+    fn workflow(&self) -> Result<Workflow>;
+
     /// Get an empty tree
     ///
     /// This function returns an empty tree.
@@ -120,6 +185,35 @@ impl RepositoryExt for git2::Repository {
         }
     }
 
+    fn find_issue_abbrev(&self, id: &str) -> Result<Issue<'_>> {
+        let prefix = id.to_lowercase();
+        if prefix.is_empty() || !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(Error::from_kind(EK::UnknownIssue(id.to_owned())));
+        }
+
+        // a full id is resolved without considering all issues
+        if let Ok(oid) = Oid::from_str(&prefix) {
+            if prefix.len() == 40 {
+                return self.find_issue(oid);
+            }
+        }
+
+        let mut candidates: Vec<Issue> = self
+            .issues()?
+            .into_iter()
+            .filter(|issue| issue.id().to_string().starts_with(&prefix))
+            .collect();
+        match candidates.len() {
+            0 => Err(Error::from_kind(EK::UnknownIssue(id.to_owned()))),
+            1 => Ok(candidates.remove(0)),
+            _ => {
+                let mut ids: Vec<Oid> = candidates.iter().map(Issue::id).collect();
+                ids.sort();
+                Err(Error::from_kind(EK::AmbiguousIssueId(id.to_owned(), ids)))
+            },
+        }
+    }
+
     fn issue_by_head_ref(&self, head_ref: &git2::Reference) -> Result<Issue> {
         let name = head_ref.name();
         name.and_then(|name| if name.ends_with("/head") {
@@ -190,6 +284,29 @@ impl RepositoryExt for git2::Repository {
             })
     }
 
+    fn create_signed_issue<'a, A, I, J, S>(&self,
+             author: &git2::Signature,
+             committer: &git2::Signature,
+             message: A,
+             tree: &Tree,
+             parents: I,
+             signer: &S
+    ) -> Result<Issue<'_>>
+        where A: AsRef<str>,
+              I: IntoIterator<Item = &'a Commit<'a>, IntoIter = J>,
+              J: Iterator<Item = &'a Commit<'a>>,
+              S: Signer + ?Sized
+    {
+        let parent_vec : Vec<&Commit> = parents.into_iter().collect();
+
+        signature::create_signed_commit(self, author, committer, message.as_ref(), tree, &parent_vec, signer)
+            .and_then(|id| Issue::new(self, id))
+            .and_then(|issue| {
+                issue.update_head(issue.id(), true)?;
+                Ok(issue)
+            })
+    }
+
     fn first_parent_messages(&self, id: Oid) -> Result<iter::Messages> {
         iter::Messages::empty(self)
             .and_then(|mut messages| {
@@ -205,6 +322,55 @@ impl RepositoryExt for git2::Repository {
         gc::CollectableRefs::new(self)
     }
 
+    fn metadata_cache(&self) -> cache::MetadataCache {
+        cache::MetadataCache::new(self)
+    }
+
+    fn search_index(&self) -> search::SearchIndex {
+        search::SearchIndex::new(self)
+    }
+
+    fn dit_config(&self) -> Result<git2::Config> {
+        use git2::{Config, ConfigLevel};
+
+        let mut config = Config::new()?;
+
+        // The project's configuration has the lowest priority
+        let project = self
+            .workdir()
+            .map(|dir| dir.join(".dit-config"))
+            .filter(|path| path.is_file());
+        if let Some(path) = project {
+            config.add_file(&path, ConfigLevel::ProgramData, false)?;
+        }
+
+        let defaults = vec![
+            (Config::find_system(), ConfigLevel::System),
+            (Config::find_xdg(), ConfigLevel::XDG),
+            (Config::find_global(), ConfigLevel::Global),
+        ];
+        for (path, level) in defaults {
+            if let Ok(path) = path {
+                config.add_file(&path, level, false)?;
+            }
+        }
+
+        let local = self.path().join("config");
+        if local.is_file() {
+            config.add_file(&local, ConfigLevel::Local, false)?;
+        }
+
+        Ok(config)
+    }
+
+    fn trailer_specs(&self) -> Result<HashMap<String, TrailerSpec>> {
+        self.dit_config().and_then(|config| spec::specs_from_config(&config))
+    }
+
+    fn workflow(&self) -> Result<Workflow> {
+        self.dit_config().and_then(|config| Workflow::from_config(&config))
+    }
+
     fn issue_messages_iter<'a>(&'a self, commit: Commit<'a>) -> Result<iter::IssueMessagesIter<'a>> {
         self.first_parent_messages(commit.id()).map(iter::Messages::until_any_initial)
     }
@@ -245,6 +411,52 @@ mod tests {
             .expect("Could not tretrieve issue by id");
     }
 
+    #[test]
+    fn find_issue_abbrev() {
+        let mut testing_repo = TestingRepo::new("find_issue_abbrev");
+        let repo = testing_repo.repo();
+
+        let sig = git2::Signature::now("Foo Bar", "foo.bar@example.com")
+            .expect("Could not create signature");
+        let empty_tree = repo
+            .empty_tree()
+            .expect("Could not create empty tree");
+
+        // with 17 issues, at least two ids share their first digit
+        let ids: Vec<Oid> = (0..17)
+            .map(|n| repo
+                .create_issue(&sig, &sig, format!("Test message {}", n), &empty_tree, vec![])
+                .expect("Could not create issue")
+                .id())
+            .collect();
+
+        for id in ids.iter() {
+            let full = id.to_string();
+            for abbrev in [&full[..12], &full[..], &full[..12].to_uppercase()] {
+                let issue = repo
+                    .find_issue_abbrev(abbrev)
+                    .expect("Could not retrieve issue by abbreviated id");
+                assert_eq!(issue.id(), *id);
+            }
+        }
+
+        let digit = ids
+            .iter()
+            .map(|id| id.to_string()[..1].to_owned())
+            .find(|digit| ids.iter().filter(|id| id.to_string().starts_with(digit)).count() > 1)
+            .expect("Could not find ambiguous prefix");
+        match repo.find_issue_abbrev(&digit).map(|issue| issue.id()) {
+            Err(Error(EK::AmbiguousIssueId(id, candidates), _)) => {
+                assert_eq!(id, digit);
+                assert!(candidates.len() > 1);
+                assert!(candidates.iter().all(|id| id.to_string().starts_with(&digit)));
+            },
+            other => panic!("Unexpected result: {:?}", other),
+        }
+
+        assert!(repo.find_issue_abbrev("xyz").is_err());
+    }
+
     #[test]
     fn issue_by_head_ref() {
         let mut testing_repo = TestingRepo::new("issue_by_head_ref");
@@ -389,5 +601,55 @@ mod tests {
         assert_eq!(iter2.next().unwrap().unwrap().id(), issue2.id());
         assert!(iter2.next().is_none());
     }
-}
 
+    #[test]
+    fn trailer_specs() {
+        use trailer::accumulation::AccumulationPolicy;
+        use trailer::types::ValueType;
+
+        let mut testing_repo = TestingRepo::new("trailer_specs");
+        let repo = testing_repo.repo();
+
+        {
+            let mut config = repo.config().expect("Could not get config");
+            config
+                .set_str("dit.trailer.assignee.accumulation", "latest")
+                .expect("Could not set config");
+            config
+                .set_str("dit.trailer.component.key", "Component")
+                .expect("Could not set config");
+            config
+                .set_str("dit.trailer.component.accumulation", "list")
+                .expect("Could not set config");
+            config
+                .set_str("dit.trailer.due.type", "date")
+                .expect("Could not set config");
+            config
+                .set_str("dit.trailer.label.type", "enum:bug,feature")
+                .expect("Could not set config");
+        }
+
+        let specs = repo.trailer_specs().expect("Could not get trailer specs");
+        assert_eq!(specs.len(), 8);
+        assert_eq!(specs.get("status"), Some(&spec::ISSUE_STATUS_SPEC));
+        assert_eq!(specs.get("type"), Some(&spec::ISSUE_TYPE_SPEC));
+        assert_eq!(
+            specs.get("label"),
+            Some(&spec::ISSUE_LABEL_SPEC.with_type(ValueType::Enum(vec!["bug".to_owned(), "feature".to_owned()])))
+        );
+        assert_eq!(specs.get("depends-on"), Some(&spec::ISSUE_DEPENDS_ON_SPEC));
+        assert_eq!(specs.get("blocks"), Some(&spec::ISSUE_BLOCKS_SPEC));
+        assert_eq!(
+            specs.get("assignee"),
+            Some(&TrailerSpec::new("Dit-assignee", AccumulationPolicy::Latest))
+        );
+        assert_eq!(
+            specs.get("component"),
+            Some(&TrailerSpec::new("Component", AccumulationPolicy::List))
+        );
+        assert_eq!(
+            specs.get("due"),
+            Some(&TrailerSpec::new("Dit-due", AccumulationPolicy::Latest).with_type(ValueType::Date))
+        );
+    }
+}
@@ -0,0 +1,196 @@
+// git-dit - the distributed issue tracker for git
+// Copyright (C) 2017 Matthias Beyer <mail@beyermatthias.de>
+// Copyright (C) 2017 Julian Ganz <neither@nut.email>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+
+//! Message revisions
+//!
+//! Messages are immutable. A message may, however, be amended by creating a
+//! new message with the same parents, carrying a `Dit-supersedes` trailer
+//! referring to the original message. Since the new message may be amended
+//! itself, a message may have an arbitrary number of revisions.
+//!
+//! This module provides the `Revisions` type, which relates the revisions of
+//! messages found in a set of messages.
+//!
+
+use git2::{Commit, Oid};
+use std::collections::HashMap;
+
+use message::Message;
+use trailer::spec::MESSAGE_SUPERSEDES_SPEC;
+
+
+/// Get the id of the message superseded by a message
+///
+/// Returns `None` if the message doesn't supersede another message.
+///
+pub fn superseded(message: &Commit) -> Option<Oid> {
+    message
+        .trailers()
+        .filter(|trailer| trailer.key.as_ref() == MESSAGE_SUPERSEDES_SPEC.key.as_ref())
+        .filter_map(|trailer| Oid::from_str(trailer.value.to_string().trim()).ok())
+        .next()
+}
+
+
+/// Revisions of messages
+///
+/// This type relates the revisions of the messages from a set of messages.
+/// Each message is associated with an original message, e.g. the first
+/// revision, and a latest revision. A message which is neither amended nor
+/// amends another message is its own original and latest revision.
+///
+/// Only messages from the set of messages are considered. If a message
+/// supersedes a message not present in the set, it is considered an original.
+///
+pub struct Revisions {
+    originals: HashMap<Oid, Oid>,
+    latest: HashMap<Oid, Oid>,
+}
+
+impl Revisions {
+    /// Compute the revisions of a set of messages
+    ///
+    /// If a message was amended multiple times independently, the most recent
+    /// revision, in terms of the commit time, is considered the latest.
+    ///
+    pub fn new<'a, 'r: 'a, I>(messages: I) -> Self
+        where I: IntoIterator<Item = &'a Commit<'r>>
+    {
+        let messages: Vec<&Commit> = messages.into_iter().collect();
+
+        // direct predecessors of messages
+        let predecessors: HashMap<Oid, Oid> = messages
+            .iter()
+            .filter_map(|message| superseded(message).map(|pred| (message.id(), pred)))
+            .filter(|&(_, pred)| messages.iter().any(|message| message.id() == pred))
+            .collect();
+
+        let mut originals = HashMap::new();
+        let mut latest: HashMap<Oid, &Commit> = HashMap::new();
+        for message in messages.iter() {
+            // NOTE: a message may only supersede messages which existed before
+            //       its creation. Hence, there can't be any cycles.
+            let mut original = message.id();
+            while let Some(pred) = predecessors.get(&original) {
+                original = *pred;
+            }
+            originals.insert(message.id(), original);
+
+            let is_newer = latest
+                .get(&original)
+                .map(|current| current.time() < message.time())
+                .unwrap_or(true);
+            if is_newer {
+                latest.insert(original, message);
+            }
+        }
+
+        let latest = originals
+            .iter()
+            .map(|(id, original)| (*id, latest[original].id()))
+            .collect();
+
+        Revisions { originals, latest }
+    }
+
+    /// Get the original message of a message
+    ///
+    pub fn original(&self, message: Oid) -> Oid {
+        self.originals.get(&message).cloned().unwrap_or(message)
+    }
+
+    /// Get the latest revision of a message
+    ///
+    pub fn latest(&self, message: Oid) -> Oid {
+        self.latest.get(&message).cloned().unwrap_or(message)
+    }
+
+    /// Check whether a message is the latest revision of a message
+    ///
+    pub fn is_latest(&self, message: Oid) -> bool {
+        self.latest(message) == message
+    }
+
+    /// Get all revisions of a message, including the message itself
+    ///
+    /// The revisions are returned in no particular order.
+    ///
+    pub fn revisions(&self, message: Oid) -> Vec<Oid> {
+        let original = self.original(message);
+        self.originals
+            .iter()
+            .filter(|&(_, o)| *o == original)
+            .map(|(id, _)| *id)
+            .collect()
+    }
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::TestingRepo;
+
+    use git2;
+    use repository::RepositoryExt;
+
+    #[test]
+    fn revisions() {
+        let mut testing_repo = TestingRepo::new("revisions");
+        let repo = testing_repo.repo();
+
+        let sig = git2::Signature::now("Foo Bar", "foo.bar@example.com")
+            .expect("Could not create signature");
+        let empty_tree = repo
+            .empty_tree()
+            .expect("Could not create empty tree");
+
+        let issue = repo
+            .create_issue(&sig, &sig, "Test message 1", &empty_tree, vec![])
+            .expect("Could not create issue");
+        let initial_message = issue
+            .initial_message()
+            .expect("Could not retrieve initial message");
+        let message1 = issue
+            .add_message(&sig, &sig, "Test message 2", &empty_tree, vec![&initial_message])
+            .expect("Could not add message");
+        let message2 = issue
+            .amend_message(&sig, &sig, "Test message 3", &message1, None)
+            .expect("Could not amend message");
+        let later = git2::Signature::new("Foo Bar", "foo.bar@example.com", &git2::Time::new(sig.when().seconds() + 60, 0))
+            .expect("Could not create signature");
+        let message3 = issue
+            .amend_message(&later, &later, "Test message 4", &message2, None)
+            .expect("Could not amend message");
+
+        assert_eq!(superseded(&message3), Some(message2.id()));
+        assert_eq!(superseded(&message1), None);
+
+        let messages = vec![initial_message.clone(), message1.clone(), message2.clone(), message3.clone()];
+        let revisions = Revisions::new(&messages);
+        assert_eq!(revisions.original(message3.id()), message1.id());
+        assert_eq!(revisions.original(message2.id()), message1.id());
+        assert_eq!(revisions.latest(message1.id()), message3.id());
+        assert_eq!(revisions.latest(initial_message.id()), initial_message.id());
+        assert!(!revisions.is_latest(message2.id()));
+
+        let mut ids = vec![message1.id(), message2.id(), message3.id()];
+        ids.sort();
+        let mut history = revisions.revisions(message2.id());
+        history.sort();
+        assert_eq!(history, ids);
+
+        // superseded messages not in the set are ignored
+        let revisions = Revisions::new(&messages[2..]);
+        assert_eq!(revisions.original(message3.id()), message2.id());
+    }
+}
+
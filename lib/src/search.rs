@@ -0,0 +1,490 @@
+// git-dit - the distributed issue tracker for git
+// Copyright (C) 2017 Matthias Beyer <mail@beyermatthias.de>
+// Copyright (C) 2017 Julian Ganz <neither@nut.email>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+
+//! Full text search
+//!
+//! This module provides means for searching the text of messages, e.g. their
+//! subjects, bodies and trailers, for a regular expression. A `Pattern` finds
+//! matches within a single message and reports them as snippets of the lines
+//! containing them.
+//!
+//! Searching all messages of all issues requires reading every single message
+//! in the repository. For large repositories, a `SearchIndex` may be used. The
+//! index maps the words occurring in messages to the messages containing them
+//! and is stored in the `dit` directory inside the repository's git directory.
+//! For each issue, the index records the references it was computed for. The
+//! entries of an issue are recomputed if any of its references changed. Only
+//! patterns without any special characters benefit from the index. Other
+//! patterns are matched against all messages recorded in the index.
+//!
+
+use git2::{self, Commit, Oid, Repository};
+use regex::{Regex, RegexBuilder};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+
+use issue::{Issue, IssueRefType};
+use utils::{self, ResultIterExt};
+
+use error::*;
+use error::ErrorKind as EK;
+
+
+/// Identifier for the format of the search index
+///
+/// An index with a different header is considered invalid.
+///
+const INDEX_HEADER: &str = "dit-search-index 1";
+
+/// Number of characters included in a snippet before and after a match
+///
+const SNIPPET_CONTEXT: usize = 30;
+
+
+/// Search pattern
+///
+pub struct Pattern {
+    regex: Regex,
+    words: Option<Vec<String>>,
+}
+
+impl Pattern {
+    /// Create a new pattern from a regular expression
+    ///
+    pub fn new(pattern: &str, ignore_case: bool) -> Result<Self> {
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(ignore_case)
+            .build()
+            .chain_err(|| EK::MalformedRegex(pattern.to_owned()))?;
+
+        // Patterns consisting of plain text may be looked up in an index.
+        let words = if ::regex::escape(pattern) == pattern {
+            Some(words(pattern).collect())
+        } else {
+            None
+        };
+
+        Ok(Pattern { regex, words })
+    }
+
+    /// Get snippets for all matches in a message
+    ///
+    /// For each line of the message containing at least one match, a snippet
+    /// containing the first match and some context is returned.
+    ///
+    pub fn snippets(&self, message: &Commit) -> Vec<String> {
+        String::from_utf8_lossy(message.message_bytes())
+            .lines()
+            .filter_map(|line| self.regex.find(line).map(|m| snippet(line, m.start(), m.end())))
+            .collect()
+    }
+}
+
+
+/// A message matching a pattern
+///
+pub struct Match<'r> {
+    pub issue: Oid,
+    pub message: Commit<'r>,
+    pub snippets: Vec<String>,
+}
+
+
+/// Search all messages of an issue
+///
+/// Matches are returned in the order in which the messages are returned by
+/// `Issue::messages()`.
+///
+pub fn search_issue<'r>(issue: &Issue<'r>, pattern: &Pattern) -> Result<Vec<Match<'r>>> {
+    let mut retval = Vec::new();
+    for message in issue.messages()? {
+        let message = message?;
+        let snippets = pattern.snippets(&message);
+        if !snippets.is_empty() {
+            retval.push(Match { issue: issue.id(), message, snippets });
+        }
+    }
+    Ok(retval)
+}
+
+
+/// Messages of an issue, as recorded in a search index
+///
+#[derive(Debug, Default, PartialEq)]
+struct IndexedIssue {
+    refs: Vec<Oid>,
+    messages: Vec<Oid>,
+}
+
+
+/// Persistent inverted index for searching messages
+///
+/// The index maps the words in messages, converted to lowercase, to the
+/// messages containing them. Use `update()` for bringing the index up to date
+/// before searching.
+///
+/// Failing to store the index is not considered an error, since the index only
+/// exists for performance reasons. An unreadable or corrupted index is simply
+/// recomputed.
+///
+pub struct SearchIndex {
+    path: PathBuf,
+    issues: BTreeMap<Oid, IndexedIssue>,
+    terms: BTreeMap<String, BTreeSet<(Oid, Oid)>>,
+}
+
+impl SearchIndex {
+    /// Open the search index of a repository
+    ///
+    pub fn new(repo: &Repository) -> Self {
+        let mut retval = SearchIndex {
+            path: utils::dit_dir(repo).join("search-index"),
+            issues: BTreeMap::new(),
+            terms: BTreeMap::new(),
+        };
+        if retval.load().is_none() {
+            retval.issues.clear();
+            retval.terms.clear();
+        }
+        retval
+    }
+
+    /// Update the index for a set of issues
+    ///
+    /// Issues whose references changed since they were indexed are indexed
+    /// anew. Issues not in the set supplied are removed from the index.
+    ///
+    pub fn update<'a, 'r: 'a, I>(&mut self, issues: I) -> Result<()>
+        where I: IntoIterator<Item = &'a Issue<'r>>
+    {
+        let mut changed = false;
+        let mut current = BTreeSet::new();
+
+        for issue in issues {
+            current.insert(issue.id());
+
+            let mut refs: Vec<Oid> = issue
+                .all_refs(IssueRefType::Any)?
+                .map(|reference| reference?.peel(git2::ObjectType::Commit).map(|c| c.id()))
+                .collect_result()?;
+            refs.sort();
+            refs.dedup();
+            if self.issues.get(&issue.id()).is_some_and(|indexed| indexed.refs == refs) {
+                continue;
+            }
+
+            self.remove(issue.id());
+            let mut messages = Vec::new();
+            for message in issue.messages()? {
+                let message = message?;
+                let text = String::from_utf8_lossy(message.message_bytes()).to_lowercase();
+                for word in words(&text) {
+                    self.terms
+                        .entry(word)
+                        .or_default()
+                        .insert((issue.id(), message.id()));
+                }
+                messages.push(message.id());
+            }
+            self.issues.insert(issue.id(), IndexedIssue { refs, messages });
+            changed = true;
+        }
+
+        let obsolete: Vec<Oid> = self
+            .issues
+            .keys()
+            .filter(|id| !current.contains(id))
+            .cloned()
+            .collect();
+        for id in obsolete {
+            self.remove(id);
+            changed = true;
+        }
+
+        if changed {
+            // A failure to store the index doesn't affect the result.
+            self.store().ok();
+        }
+        Ok(())
+    }
+
+    /// Search the messages recorded in the index
+    ///
+    /// Only messages which may contain a match according to the index are
+    /// checked against the pattern. Matches are ordered by issue.
+    ///
+    pub fn search<'r>(&self, repo: &'r Repository, pattern: &Pattern) -> Result<Vec<Match<'r>>> {
+        let mut retval = Vec::new();
+        for (issue, message) in self.candidates(pattern) {
+            let message = repo.find_commit(message)?;
+            let snippets = pattern.snippets(&message);
+            if !snippets.is_empty() {
+                retval.push(Match { issue, message, snippets });
+            }
+        }
+        Ok(retval)
+    }
+
+    /// Get the messages which may match a pattern
+    ///
+    /// For patterns consisting of plain text, a message is a candidate if each
+    /// word in the pattern is part of a word in the message. Since words at
+    /// the boundaries of the pattern may be part of longer words, all terms
+    /// containing a word are considered.
+    ///
+    fn candidates(&self, pattern: &Pattern) -> BTreeSet<(Oid, Oid)> {
+        let all = || self
+            .issues
+            .iter()
+            .flat_map(|(issue, indexed)| indexed.messages.iter().map(move |message| (*issue, *message)))
+            .collect();
+
+        let words = match pattern.words {
+            Some(ref words) if !words.is_empty() => words,
+            _ => return all(),
+        };
+
+        let mut retval: Option<BTreeSet<(Oid, Oid)>> = None;
+        for word in words {
+            let found: BTreeSet<(Oid, Oid)> = self
+                .terms
+                .iter()
+                .filter(|(term, _)| term.contains(word.as_str()))
+                .flat_map(|(_, postings)| postings.iter().cloned())
+                .collect();
+            retval = Some(match retval {
+                Some(previous) => previous.intersection(&found).cloned().collect(),
+                None => found,
+            });
+        }
+        retval.unwrap_or_default()
+    }
+
+    /// Remove an issue from the index
+    ///
+    fn remove(&mut self, issue: Oid) {
+        if self.issues.remove(&issue).is_none() {
+            return;
+        }
+        for postings in self.terms.values_mut() {
+            postings.retain(|(id, _)| *id != issue);
+        }
+        self.terms.retain(|_, postings| !postings.is_empty());
+    }
+
+    /// Load the index
+    ///
+    /// The index is stored as a list of issues, each followed by its messages,
+    /// and a list of terms, each followed by the numbers of the messages
+    /// containing it.
+    ///
+    fn load(&mut self) -> Option<()> {
+        let file = fs::File::open(&self.path).ok()?;
+        let mut lines = io::BufReader::new(file).lines();
+
+        if lines.next()?.ok()? != INDEX_HEADER {
+            return None;
+        }
+
+        let mut messages: Vec<(Oid, Oid)> = Vec::new();
+        let mut issue: Option<Oid> = None;
+        for line in lines {
+            let line = line.ok()?;
+            let mut parts = line.split(' ');
+            match parts.next()? {
+                "issue" => {
+                    let id = Oid::from_str(parts.next()?).ok()?;
+                    let refs = parts.map(Oid::from_str).collect::<::std::result::Result<_, _>>().ok()?;
+                    self.issues.insert(id, IndexedIssue { refs, messages: Vec::new() });
+                    issue = Some(id);
+                },
+                "message" => {
+                    let id = Oid::from_str(parts.next()?).ok()?;
+                    let issue = issue?;
+                    self.issues.get_mut(&issue)?.messages.push(id);
+                    messages.push((issue, id));
+                },
+                "term" => {
+                    let term = parts.next()?.to_owned();
+                    let mut postings = BTreeSet::new();
+                    for number in parts {
+                        postings.insert(*messages.get(number.parse::<usize>().ok()?)?);
+                    }
+                    self.terms.insert(term, postings);
+                },
+                _ => return None,
+            }
+        }
+        Some(())
+    }
+
+    /// Store the index
+    ///
+    fn store(&self) -> Result<()> {
+        let dir = self.path.parent().unwrap_or(&self.path).to_owned();
+        let tmp_path = dir.join(".search-index.tmp");
+
+        fs::create_dir_all(&dir)
+            .and_then(|_| {
+                let mut file = io::BufWriter::new(fs::File::create(&tmp_path)?);
+                writeln!(file, "{}", INDEX_HEADER)?;
+
+                let mut numbers = BTreeMap::new();
+                for (id, indexed) in self.issues.iter() {
+                    write!(file, "issue {}", id)?;
+                    for reference in indexed.refs.iter() {
+                        write!(file, " {}", reference)?;
+                    }
+                    writeln!(file)?;
+                    for message in indexed.messages.iter() {
+                        writeln!(file, "message {}", message)?;
+                        let number = numbers.len();
+                        numbers.insert((*id, *message), number);
+                    }
+                }
+
+                for (term, postings) in self.terms.iter() {
+                    write!(file, "term {}", term)?;
+                    for posting in postings.iter() {
+                        if let Some(number) = numbers.get(posting) {
+                            write!(file, " {}", number)?;
+                        }
+                    }
+                    writeln!(file)?;
+                }
+                file.flush()
+            })
+            .and_then(|_| fs::rename(&tmp_path, &self.path))
+            .chain_err(|| EK::CannotWriteSearchIndex(self.path.to_string_lossy().into_owned()))
+    }
+}
+
+
+/// Split a text into words
+///
+/// Words are sequences of alphanumeric characters. They are returned as
+/// lowercase strings.
+///
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
+
+
+/// Create a snippet for a match within a line
+///
+/// The snippet contains the match itself as well as some context. Omitted
+/// portions of the line are indicated by an ellipsis.
+///
+fn snippet(line: &str, start: usize, end: usize) -> String {
+    let from = line[..start]
+        .char_indices()
+        .rev()
+        .nth(SNIPPET_CONTEXT - 1)
+        .map(|(pos, _)| pos)
+        .unwrap_or(0);
+    let to = line[end..]
+        .char_indices()
+        .nth(SNIPPET_CONTEXT)
+        .map(|(pos, _)| end + pos)
+        .unwrap_or(line.len());
+
+    let mut retval = String::new();
+    if from > 0 {
+        retval.push_str("...");
+    }
+    retval.push_str(line[from..to].trim());
+    if to < line.len() {
+        retval.push_str("...");
+    }
+    retval
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::TestingRepo;
+
+    use repository::RepositoryExt;
+
+    #[test]
+    fn snippets() {
+        let line = "The quick brown fox jumps over the lazy dog. It is very quick indeed, but the dog is lazy.";
+        assert_eq!(snippet(line, 16, 19), "The quick brown fox jumps over the lazy dog. It i...");
+        assert_eq!(snippet(line, 78, 81), "...is very quick indeed, but the dog is lazy.");
+        assert_eq!(snippet("foo", 0, 3), "foo");
+    }
+
+    #[test]
+    fn search() {
+        let mut testing_repo = TestingRepo::new("search");
+        let repo = testing_repo.repo();
+
+        let sig = git2::Signature::now("Foo Bar", "foo.bar@example.com")
+            .expect("Could not create signature");
+        let empty_tree = repo
+            .empty_tree()
+            .expect("Could not create empty tree");
+
+        let issue1 = repo
+            .create_issue(&sig, &sig, "Crash on startup\n\nThe program crashes.", &empty_tree, vec![])
+            .expect("Could not create issue");
+        let initial_message = issue1
+            .initial_message()
+            .expect("Could not retrieve initial message");
+        let reply = issue1
+            .add_message(&sig, &sig, "Re: Crash\n\nDit-status: closed", &empty_tree, vec![&initial_message])
+            .expect("Could not add message");
+        let issue2 = repo
+            .create_issue(&sig, &sig, "Feature request\n\nPlease add a status bar.", &empty_tree, vec![])
+            .expect("Could not create issue");
+
+        let ids = |matches: Vec<Match>| -> Vec<Oid> {
+            let mut ids: Vec<Oid> = matches.into_iter().map(|m| m.message.id()).collect();
+            ids.sort();
+            ids
+        };
+        let mut expected = vec![initial_message.id(), reply.id()];
+        expected.sort();
+
+        let pattern = Pattern::new("crash", true).expect("Could not create pattern");
+        let matches = search_issue(&issue1, &pattern).expect("Could not search issue");
+        assert_eq!(ids(matches), expected);
+        assert!(search_issue(&issue2, &pattern).expect("Could not search issue").is_empty());
+
+        let pattern = Pattern::new("crash", false).expect("Could not create pattern");
+        let matches = search_issue(&issue1, &pattern).expect("Could not search issue");
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].snippets, vec!["The program crashes.".to_owned()]);
+
+        let mut index = repo.search_index();
+        index.update(vec![&issue1, &issue2]).expect("Could not update index");
+
+        let index = repo.search_index();
+        let pattern = Pattern::new("crash", true).expect("Could not create pattern");
+        assert_eq!(ids(index.search(repo, &pattern).expect("Could not search")), expected);
+        let pattern = Pattern::new("status", true).expect("Could not create pattern");
+        let matches = index.search(repo, &pattern).expect("Could not search");
+        assert_eq!(matches.len(), 2);
+        let pattern = Pattern::new("st.tus: c", true).expect("Could not create pattern");
+        assert_eq!(ids(index.search(repo, &pattern).expect("Could not search")), vec![reply.id()]);
+
+        let mut index = repo.search_index();
+        index.update(vec![&issue2]).expect("Could not update index");
+        let pattern = Pattern::new("crash", true).expect("Could not create pattern");
+        assert!(index.search(repo, &pattern).expect("Could not search").is_empty());
+    }
+}
+
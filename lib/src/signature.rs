@@ -0,0 +1,688 @@
+// git-dit - the distributed issue tracker for git
+// Copyright (C) 2017 Matthias Beyer <mail@beyermatthias.de>
+// Copyright (C) 2017 Julian Ganz <neither@nut.email>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+
+//! Message signatures
+//!
+//! Messages are commits and may thus carry a signature, just like any other
+//! commit. Since the author of a message may be impersonated easily, the
+//! signature is the only reliable indication of who created a message.
+//!
+//! This module provides the `Signer` and `Verifier` traits for creating and
+//! verifying signatures, the `Trust` type representing the outcome of a
+//! verification and the `Program` type, which implements both traits using
+//! the programs git uses for the same purpose. Like git, `Program` supports
+//! OpenPGP, X.509 and SSH signatures and is configured via the `gpg.format`,
+//! `gpg.program`, `gpg.<format>.program`, `user.signingkey` and
+//! `gpg.ssh.allowedSignersFile` options.
+//!
+//! For accumulating only metadata from messages carrying a good signature,
+//! this module provides the `VerifiedSigners` authorization.
+//!
+
+use git2::{self, Commit, Oid, Repository};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use trailer::accumulation::{Authorization, AuthorizationContext, AuthorizedPeople};
+use utils::dit_dir;
+
+use error::*;
+use error::ErrorKind as EK;
+
+
+/// Trust in the signature of a message
+///
+#[derive(Clone, Debug, PartialEq)]
+pub enum Trust {
+    /// The message doesn't carry a signature
+    Unsigned,
+    /// The message carries a good signature by a trusted signer
+    Good(String),
+    /// The message carries a good signature by an unknown or untrusted signer
+    Untrusted(Option<String>),
+    /// The signature could not be checked, e.g. because the key is missing
+    Unverifiable,
+    /// The message carries a bad signature
+    Bad,
+}
+
+impl Trust {
+    /// Check whether the message carries a good signature by a trusted signer
+    ///
+    pub fn is_good(&self) -> bool {
+        matches!(*self, Trust::Good(_))
+    }
+
+    /// Get the signer, if known
+    ///
+    pub fn signer(&self) -> Option<&str> {
+        match *self {
+            Trust::Good(ref signer) => Some(signer),
+            Trust::Untrusted(ref signer) => signer.as_ref().map(String::as_str),
+            _ => None,
+        }
+    }
+
+    /// Get the identity of the person who signed a message
+    ///
+    /// Only good signatures by trusted signers identify a person. If the
+    /// signer, e.g. the user id of an OpenPGP key or the principal of an SSH
+    /// key, carries the email address of the message's author, the author is
+    /// returned as `Name <email>`. Otherwise, the signer is returned as
+    /// reported by the verifier.
+    ///
+    pub fn identity(&self, message: &Commit) -> Option<String> {
+        let signer = match *self {
+            Trust::Good(ref signer) => signer.trim(),
+            _ => return None,
+        };
+        let email = match (signer.find('<'), signer.rfind('>')) {
+            (Some(start), Some(end)) if start < end => &signer[start + 1..end],
+            _ => signer,
+        };
+
+        let author = message.author();
+        if author.email().is_some_and(|author| author.eq_ignore_ascii_case(email)) {
+            Some(author.to_string())
+        } else {
+            Some(signer.to_owned())
+        }
+    }
+}
+
+impl fmt::Display for Trust {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Trust::Unsigned => write!(f, "none"),
+            Trust::Good(ref signer) => write!(f, "good ({})", signer),
+            Trust::Untrusted(Some(ref signer)) => write!(f, "untrusted ({})", signer),
+            Trust::Untrusted(None) => write!(f, "untrusted"),
+            Trust::Unverifiable => write!(f, "unverifiable"),
+            Trust::Bad => write!(f, "bad"),
+        }
+    }
+}
+
+
+/// Signer for messages
+///
+pub trait Signer {
+    /// Create a detached signature for the data supplied
+    ///
+    fn sign(&self, data: &str) -> Result<String>;
+}
+
+
+/// Verifier for signatures of messages
+///
+pub trait Verifier {
+    /// Verify a detached signature for the data supplied
+    ///
+    fn verify(&self, signature: &str, data: &str) -> Result<Trust>;
+}
+
+
+/// Verify the signature of a message
+///
+pub fn verify<V>(repo: &Repository, message: &Commit, verifier: &V) -> Result<Trust>
+    where V: Verifier + ?Sized
+{
+    match repo.extract_signature(&message.id(), None) {
+        Ok((signature, data)) => {
+            let signature = String::from_utf8_lossy(&signature);
+            let data = String::from_utf8_lossy(&data);
+            verifier.verify(&signature, &data)
+        },
+        Err(ref err) if err.code() == git2::ErrorCode::NotFound => Ok(Trust::Unsigned),
+        Err(err) => Err(err.into()),
+    }
+}
+
+
+/// Authorization of messages carrying a good signature
+///
+/// This authorization authorizes messages carrying a good signature by a
+/// trusted signer, as determined by the verifier supplied.
+///
+pub struct VerifiedSigners<'r, V>
+    where V: Verifier + ?Sized + 'r
+{
+    repo: &'r Repository,
+    verifier: &'r V,
+}
+
+impl<'r, V> VerifiedSigners<'r, V>
+    where V: Verifier + ?Sized
+{
+    /// Create a new authorization using the verifier supplied
+    ///
+    pub fn new(repo: &'r Repository, verifier: &'r V) -> Self {
+        VerifiedSigners { repo, verifier }
+    }
+}
+
+impl<'r, V> Authorization for VerifiedSigners<'r, V>
+    where V: Verifier + ?Sized
+{
+    fn authorizes(&self, message: &Commit, _: &AuthorizationContext) -> Result<bool> {
+        verify(self.repo, message, self.verifier).map(|trust| trust.is_good())
+    }
+}
+
+
+/// Authorization of people identified via signatures
+///
+/// This authorization authorizes messages carrying a good signature by a
+/// trusted signer if the signer, as identified by `Trust::identity()`, is one
+/// of the people supplied. Unlike `AuthorizedPeople`, it doesn't rely on the
+/// author of a message, which may be set to anything.
+///
+pub struct VerifiedPeople<'r, V>
+    where V: Verifier
+{
+    repo: &'r Repository,
+    verifier: V,
+    people: AuthorizedPeople,
+}
+
+impl<'r, V> VerifiedPeople<'r, V>
+    where V: Verifier
+{
+    /// Create a new authorization using the verifier supplied
+    ///
+    pub fn new(repo: &'r Repository, verifier: V, people: AuthorizedPeople) -> Self {
+        VerifiedPeople { repo, verifier, people }
+    }
+}
+
+impl<'r, V> Authorization for VerifiedPeople<'r, V>
+    where V: Verifier
+{
+    fn authorizes(&self, message: &Commit, context: &AuthorizationContext) -> Result<bool> {
+        Ok(verify(self.repo, message, &self.verifier)?
+            .identity(message)
+            .is_some_and(|person| self.people.includes(&person, context)))
+    }
+}
+
+
+/// Create a signed commit
+///
+/// This function creates a commit like `git2::Repository::commit()`, signed
+/// using the signer supplied. No reference is updated.
+///
+pub fn create_signed_commit<S>(repo: &Repository,
+                               author: &git2::Signature,
+                               committer: &git2::Signature,
+                               message: &str,
+                               tree: &git2::Tree,
+                               parents: &[&Commit],
+                               signer: &S
+) -> Result<Oid>
+    where S: Signer + ?Sized
+{
+    let buffer = repo.commit_create_buffer(author, committer, message, tree, parents)?;
+    let buffer = buffer.as_str().ok_or_else(|| Error::from_kind(EK::CannotCreateMessage))?;
+    let signature = signer.sign(buffer)?;
+    repo.commit_signed(buffer, &signature, None).chain_err(|| EK::CannotCreateMessage)
+}
+
+
+/// Format of signatures
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SignatureFormat {
+    OpenPgp,
+    X509,
+    Ssh,
+}
+
+impl SignatureFormat {
+    /// Get the program used for the format by default
+    ///
+    fn default_program(&self) -> &'static str {
+        match *self {
+            SignatureFormat::OpenPgp => "gpg",
+            SignatureFormat::X509    => "gpgsm",
+            SignatureFormat::Ssh     => "ssh-keygen",
+        }
+    }
+}
+
+impl FromStr for SignatureFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "openpgp"   => Ok(SignatureFormat::OpenPgp),
+            "x509"      => Ok(SignatureFormat::X509),
+            "ssh"       => Ok(SignatureFormat::Ssh),
+            _           => Err(Error::from_kind(EK::UnknownSignatureFormat(s.to_owned()))),
+        }
+    }
+}
+
+
+/// Signing and verification via external programs
+///
+/// OpenPGP and X.509 signatures are created and verified using `gpg` and
+/// `gpgsm`, respectively. SSH signatures are created and verified using
+/// `ssh-keygen`. SSH signatures are only trusted if the signer is listed in the
+/// allowed signers file.
+///
+/// Signatures to verify are passed to the programs via temporary files. Those
+/// are created in the system's directory for temporary files, unless a
+/// directory is specified.
+///
+#[derive(Clone, Debug)]
+pub struct Program {
+    format: SignatureFormat,
+    program: String,
+    key: Option<String>,
+    allowed_signers: Option<PathBuf>,
+    temp_dir: Option<PathBuf>,
+}
+
+impl Program {
+    /// Create a new signing program for a given format
+    ///
+    /// The default program for the format will be used.
+    ///
+    pub fn new(format: SignatureFormat) -> Self {
+        Program {
+            format,
+            program: format.default_program().to_owned(),
+            key: None,
+            allowed_signers: None,
+            temp_dir: None,
+        }
+    }
+
+    /// Create a signing program from a configuration
+    ///
+    pub fn from_config(config: &git2::Config) -> Result<Self> {
+        let format = match config.get_string("gpg.format") {
+            Ok(format) => format.parse()?,
+            Err(_) => SignatureFormat::OpenPgp,
+        };
+        let program_var = match format {
+            SignatureFormat::OpenPgp => "gpg.openpgp.program",
+            SignatureFormat::X509    => "gpg.x509.program",
+            SignatureFormat::Ssh     => "gpg.ssh.program",
+        };
+
+        let mut retval = Self::new(format);
+        let program = config
+            .get_string(program_var)
+            .ok()
+            .or_else(|| Some(format)
+                .filter(|format| *format == SignatureFormat::OpenPgp)
+                .and_then(|_| config.get_string("gpg.program").ok())
+            );
+        if let Some(program) = program {
+            retval.program = program;
+        }
+        retval.key = config.get_string("user.signingkey").ok();
+        retval.allowed_signers = config.get_path("gpg.ssh.allowedSignersFile").ok();
+        Ok(retval)
+    }
+
+    /// Create a signing program for a repository
+    ///
+    /// The program is configured via the repository's configuration. Temporary
+    /// files are created in the directory holding git-dit specific data.
+    ///
+    pub fn for_repository(repo: &Repository) -> Result<Self> {
+        let config = repo.config()?;
+        Self::from_config(&config).map(|program| program.with_temp_dir(dit_dir(repo)))
+    }
+
+    /// Use a specific directory for temporary files
+    ///
+    pub fn with_temp_dir(mut self, dir: PathBuf) -> Self {
+        self.temp_dir = Some(dir);
+        self
+    }
+
+    /// Use a specific key for signing
+    ///
+    pub fn with_key(mut self, key: Option<String>) -> Self {
+        if key.is_some() {
+            self.key = key;
+        }
+        self
+    }
+
+    /// Get the format of signatures
+    ///
+    pub fn format(&self) -> SignatureFormat {
+        self.format
+    }
+
+    /// Run the program with some input
+    ///
+    /// Returns whether the program exited successfully, along with its output.
+    ///
+    fn run(&self, command: &mut Command, input: &str) -> Result<(bool, String)> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .chain_err(|| EK::CannotRunProgram(self.program.clone()))?;
+        child
+            .stdin
+            .take()
+            .map(|mut stdin| stdin.write_all(input.as_bytes()))
+            .transpose()
+            .chain_err(|| EK::CannotRunProgram(self.program.clone()))?;
+        let output = child
+            .wait_with_output()
+            .chain_err(|| EK::CannotRunProgram(self.program.clone()))?;
+        let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        Ok((output.status.success(), stdout))
+    }
+
+    /// Verify a GPG signature via the status output of `gpg` or `gpgsm`
+    ///
+    fn verify_gpg(&self, signature: &TempFile, data: &str) -> Result<Trust> {
+        // NOTE: `gpg` exits with a non-zero status for bad signatures and
+        //       missing keys. We evaluate the status output in any case.
+        let (_, output) = self.run(
+            Command::new(&self.program)
+                .arg("--status-fd=1")
+                .arg("--verify")
+                .arg(signature.path())
+                .arg("-"),
+            data
+        )?;
+
+        let mut trust = Trust::Unverifiable;
+        for line in output.lines() {
+            let mut parts = line.splitn(4, ' ');
+            if parts.next() != Some("[GNUPG:]") {
+                continue;
+            }
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some("GOODSIG"), _, signer) => trust = Trust::Good(signer.unwrap_or_default().to_owned()),
+                (Some("BADSIG"), _, _) => return Ok(Trust::Bad),
+                (Some("ERRSIG"), _, _) => return Ok(Trust::Unverifiable),
+                (Some(status), _, _) if status == "TRUST_UNDEFINED" || status == "TRUST_NEVER" => {
+                    if let Trust::Good(signer) = trust {
+                        trust = Trust::Untrusted(Some(signer));
+                    }
+                },
+                (Some(status), _, _) if status == "EXPSIG" || status == "EXPKEYSIG" || status == "REVKEYSIG" => {
+                    return Ok(Trust::Untrusted(None));
+                },
+                _ => {},
+            }
+        }
+        Ok(trust)
+    }
+
+    /// Verify an SSH signature using `ssh-keygen`
+    ///
+    fn verify_ssh(&self, signature: &TempFile, data: &str) -> Result<Trust> {
+        if let Some(ref allowed) = self.allowed_signers {
+            let principal = self
+                .run(
+                    Command::new(&self.program)
+                        .args(["-Y", "find-principals", "-f"])
+                        .arg(allowed)
+                        .arg("-s")
+                        .arg(signature.path()),
+                    ""
+                )
+                .map(|(found, output)| Some(output).filter(|_| found))?
+                .and_then(|output| output.lines().next().map(str::to_owned))
+                .filter(|principal| !principal.is_empty());
+            if let Some(principal) = principal {
+                let (verified, _) = self.run(
+                    Command::new(&self.program)
+                        .args(["-Y", "verify", "-n", "git", "-f"])
+                        .arg(allowed)
+                        .arg("-I")
+                        .arg(&principal)
+                        .arg("-s")
+                        .arg(signature.path()),
+                    data
+                )?;
+                return Ok(if verified { Trust::Good(principal) } else { Trust::Bad });
+            }
+        }
+
+        let (valid, _) = self.run(
+            Command::new(&self.program)
+                .args(["-Y", "check-novalidate", "-n", "git", "-s"])
+                .arg(signature.path()),
+            data
+        )?;
+        Ok(if valid { Trust::Untrusted(None) } else { Trust::Bad })
+    }
+}
+
+impl Signer for Program {
+    fn sign(&self, data: &str) -> Result<String> {
+        let signature = match self.format {
+            SignatureFormat::OpenPgp | SignatureFormat::X509 => {
+                let mut command = Command::new(&self.program);
+                command.arg("--status-fd=2").arg("-bsa");
+                if let Some(ref key) = self.key {
+                    command.arg("-u").arg(key);
+                }
+                let (signed, signature) = self.run(&mut command, data)?;
+                Some(signature).filter(|_| signed)
+            },
+            SignatureFormat::Ssh => {
+                let key = self
+                    .key
+                    .as_ref()
+                    .ok_or_else(|| Error::from_kind(EK::CannotSign))?;
+                // Without any file, `ssh-keygen` signs the data on stdin
+                // and writes the signature to stdout.
+                let (signed, signature) = self.run(
+                    Command::new(&self.program)
+                        .args(["-Y", "sign", "-n", "git", "-f"])
+                        .arg(key),
+                    data
+                )?;
+                Some(signature).filter(|_| signed)
+            },
+        };
+        signature
+            .filter(|signature| !signature.trim().is_empty())
+            .ok_or_else(|| Error::from_kind(EK::CannotSign))
+    }
+}
+
+impl Verifier for Program {
+    fn verify(&self, signature: &str, data: &str) -> Result<Trust> {
+        let dir = self.temp_dir.clone().unwrap_or_else(env::temp_dir);
+        let file = TempFile::new(&dir, signature)?;
+        match self.format {
+            SignatureFormat::OpenPgp | SignatureFormat::X509 => self.verify_gpg(&file, data),
+            SignatureFormat::Ssh => self.verify_ssh(&file, data),
+        }
+    }
+}
+
+
+/// Temporary file, removed when dropped
+///
+struct TempFile(PathBuf);
+
+impl TempFile {
+    /// Create a temporary file with the content supplied
+    ///
+    /// The file is created in the directory supplied, which is created if it
+    /// doesn't exist. We never open an existing file, since it might be a
+    /// symlink placed by someone else.
+    ///
+    fn new(dir: &Path, content: &str) -> Result<Self> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        fs::create_dir_all(dir).chain_err(|| EK::CannotSign)?;
+        loop {
+            let name = format!(
+                "dit-signature-{}-{}",
+                ::std::process::id(),
+                COUNTER.fetch_add(1, Ordering::SeqCst)
+            );
+            let path = dir.join(name);
+            let file = fs::OpenOptions::new().write(true).create_new(true).open(&path);
+            let mut file = match file {
+                Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+                file => file.chain_err(|| EK::CannotSign)?,
+            };
+
+            // The file is removed if we fail to write the content.
+            let retval = TempFile(path);
+            file.write_all(content.as_bytes()).chain_err(|| EK::CannotSign)?;
+            return Ok(retval);
+        }
+    }
+
+    /// Get the path of the file
+    ///
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        fs::remove_file(&self.0).ok();
+    }
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::TestingRepo;
+
+    use repository::RepositoryExt;
+
+    /// Signer and verifier using a trivial signature scheme
+    ///
+    struct Trivial;
+
+    impl Signer for Trivial {
+        fn sign(&self, data: &str) -> Result<String> {
+            Ok(format!("trivial {}\n", data.len()))
+        }
+    }
+
+    impl Verifier for Trivial {
+        fn verify(&self, signature: &str, data: &str) -> Result<Trust> {
+            if signature.trim() == format!("trivial {}", data.len()) {
+                Ok(Trust::Good("Foo Bar <foo.bar@example.com>".to_owned()))
+            } else {
+                Ok(Trust::Bad)
+            }
+        }
+    }
+
+    #[test]
+    fn signed_messages() {
+        let mut testing_repo = TestingRepo::new("signed_messages");
+        let repo = testing_repo.repo();
+
+        let sig = git2::Signature::now("Foo Bar", "foo.bar@example.com")
+            .expect("Could not create signature");
+        let empty_tree = repo
+            .empty_tree()
+            .expect("Could not create empty tree");
+
+        let issue = repo
+            .create_signed_issue(&sig, &sig, "Test message 1", &empty_tree, vec![], &Trivial)
+            .expect("Could not create issue");
+        let initial_message = issue
+            .initial_message()
+            .expect("Could not retrieve initial message");
+        assert!(verify(repo, &initial_message, &Trivial).unwrap().is_good());
+
+        let message = issue
+            .add_signed_message(&sig, &sig, "Test message 2", &empty_tree, vec![&initial_message], &Trivial)
+            .expect("Could not add message");
+        assert_eq!(message.parent_ids().collect::<Vec<_>>(), vec![initial_message.id()]);
+        assert_eq!(
+            verify(repo, &message, &Trivial).unwrap(),
+            Trust::Good("Foo Bar <foo.bar@example.com>".to_owned())
+        );
+
+        let message = issue
+            .add_message(&sig, &sig, "Test message 3", &empty_tree, vec![&message])
+            .expect("Could not add message");
+        assert_eq!(verify(repo, &message, &Trivial).unwrap(), Trust::Unsigned);
+
+        let authorization = VerifiedSigners::new(repo, &Trivial);
+        let context = Default::default();
+        assert!(authorization.authorizes(&initial_message, &context).unwrap());
+        assert!(!authorization.authorizes(&message, &context).unwrap());
+
+        // the unsigned message is not attributed to its author
+        let authorization = VerifiedPeople::new(repo, Trivial, AuthorizedPeople::new(vec!["Foo Bar <foo.bar@example.com>"]));
+        assert!(authorization.authorizes(&initial_message, &context).unwrap());
+        assert!(!authorization.authorizes(&message, &context).unwrap());
+
+        let authorization = VerifiedPeople::new(repo, Trivial, AuthorizedPeople::new(vec!["Baz Qux <baz.qux@example.com>"]));
+        assert!(!authorization.authorizes(&initial_message, &context).unwrap());
+    }
+
+    #[test]
+    fn signature_format() {
+        assert_eq!("ssh".parse::<SignatureFormat>().unwrap(), SignatureFormat::Ssh);
+        assert_eq!("openpgp".parse::<SignatureFormat>().unwrap(), SignatureFormat::OpenPgp);
+        assert!("foo".parse::<SignatureFormat>().is_err());
+
+        let mut testing_repo = TestingRepo::new("signature_format");
+        let mut config = testing_repo.repo().config().expect("Could not get config");
+        config.set_str("gpg.format", "ssh").unwrap();
+        config.set_str("user.signingkey", "/foo/bar").unwrap();
+        let program = Program::from_config(&config).expect("Could not create program");
+        assert_eq!(program.format(), SignatureFormat::Ssh);
+        assert_eq!(program.program, "ssh-keygen");
+        assert_eq!(program.key.as_deref(), Some("/foo/bar"));
+    }
+
+    #[test]
+    fn trust_identity() {
+        let mut testing_repo = TestingRepo::new("trust_identity");
+        let repo = testing_repo.repo();
+
+        let sig = git2::Signature::now("Foo Bar", "foo.bar@example.com")
+            .expect("Could not create signature");
+        let empty_tree = repo
+            .empty_tree()
+            .expect("Could not create empty tree");
+        let message = repo
+            .create_issue(&sig, &sig, "Test message 1", &empty_tree, vec![])
+            .expect("Could not create issue")
+            .initial_message()
+            .expect("Could not retrieve initial message");
+
+        let good = |signer: &str| Trust::Good(signer.to_owned()).identity(&message);
+        assert_eq!(good("Foo Bar <foo.bar@example.com>"), Some("Foo Bar <foo.bar@example.com>".to_owned()));
+        assert_eq!(good("Foo <FOO.BAR@example.com>"), Some("Foo Bar <foo.bar@example.com>".to_owned()));
+        assert_eq!(good("foo.bar@example.com"), Some("Foo Bar <foo.bar@example.com>".to_owned()));
+        assert_eq!(good("Baz Qux <baz.qux@example.com>"), Some("Baz Qux <baz.qux@example.com>".to_owned()));
+        assert_eq!(Trust::Untrusted(Some("foo.bar@example.com".to_owned())).identity(&message), None);
+        assert_eq!(Trust::Unsigned.identity(&message), None);
+    }
+}
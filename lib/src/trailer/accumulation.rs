@@ -13,21 +13,55 @@
 //! module provides functionality for accumulating trailers and forming sets of
 //! metadata.
 //!
+//! Values of pieces of metadata accumulated as sets, e.g. labels, may be added
+//! or removed over the lifetime of an issue. A value prefixed with `+` adds the
+//! value to the set, a value prefixed with `-` removes it. Values without a
+//! prefix are added. Alternatively, values may be added and removed via
+//! trailers with the key of the piece of metadata suffixed with `-add` or
+//! `-remove`, e.g. `Dit-label-add`.
+//!
+//! By default, trailers from all messages are accumulated. Since anyone may
+//! author a message, trailers may also be accumulated using an
+//! `AuthorizingAccumulator`, which only honours trailers from messages whose
+//! authors are authorized by some `Authorization`. `AuthorizedPeople`
+//! authorizes configured maintainers as well as the issue's reporter and
+//! current assignee.
+//!
 
+use git2::Commit;
 use std::collections;
+use std::collections::BTreeMap;
 use std::hash::BuildHasher;
+use std::str::FromStr;
 
 use trailer::{Trailer, TrailerValue};
 
+use error::*;
+use error::ErrorKind as EK;
+
 /// Policy for accumulating trailers
 ///
 /// These enum values represent accumulation policies for trailers, e.g. how
 /// trailer values are accumulated.
 ///
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum AccumulationPolicy {
     Latest,
     List,
+    Set,
+}
+
+impl FromStr for AccumulationPolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "latest"    => Ok(AccumulationPolicy::Latest),
+            "list"      => Ok(AccumulationPolicy::List),
+            "set"       => Ok(AccumulationPolicy::Set),
+            _           => Err(Error::from_kind(EK::UnknownAccumulationPolicy(s.to_owned()))),
+        }
+    }
 }
 
 
@@ -36,10 +70,19 @@ pub enum AccumulationPolicy {
 /// This type encapsulates the task of accumulating trailers in an appropriate
 /// data structure.
 ///
+/// A set maps each value encountered to either the value, if it is part of the
+/// set, or `None`, if it was removed. Since values are processed starting with
+/// the most recent one, only the first operation on a value is honoured.
+///
+/// An empty value accumulated as the latest value unsets the piece of
+/// metadata: it is retained, shadowing older values, but not yielded when
+/// iterating over the accumulator.
+///
 #[derive(Clone)]
 pub enum ValueAccumulator {
     Latest(Option<TrailerValue>),
     List(Vec<TrailerValue>),
+    Set(BTreeMap<String, Option<TrailerValue>>),
 }
 
 impl ValueAccumulator {
@@ -51,8 +94,25 @@ impl ValueAccumulator {
                 *value = Some(new_value);
             },
             &mut ValueAccumulator::List(ref mut values)  => values.push(new_value),
+            &mut ValueAccumulator::Set(ref mut values)   => {
+                let string = new_value.to_string();
+                let string = string.trim();
+                let (name, value) = if let Some(name) = string.strip_prefix('-') {
+                    (name, None)
+                } else {
+                    let name = string.strip_prefix('+').unwrap_or(string);
+                    (name, Some(TrailerValue::from_slice(name)))
+                };
+                values.entry(name.to_owned()).or_insert(value);
+            },
         }
     }
+
+    /// Check whether the accumulator accumulates values as a set
+    ///
+    pub fn is_set(&self) -> bool {
+        matches!(*self, ValueAccumulator::Set(_))
+    }
 }
 
 impl From<AccumulationPolicy> for ValueAccumulator {
@@ -60,6 +120,7 @@ impl From<AccumulationPolicy> for ValueAccumulator {
         match policy {
             AccumulationPolicy::Latest  => ValueAccumulator::Latest(None),
             AccumulationPolicy::List    => ValueAccumulator::List(Vec::new()),
+            AccumulationPolicy::Set     => ValueAccumulator::Set(BTreeMap::new()),
         }
     }
 }
@@ -70,8 +131,9 @@ impl IntoIterator for ValueAccumulator {
 
     fn into_iter(self) -> Self::IntoIter {
         match self {
-            ValueAccumulator::Latest(value) => Box::new(value.into_iter()),
+            ValueAccumulator::Latest(value) => Box::new(value.into_iter().filter(|value| !is_unset(value))),
             ValueAccumulator::List(values)  => Box::new(values.into_iter()),
+            ValueAccumulator::Set(values)   => Box::new(values.into_values().flatten()),
         }
     }
 }
@@ -83,6 +145,13 @@ impl Default for ValueAccumulator {
 }
 
 
+/// Check whether a value unsets a piece of metadata accumulated as latest value
+///
+fn is_unset(value: &TrailerValue) -> bool {
+    matches!(*value, TrailerValue::String(ref s) if s.trim().is_empty())
+}
+
+
 /// Accumulation trait for trailers
 ///
 pub trait Accumulator {
@@ -128,8 +197,33 @@ impl<M> Accumulator for M
 {
     fn process(&mut self, trailer: Trailer) {
         let (key, value) = trailer.into();
-        self.get_mut(key.as_ref())
-            .map(|ref mut acc| acc.process(value));
+        if let Some(acc) = self.get_mut(key.as_ref()) {
+            acc.process(value);
+            return;
+        }
+
+        if let Some((key, value)) = set_operation(key.as_ref(), &value) {
+            if let Some(acc) = self.get_mut(key).filter(|acc| acc.is_set()) {
+                acc.process(value);
+            }
+        }
+    }
+}
+
+
+/// Translate a trailer adding or removing a value of a set
+///
+/// For keys suffixed with `-add` or `-remove`, this function returns the key
+/// with the suffix stripped and the value prefixed with the corresponding
+/// operation.
+///
+fn set_operation<'k>(key: &'k str, value: &TrailerValue) -> Option<(&'k str, TrailerValue)> {
+    let value = value.to_string();
+    let value = value.trim();
+    if let Some(key) = key.strip_suffix("-add") {
+        Some((key, TrailerValue::from_slice(&format!("+{}", value))))
+    } else {
+        key.strip_suffix("-remove").map(|key| (key, TrailerValue::from_slice(&format!("-{}", value))))
     }
 }
 
@@ -185,6 +279,10 @@ impl Accumulator for SingleAccumulator {
         let (key, value) = trailer.into();
         if *key.as_ref() == self.key {
             self.acc.process(value);
+        } else if self.acc.is_set() {
+            if let Some((_, value)) = set_operation(key.as_ref(), &value).filter(|(key, _)| *key == self.key) {
+                self.acc.process(value);
+            }
         }
     }
 }
@@ -239,6 +337,219 @@ impl IntoIterator for SingleAccumulator {
 }
 
 
+/// Context for the authorization of a message
+///
+/// People are identified as `Name <email>`.
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AuthorizationContext {
+    /// The reporter of the issue, e.g. the author of the initial message
+    pub reporter: Option<String>,
+    /// The assignee of the issue at the time the message was created
+    pub assignee: Option<String>,
+}
+
+
+/// Authorization of messages
+///
+/// Implementations of this trait decide whether the trailers of a message are
+/// honoured during accumulation.
+///
+pub trait Authorization {
+    /// Check whether the trailers of a message are to be honoured
+    ///
+    fn authorizes(&self, message: &Commit, context: &AuthorizationContext) -> Result<bool>;
+
+    /// Combine this authorization with another one
+    ///
+    /// The resulting authorization authorizes a message if either of the
+    /// authorizations does.
+    ///
+    fn or<A>(self, other: A) -> EitherAuthorization<Self, A>
+        where Self: Sized,
+              A: Authorization
+    {
+        EitherAuthorization(self, other)
+    }
+}
+
+impl<A> Authorization for &A
+    where A: Authorization + ?Sized
+{
+    fn authorizes(&self, message: &Commit, context: &AuthorizationContext) -> Result<bool> {
+        (**self).authorizes(message, context)
+    }
+}
+
+
+/// Authorization authorizing messages authorized by either of two
+/// authorizations
+///
+pub struct EitherAuthorization<A, B>(A, B);
+
+impl<A, B> Authorization for EitherAuthorization<A, B>
+    where A: Authorization,
+          B: Authorization
+{
+    fn authorizes(&self, message: &Commit, context: &AuthorizationContext) -> Result<bool> {
+        Ok(self.0.authorizes(message, context)? || self.1.authorizes(message, context)?)
+    }
+}
+
+
+/// Authorization based on the author of a message
+///
+/// This authorization authorizes messages authored by one of a set of
+/// maintainers and, optionally, by the issue's reporter or current assignee.
+/// By default, only the maintainers are authorized.
+///
+/// Since the author of a message may be set to anything, this authorization
+/// should only be used for messages from trusted sources. For identifying
+/// people via the signatures of messages, refer to
+/// `signature::VerifiedPeople`.
+///
+#[derive(Clone, Debug, Default)]
+pub struct AuthorizedPeople {
+    maintainers: Vec<String>,
+    reporter: bool,
+    assignee: bool,
+}
+
+impl AuthorizedPeople {
+    /// Create a new authorization for the maintainers supplied
+    ///
+    /// Maintainers are identified as `Name <email>`.
+    ///
+    pub fn new<I, S>(maintainers: I) -> Self
+        where I: IntoIterator<Item = S>,
+              S: Into<String>
+    {
+        AuthorizedPeople {
+            maintainers: maintainers.into_iter().map(Into::into).collect(),
+            reporter: false,
+            assignee: false,
+        }
+    }
+
+    /// Also authorize the reporter of the issue
+    ///
+    pub fn with_reporter(mut self) -> Self {
+        self.reporter = true;
+        self
+    }
+
+    /// Also authorize the current assignee of the issue
+    ///
+    pub fn with_assignee(mut self) -> Self {
+        self.assignee = true;
+        self
+    }
+
+    /// Check whether a person is authorized in the context supplied
+    ///
+    /// The person is identified as `Name <email>`.
+    ///
+    pub fn includes(&self, person: &str, context: &AuthorizationContext) -> bool {
+        let is_person = |p: &Option<String>| p.as_ref().is_some_and(|p| p == person);
+        self.maintainers.iter().any(|m| m == person) ||
+            (self.reporter && is_person(&context.reporter)) ||
+            (self.assignee && is_person(&context.assignee))
+    }
+}
+
+impl Authorization for AuthorizedPeople {
+    fn authorizes(&self, message: &Commit, context: &AuthorizationContext) -> Result<bool> {
+        Ok(self.includes(&message.author().to_string(), context))
+    }
+}
+
+
+/// Accumulator only honouring trailers of authorized messages
+///
+/// This accumulator wraps another accumulator. Messages have to be supplied
+/// in chronological order, starting with the initial message of an issue. The
+/// author of the first message is considered the reporter. The assignee is
+/// tracked using the assignee trailers of authorized messages. Hence, an
+/// assignee remains authorized until they are replaced by an authorized
+/// message.
+///
+/// Since accumulation policies assume that trailers are processed starting
+/// with the most recent one, trailers are passed to the wrapped accumulator
+/// only when calling `into_inner()`.
+///
+pub struct AuthorizingAccumulator<A, M>
+    where A: Authorization,
+          M: Accumulator
+{
+    authorization: A,
+    assignee_key: String,
+    context: AuthorizationContext,
+    trailers: Vec<Vec<Trailer>>,
+    inner: M,
+}
+
+impl<A, M> AuthorizingAccumulator<A, M>
+    where A: Authorization,
+          M: Accumulator
+{
+    /// Create a new accumulator
+    ///
+    /// The assignee is tracked using trailers with the key supplied.
+    ///
+    pub fn new(inner: M, authorization: A, assignee_key: String) -> Self {
+        AuthorizingAccumulator {
+            authorization,
+            assignee_key,
+            context: Default::default(),
+            trailers: Vec::new(),
+            inner,
+        }
+    }
+
+    /// Process a message and its trailers
+    ///
+    /// The trailers are only retained if the message is authorized.
+    ///
+    pub fn process_message<I>(&mut self, message: &Commit, trailers: I) -> Result<()>
+        where I: IntoIterator<Item = Trailer>
+    {
+        if self.context.reporter.is_none() {
+            self.context.reporter = Some(message.author().to_string());
+        }
+
+        if !self.authorization.authorizes(message, &self.context)? {
+            return Ok(());
+        }
+
+        let trailers: Vec<Trailer> = trailers.into_iter().collect();
+        if let Some(trailer) = trailers.iter().find(|t| *t.key.as_ref() == self.assignee_key) {
+            self.context.assignee = Some(trailer.value.to_string().trim().to_owned())
+                .filter(|assignee| !assignee.is_empty());
+        }
+        self.trailers.push(trailers);
+        Ok(())
+    }
+
+    /// Get the current authorization context
+    ///
+    pub fn context(&self) -> &AuthorizationContext {
+        &self.context
+    }
+
+    /// Retrieve the wrapped accumulator
+    ///
+    /// The wrapped accumulator is fed the trailers of all authorized messages,
+    /// starting with the most recent one.
+    ///
+    pub fn into_inner(mut self) -> M {
+        for trailers in self.trailers.into_iter().rev() {
+            self.inner.process_all(trailers);
+        }
+        self.inner
+    }
+}
+
+
 
 
 #[cfg(test)]
@@ -259,6 +570,21 @@ mod tests {
         assert_eq!(values.next(), None);
     }
 
+    #[test]
+    fn accumulate_latest_unset() {
+        let mut acc = ValueAccumulator::from(AccumulationPolicy::Latest);
+        acc.process(TrailerValue::from_slice(""));
+        acc.process(TrailerValue::from_slice("foo-bar"));
+        assert_eq!(acc.into_iter().next(), None);
+
+        let mut acc = ValueAccumulator::from(AccumulationPolicy::Latest);
+        acc.process(TrailerValue::from_slice("baz"));
+        acc.process(TrailerValue::from_slice(""));
+        acc.process(TrailerValue::from_slice("foo-bar"));
+        let values: Vec<String> = acc.into_iter().map(|value| value.to_string()).collect();
+        assert_eq!(values, vec!["baz"]);
+    }
+
     #[test]
     fn accumulate_list() {
         let mut acc = ValueAccumulator::from(AccumulationPolicy::List);
@@ -271,6 +597,20 @@ mod tests {
         assert_eq!(values.next(), None);
     }
 
+    #[test]
+    fn accumulate_set() {
+        let mut acc = ValueAccumulator::from(AccumulationPolicy::Set);
+        acc.process(TrailerValue::from_slice("+foo"));
+        acc.process(TrailerValue::from_slice("-bar"));
+        acc.process(TrailerValue::from_slice("baz"));
+        acc.process(TrailerValue::from_slice("-foo"));
+        acc.process(TrailerValue::from_slice("+bar"));
+        acc.process(TrailerValue::from_slice("foo"));
+
+        let values: Vec<String> = acc.into_iter().map(|value| value.to_string()).collect();
+        assert_eq!(values, vec!["baz", "foo"]);
+    }
+
     // Accumulator tests
 
     #[test]
@@ -348,5 +688,18 @@ mod tests {
         assert_eq!(vals.next().expect("Could not retrieve value").1.to_string(), "bam");
         assert_eq!(vals.next(), None);
     }
+
+    #[test]
+    fn single_accumulator_set() {
+        let mut acc = SingleAccumulator::new(String::from("Dit-label"), AccumulationPolicy::Set);
+        acc.process(Trailer::new("Dit-label-remove", "bug"));
+        acc.process(Trailer::new("Dit-label", "+feature"));
+        acc.process(Trailer::new("Dit-label-add", "bug"));
+        acc.process(Trailer::new("Dit-label-add", "ui"));
+        acc.process(Trailer::new("Dit-other-add", "foo"));
+
+        let vals: Vec<String> = acc.into_values().map(|value| value.to_string()).collect();
+        assert_eq!(vals, vec!["feature", "ui"]);
+    }
 }
 
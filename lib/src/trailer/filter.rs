@@ -18,7 +18,9 @@
 //! more performant.
 //!
 
+use regex::Regex;
 use std::borrow::Borrow;
+use std::cmp::Ordering;
 
 use trailer::TrailerValue;
 use trailer::accumulation::ValueAccumulator;
@@ -27,10 +29,25 @@ use trailer::spec::TrailerSpec;
 
 /// Type for matching TrailerValues
 ///
+/// Comparisons, e.g. `Less` or `Greater`, only match values of the same kind
+/// as the value compared to: integers are compared numerically and strings are
+/// compared lexicographically. An integer will never match a comparison with
+/// a string and vice versa. Identities are compared by their email addresses,
+/// ignoring the case, and dates chronologically. The same rules apply to
+/// `Equals`.
+///
+/// For comparing values semantically, both the values and the matcher need to
+/// be converted according to the `ValueType` of the piece of metadata.
+///
 pub enum ValueMatcher {
     Any,
     Equals(TrailerValue),
     Contains(String),
+    Regex(Regex),
+    Less(TrailerValue),
+    LessOrEqual(TrailerValue),
+    Greater(TrailerValue),
+    GreaterOrEqual(TrailerValue),
 }
 
 impl ValueMatcher {
@@ -38,10 +55,15 @@ impl ValueMatcher {
     ///
     pub fn matches(&self, value: &TrailerValue) -> bool
     {
-        match self {
-            &ValueMatcher::Any             => true,
-            &ValueMatcher::Equals(ref v)   => value == v,
-            &ValueMatcher::Contains(ref s) => value.to_string().contains(s),
+        match *self {
+            ValueMatcher::Any                   => true,
+            ValueMatcher::Equals(ref v)         => compare(value, v) == Some(Ordering::Equal),
+            ValueMatcher::Contains(ref s)       => value.to_string().contains(s),
+            ValueMatcher::Regex(ref r)          => r.is_match(&value.to_string()),
+            ValueMatcher::Less(ref v)           => compare(value, v) == Some(Ordering::Less),
+            ValueMatcher::LessOrEqual(ref v)    => matches!(compare(value, v), Some(Ordering::Less) | Some(Ordering::Equal)),
+            ValueMatcher::Greater(ref v)        => compare(value, v) == Some(Ordering::Greater),
+            ValueMatcher::GreaterOrEqual(ref v) => matches!(compare(value, v), Some(Ordering::Greater) | Some(Ordering::Equal)),
         }
     }
 
@@ -56,38 +78,56 @@ impl ValueMatcher {
 }
 
 
+/// Compare two values of the same kind
+///
+/// Returns `None` if the values are not of the same kind.
+///
+fn compare(lhs: &TrailerValue, rhs: &TrailerValue) -> Option<Ordering> {
+    match (lhs, rhs) {
+        (TrailerValue::Int(l), TrailerValue::Int(r))        => Some(l.cmp(r)),
+        (TrailerValue::String(l), TrailerValue::String(r))  => Some(l.cmp(r)),
+        (TrailerValue::Identity { email: l, .. }, TrailerValue::Identity { email: r, .. })
+                                                            => Some(l.to_lowercase().cmp(&r.to_lowercase())),
+        (TrailerValue::Date(l), TrailerValue::Date(r))      => Some(l.cmp(r)),
+        (TrailerValue::Bool(l), TrailerValue::Bool(r))      => Some(l.cmp(r)),
+        _                                                   => None,
+    }
+}
+
+
 /// Trailer based filter
 ///
-pub struct TrailerFilter<'a> {
-    trailer: TrailerSpec<'a>,
+pub struct TrailerFilter {
+    trailer: TrailerSpec,
     matcher: ValueMatcher,
 }
 
-impl<'a> TrailerFilter<'a> {
+impl TrailerFilter {
     /// Create a new trailer filter
     ///
-    pub fn new(trailer: TrailerSpec<'a>, matcher: ValueMatcher) -> Self {
+    pub fn new(trailer: TrailerSpec, matcher: ValueMatcher) -> Self {
         Self { trailer: trailer, matcher: matcher }
     }
 
     /// Check whether an issue matches the filter
     ///
     /// Rather than the issue itself, this method takes a metadata map which
-    /// was previously retrieved via accumulation.
+    /// was previously retrieved via accumulation. Values are converted
+    /// according to the value type of the spec before they are matched.
     /// The function returns true if the issue matches the filter, e.g. it
     /// should be displayed or considered for an operation.
     ///
     pub fn matches<'b>(&self, accumulator: &::std::collections::HashMap<String, ValueAccumulator>) -> bool {
         let values = accumulator
-            .get(self.trailer.key)
+            .get(self.trailer.key.as_ref())
             .cloned()
             .unwrap_or_default();
-        self.matcher.matches_any(values)
+        self.matcher.matches_any(values.into_iter().map(|value| self.trailer.value_type.convert(value)))
     }
 
     /// Retrieve the spec associated with this filter
     ///
-    pub fn spec(&self) -> &TrailerSpec<'a> {
+    pub fn spec(&self) -> &TrailerSpec {
         &self.trailer
     }
 }
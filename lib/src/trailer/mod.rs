@@ -18,6 +18,7 @@ pub mod accumulation;
 pub mod filter;
 pub mod iter;
 pub mod spec;
+pub mod types;
 
 use regex::Regex;
 use std::fmt;
@@ -26,6 +27,7 @@ use std::str::FromStr;
 
 use error::*;
 use error::ErrorKind as EK;
+use utils;
 
 /// The Key of a Trailer:
 ///
@@ -65,12 +67,19 @@ impl fmt::Display for TrailerKey {
 ///                # This is the value
 /// ```
 ///
+/// Values parsed from messages are either integers or plain strings. The other
+/// kinds of values are only produced by parsing a value according to its
+/// declared `ValueType`.
+///
 #[derive(Debug, Hash, Eq, PartialEq, Ord, PartialOrd, Clone)]
 pub enum TrailerValue {
     Int(i64),
     String(String),
-
-    // Maybe something like Name { name: String, email: String } ?
+    /// An identity of the form `Name <email>`
+    Identity { name: String, email: String },
+    /// A date, as the number of seconds since the epoch
+    Date(i64),
+    Bool(bool),
 }
 
 impl TrailerValue {
@@ -93,8 +102,8 @@ impl TrailerValue {
     ///
     pub fn append(&mut self, slice: &str) {
         match self {
-            &mut TrailerValue::Int(i)    => *self = TrailerValue::String(i.to_string() + slice),
             &mut TrailerValue::String(ref mut s) => s.push_str(slice),
+            _ => *self = TrailerValue::String(self.to_string() + slice),
         }
     }
 }
@@ -110,6 +119,9 @@ impl fmt::Display for TrailerValue {
         match *self {
             TrailerValue::Int(i)        => write!(f, "{}", i),
             TrailerValue::String(ref s) => write!(f, "{}", s),
+            TrailerValue::Identity { ref name, ref email } => write!(f, "{} <{}>", name, email),
+            TrailerValue::Date(d)       => write!(f, "{}", utils::format_date(d)),
+            TrailerValue::Bool(b)       => write!(f, "{}", b),
         }
     }
 }
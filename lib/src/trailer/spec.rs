@@ -12,25 +12,90 @@
 //! This module provides a type for convenient metadata specification as well as
 //! well as specifications for some dit metadata tags.
 //!
+//! Additional specifications may be declared in a git configuration. A piece of
+//! metadata is declared in a subsection of `dit.trailer`, named after the
+//! piece of metadata. For example, the following section declares a piece of
+//! metadata named "assignee":
+//!
+//! ```ignore
+//! [dit "trailer.assignee"]
+//!     key = Dit-assignee
+//!     accumulation = latest
+//!     type = identity
+//! ```
+//!
+//! If no key is given, the key is derived from the name, e.g. "Dit-assignee"
+//! for "assignee". The accumulation policy defaults to `latest` and the type
+//! to `any`. For the pieces of metadata specified by default, e.g. "status",
+//! omitted variables default to those of the default specification instead.
+//! See the `types` module for the value types available.
+//!
 
-use std::borrow::Borrow;
+use git2;
+use std::borrow::{Borrow, Cow};
+use std::collections::{HashMap, HashSet};
 use std::iter::FromIterator;
 
+use trailer::TrailerValue;
 use trailer::accumulation::{AccumulationPolicy, SingleAccumulator, ValueAccumulator};
+use trailer::types::ValueType;
+
+use error::*;
 
 
 /// Metadata specification
 ///
-/// Use instances of this type for specifying the names and accumulation rules
-/// of pieces of metadata.
+/// Use instances of this type for specifying the names, accumulation rules and
+/// value types of pieces of metadata.
 ///
-#[derive(Clone)]
-pub struct TrailerSpec<'k> {
-    pub key: &'k str,
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrailerSpec {
+    pub key: Cow<'static, str>,
     pub accumulation: AccumulationPolicy,
+    pub value_type: ValueType,
 }
 
-impl<'k> TrailerSpec<'k> {
+impl TrailerSpec {
+    /// Create a new specification
+    ///
+    /// The specification will accept values of any type.
+    ///
+    pub fn new<K>(key: K, accumulation: AccumulationPolicy) -> Self
+        where K: Into<Cow<'static, str>>
+    {
+        TrailerSpec { key: key.into(), accumulation, value_type: ValueType::Any }
+    }
+
+    /// Set the type of the values
+    ///
+    pub fn with_type(mut self, value_type: ValueType) -> Self {
+        self.value_type = value_type;
+        self
+    }
+
+    /// Parse and validate a value for a trailer
+    ///
+    /// For pieces of metadata accumulated as sets, the value may be prefixed
+    /// with an operation, e.g. `+` or `-`, which is preserved. For pieces of
+    /// metadata accumulated as the latest value, an empty value is accepted
+    /// regardless of the type. It unsets the piece of metadata.
+    ///
+    pub fn parse_value(&self, value: &str) -> Result<TrailerValue> {
+        let value = value.trim();
+        if self.accumulation == AccumulationPolicy::Latest && value.is_empty() {
+            return Ok(TrailerValue::String(String::new()));
+        }
+        if self.accumulation == AccumulationPolicy::Set {
+            for operation in &["+", "-"] {
+                if let Some(value) = value.strip_prefix(operation) {
+                    let value = self.value_type.parse(value)?;
+                    return Ok(TrailerValue::String(format!("{}{}", operation, value)));
+                }
+            }
+        }
+        self.value_type.parse(value)
+    }
+
     /// Create a SingleAccumulator from the specification
     ///
     pub fn single_accumulator(&self) -> SingleAccumulator {
@@ -42,17 +107,171 @@ impl<'k> TrailerSpec<'k> {
 /// Metadata specification for an issue's type
 ///
 pub const ISSUE_TYPE_SPEC: TrailerSpec = TrailerSpec {
-    key: "Dit-type",
+    key: Cow::Borrowed("Dit-type"),
     accumulation: AccumulationPolicy::Latest,
+    value_type: ValueType::Any,
 };
 
 /// Metadata specification for an issue's status
 ///
 pub const ISSUE_STATUS_SPEC: TrailerSpec = TrailerSpec {
-    key: "Dit-status",
+    key: Cow::Borrowed("Dit-status"),
+    accumulation: AccumulationPolicy::Latest,
+    value_type: ValueType::Any,
+};
+
+/// Metadata specification for the labels of an issue
+///
+/// Labels are accumulated as a set. They are added and removed via values
+/// prefixed with `+` and `-` or via `Dit-label-add` and `Dit-label-remove`
+/// trailers.
+///
+pub const ISSUE_LABEL_SPEC: TrailerSpec = TrailerSpec {
+    key: Cow::Borrowed("Dit-label"),
+    accumulation: AccumulationPolicy::Set,
+    value_type: ValueType::Any,
+};
+
+/// Metadata specification for the issues an issue depends on
+///
+/// Each value holds the id of an issue, which may be abbreviated.
+///
+pub const ISSUE_DEPENDS_ON_SPEC: TrailerSpec = TrailerSpec {
+    key: Cow::Borrowed("Dit-depends-on"),
+    accumulation: AccumulationPolicy::List,
+    value_type: ValueType::Any,
+};
+
+/// Metadata specification for the issues blocked by an issue
+///
+/// Each value holds the id of an issue, which may be abbreviated.
+///
+pub const ISSUE_BLOCKS_SPEC: TrailerSpec = TrailerSpec {
+    key: Cow::Borrowed("Dit-blocks"),
+    accumulation: AccumulationPolicy::List,
+    value_type: ValueType::Any,
+};
+
+/// Metadata specification for the message superseded by a message
+///
+/// Unlike other pieces of metadata, this trailer refers to a single message
+/// rather than the issue as a whole.
+///
+pub const MESSAGE_SUPERSEDES_SPEC: TrailerSpec = TrailerSpec {
+    key: Cow::Borrowed("Dit-supersedes"),
+    accumulation: AccumulationPolicy::Latest,
+    value_type: ValueType::Any,
+};
+
+/// Metadata specification for the id of the email a message was imported from
+///
+/// Like `Dit-supersedes`, this trailer refers to a single message rather than
+/// the issue as a whole. The key deliberately matches the email header.
+///
+pub const MESSAGE_ID_SPEC: TrailerSpec = TrailerSpec {
+    key: Cow::Borrowed("Message-Id"),
+    accumulation: AccumulationPolicy::Latest,
+    value_type: ValueType::Any,
+};
+
+/// Metadata specification for the commit carried by a message
+///
+/// Like `Dit-supersedes`, this trailer refers to a single message rather than
+/// the issue as a whole.
+///
+pub const MESSAGE_PATCH_SPEC: TrailerSpec = TrailerSpec {
+    key: Cow::Borrowed("Dit-patch"),
     accumulation: AccumulationPolicy::Latest,
+    value_type: ValueType::Any,
+};
+
+/// Metadata specification for references to other commits
+///
+/// Like `Dit-supersedes`, this trailer refers to a single message rather than
+/// the issue as a whole. A message may contain multiple such trailers, each
+/// holding the id of a commit, e.g. a message of another issue.
+///
+pub const MESSAGE_REF_SPEC: TrailerSpec = TrailerSpec {
+    key: Cow::Borrowed("Dit-ref"),
+    accumulation: AccumulationPolicy::List,
+    value_type: ValueType::Any,
 };
 
+/// Metadata specification for the issues fixed by a commit
+///
+/// Unlike other pieces of metadata, this trailer is found in ordinary commits,
+/// e.g. commits on a project's branches, rather than in messages. A commit may
+/// contain multiple such trailers, each holding the id of an issue.
+///
+pub const COMMIT_FIXES_SPEC: TrailerSpec = TrailerSpec {
+    key: Cow::Borrowed("Dit-fixes"),
+    accumulation: AccumulationPolicy::List,
+    value_type: ValueType::Any,
+};
+
+
+/// Retrieve trailer specifications from a configuration
+///
+/// This function returns a map from names to specifications, containing the
+/// specifications declared in the configuration supplied. The map always
+/// contains the specifications for an issue's "status", "type", "label",
+/// "depends-on" and "blocks", unless they are overridden by the
+/// configuration.
+///
+pub fn specs_from_config(config: &git2::Config) -> Result<HashMap<String, TrailerSpec>> {
+    let mut keys = HashMap::new();
+    let mut policies = HashMap::new();
+    let mut types = HashMap::new();
+
+    for entry in &config.entries(Some(r"^dit\.trailer\..*\.(key|accumulation|type)$"))? {
+        let entry = entry?;
+        let (name, value) = match (entry.name(), entry.value()) {
+            (Some(name), Some(value)) => (name, value),
+            _ => continue,
+        };
+
+        // The name of the entry has the form `dit.trailer.<name>.<variable>`
+        let mut parts = name["dit.trailer.".len()..].rsplitn(2, '.');
+        let (variable, name) = match (parts.next(), parts.next()) {
+            (Some(variable), Some(name)) => (variable, name.to_owned()),
+            _ => continue,
+        };
+        match variable {
+            "key"           => { keys.insert(name, value.to_owned()); },
+            "accumulation"  => { policies.insert(name, value.parse::<AccumulationPolicy>()?); },
+            "type"          => { types.insert(name, value.parse::<ValueType>()?); },
+            _               => {},
+        }
+    }
+
+    let mut retval = HashMap::new();
+    retval.insert("status".to_owned(), ISSUE_STATUS_SPEC);
+    retval.insert("type".to_owned(), ISSUE_TYPE_SPEC);
+    retval.insert("label".to_owned(), ISSUE_LABEL_SPEC);
+    retval.insert("depends-on".to_owned(), ISSUE_DEPENDS_ON_SPEC);
+    retval.insert("blocks".to_owned(), ISSUE_BLOCKS_SPEC);
+
+    let names: HashSet<String> = keys.keys().chain(policies.keys()).chain(types.keys()).cloned().collect();
+    for name in names {
+        let default = retval
+            .remove(&name)
+            .unwrap_or_else(|| TrailerSpec::new(format!("Dit-{}", name), AccumulationPolicy::Latest));
+        let key = keys
+            .remove(&name)
+            .map(Cow::from)
+            .unwrap_or(default.key);
+        let policy = policies
+            .remove(&name)
+            .unwrap_or(default.accumulation);
+        let value_type = types
+            .remove(&name)
+            .unwrap_or(default.value_type);
+        retval.insert(name, TrailerSpec::new(key, policy).with_type(value_type));
+    }
+
+    Ok(retval)
+}
+
 
 /// Construct an accumulation map from a set of MetadataSpecifications
 ///
@@ -69,9 +288,9 @@ pub trait ToMap {
     fn into_map(self) -> Self::Output;
 }
 
-impl<'s, I, J> ToMap for I
+impl<I, J> ToMap for I
     where I: IntoIterator<Item = J>,
-          J: Borrow<TrailerSpec<'s>>
+          J: Borrow<TrailerSpec>
 {
     type Output = ::std::collections::HashMap<String, ValueAccumulator>;
 
@@ -0,0 +1,294 @@
+// git-dit - the distributed issue tracker for git
+// Copyright (C) 2017 Matthias Beyer <mail@beyermatthias.de>
+// Copyright (C) 2017 Julian Ganz <neither@nut.email>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+
+//! Types of trailer values
+//!
+//! By default, trailer values are either integers or plain strings. A piece of
+//! metadata may declare a more specific type for its values, e.g. a date. Such
+//! values are validated and normalized when they are written and converted to
+//! the appropriate kind of `TrailerValue` for comparisons.
+//!
+//! Types are specified as strings:
+//!
+//!  * `any` or `string`: any value, the default.
+//!  * `identity`: an identity of the form `Name <email>`.
+//!  * `date`: a date as described for `utils::parse_date()`, normalized to
+//!    RFC 3339 in UTC.
+//!  * `bool`: one of `true`, `yes`, `on` and `1` or `false`, `no`, `off` and
+//!    `0`, normalized to `true` or `false`.
+//!  * `enum:<value>,<value>...`: one of the values listed, matched ignoring
+//!    the case.
+//!  * `int` or `int:<min>..<max>`: an integer, optionally within the inclusive
+//!    bounds given. Either bound may be omitted.
+//!
+
+use regex::Regex;
+use std::fmt;
+use std::result::Result as RResult;
+use std::str::FromStr;
+
+use trailer::TrailerValue;
+use trailer::filter::ValueMatcher;
+use utils;
+
+use error::*;
+use error::ErrorKind as EK;
+
+
+/// Type of the values of a piece of metadata
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum ValueType {
+    #[default]
+    Any,
+    Identity,
+    Date,
+    Bool,
+    Enum(Vec<String>),
+    Int(Option<i64>, Option<i64>),
+}
+
+impl ValueType {
+    /// Parse and validate a value
+    ///
+    /// The value is returned as the kind of `TrailerValue` appropriate for the
+    /// type. Its string representation is the normalized form of the value.
+    ///
+    pub fn parse(&self, value: &str) -> Result<TrailerValue> {
+        self.parse_value(value.trim(), false)
+            .ok_or_else(|| Error::from_kind(EK::InvalidTrailerValue(value.to_owned(), self.to_string())))
+    }
+
+    /// Convert a value to the kind appropriate for the type
+    ///
+    /// Values which are not valid are returned unaltered. This function is
+    /// intended for values retrieved from messages, which may have been
+    /// written without validation.
+    ///
+    pub fn convert(&self, value: TrailerValue) -> TrailerValue {
+        match *self {
+            ValueType::Any  => value,
+            _               => self.parse_value(value.to_string().trim(), false).unwrap_or(value),
+        }
+    }
+
+    /// Convert the values of a matcher to the kind appropriate for the type
+    ///
+    /// Values in queries are parsed as plain `TrailerValue`s. This function
+    /// converts the values of equality matchers and comparisons, allowing them
+    /// to be compared semantically with converted values. Identities may be
+    /// given as a plain email address.
+    ///
+    pub fn matcher(&self, matcher: ValueMatcher) -> Result<ValueMatcher> {
+        if *self == ValueType::Any {
+            return Ok(matcher);
+        }
+
+        let convert = |value: TrailerValue| {
+            let value = value.to_string();
+            self.parse_value(value.trim(), true)
+                .ok_or_else(|| Error::from_kind(EK::InvalidTrailerValue(value.clone(), self.to_string())))
+        };
+
+        Ok(match matcher {
+            ValueMatcher::Equals(v)         => ValueMatcher::Equals(convert(v)?),
+            ValueMatcher::Less(v)           => ValueMatcher::Less(convert(v)?),
+            ValueMatcher::LessOrEqual(v)    => ValueMatcher::LessOrEqual(convert(v)?),
+            ValueMatcher::Greater(v)        => ValueMatcher::Greater(convert(v)?),
+            ValueMatcher::GreaterOrEqual(v) => ValueMatcher::GreaterOrEqual(convert(v)?),
+            other                           => other,
+        })
+    }
+
+    /// Parse a trimmed value
+    ///
+    /// If `bare_email` is set, identities may consist of only an email
+    /// address.
+    ///
+    fn parse_value(&self, value: &str, bare_email: bool) -> Option<TrailerValue> {
+        lazy_static! {
+            static ref IDENTITY: Regex = Regex::new(r"^(?:([^<>]*[^<>[:space:]])[[:space:]]*)?<([^[:space:]<>@]+@[^[:space:]<>@]+)>$").unwrap();
+            static ref EMAIL: Regex = Regex::new(r"^<?([^[:space:]<>@]+@[^[:space:]<>@]+)>?$").unwrap();
+        }
+
+        match *self {
+            ValueType::Any => Some(TrailerValue::from_slice(value)),
+            ValueType::Identity => match IDENTITY.captures(value) {
+                Some(parts) => match parts.get(1) {
+                    Some(name) => Some(TrailerValue::Identity {
+                        name: name.as_str().to_owned(),
+                        email: parts[2].to_owned(),
+                    }),
+                    None if bare_email => Some(TrailerValue::Identity { name: String::new(), email: parts[2].to_owned() }),
+                    None => None,
+                },
+                None if bare_email => EMAIL
+                    .captures(value)
+                    .map(|parts| TrailerValue::Identity { name: String::new(), email: parts[1].to_owned() }),
+                None => None,
+            },
+            ValueType::Date => utils::parse_date(value).map(TrailerValue::Date),
+            ValueType::Bool => match value.to_lowercase().as_str() {
+                "true" | "yes" | "on" | "1"     => Some(TrailerValue::Bool(true)),
+                "false" | "no" | "off" | "0"    => Some(TrailerValue::Bool(false)),
+                _                               => None,
+            },
+            ValueType::Enum(ref values) => values
+                .iter()
+                .find(|allowed| allowed.eq_ignore_ascii_case(value))
+                .map(|allowed| TrailerValue::String(allowed.clone())),
+            ValueType::Int(min, max) => i64::from_str(value)
+                .ok()
+                .filter(|i| min.is_none_or(|min| *i >= min) && max.is_none_or(|max| *i <= max))
+                .map(TrailerValue::Int),
+        }
+    }
+}
+
+impl FromStr for ValueType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let unknown = || Error::from_kind(EK::UnknownValueType(s.to_owned()));
+
+        let mut parts = s.splitn(2, ':');
+        match (parts.next().unwrap_or_default(), parts.next()) {
+            ("any", None) | ("string", None)    => Ok(ValueType::Any),
+            ("identity", None)                  => Ok(ValueType::Identity),
+            ("date", None)                      => Ok(ValueType::Date),
+            ("bool", None)                      => Ok(ValueType::Bool),
+            ("enum", Some(values)) => {
+                let values: Vec<String> = values
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|value| !value.is_empty())
+                    .map(String::from)
+                    .collect();
+                if values.is_empty() {
+                    return Err(unknown());
+                }
+                Ok(ValueType::Enum(values))
+            },
+            ("int", None) => Ok(ValueType::Int(None, None)),
+            ("int", Some(range)) => {
+                let mut bounds = range.splitn(2, "..");
+                let bound = |bound: Option<&str>| match bound.map(str::trim) {
+                    Some("") | None => Ok(None),
+                    Some(bound)     => i64::from_str(bound).map(Some).map_err(|_| unknown()),
+                };
+                let min = bound(bounds.next())?;
+                match bounds.next() {
+                    Some(max)   => Ok(ValueType::Int(min, bound(Some(max))?)),
+                    None        => Err(unknown()),
+                }
+            },
+            _ => Err(unknown()),
+        }
+    }
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> RResult<(), fmt::Error> {
+        let bound = |bound: Option<i64>| bound.map(|b| b.to_string()).unwrap_or_default();
+        match *self {
+            ValueType::Any                  => write!(f, "any"),
+            ValueType::Identity             => write!(f, "identity"),
+            ValueType::Date                 => write!(f, "date"),
+            ValueType::Bool                 => write!(f, "bool"),
+            ValueType::Enum(ref values)     => write!(f, "enum:{}", values.join(",")),
+            ValueType::Int(None, None)      => write!(f, "int"),
+            ValueType::Int(min, max)        => write!(f, "int:{}..{}", bound(min), bound(max)),
+        }
+    }
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn value_types() {
+        assert_eq!(ValueType::from_str("string").expect("Could not parse type"), ValueType::Any);
+        assert_eq!(
+            ValueType::from_str("enum:low, medium,high").expect("Could not parse type"),
+            ValueType::Enum(vec!["low".to_owned(), "medium".to_owned(), "high".to_owned()])
+        );
+        assert_eq!(ValueType::from_str("int:1..5").expect("Could not parse type"), ValueType::Int(Some(1), Some(5)));
+        assert_eq!(ValueType::from_str("int:..5").expect("Could not parse type"), ValueType::Int(None, Some(5)));
+        assert!(ValueType::from_str("int:5").is_err());
+        assert!(ValueType::from_str("enum:").is_err());
+        assert!(ValueType::from_str("foo").is_err());
+
+        for spec in &["any", "identity", "date", "bool", "enum:a,b", "int", "int:1..", "int:-3..5"] {
+            let value_type = ValueType::from_str(spec).expect("Could not parse type");
+            assert_eq!(value_type.to_string(), *spec);
+        }
+    }
+
+    #[test]
+    fn parse_values() {
+        let identity = ValueType::Identity;
+        assert_eq!(
+            identity.parse(" Foo Bar  <foo.bar@example.com>").expect("Could not parse value"),
+            TrailerValue::Identity { name: "Foo Bar".to_owned(), email: "foo.bar@example.com".to_owned() }
+        );
+        assert!(identity.parse("foo.bar@example.com").is_err());
+        assert!(identity.parse("Foo Bar").is_err());
+
+        let date = ValueType::Date;
+        let value = date.parse("2017-09-01 02:00+02:00").expect("Could not parse value");
+        assert_eq!(value, TrailerValue::Date(1504224000));
+        assert_eq!(value.to_string(), "2017-09-01T00:00:00Z");
+        assert!(date.parse("yesterday").is_err());
+
+        let boolean = ValueType::Bool;
+        assert_eq!(boolean.parse("Yes").expect("Could not parse value"), TrailerValue::Bool(true));
+        assert_eq!(boolean.parse("0").expect("Could not parse value").to_string(), "false");
+        assert!(boolean.parse("maybe").is_err());
+
+        let enumeration = ValueType::Enum(vec!["low".to_owned(), "high".to_owned()]);
+        assert_eq!(enumeration.parse("HIGH").expect("Could not parse value"), TrailerValue::String("high".to_owned()));
+        assert!(enumeration.parse("medium").is_err());
+
+        let int = ValueType::Int(Some(1), Some(5));
+        assert_eq!(int.parse("5").expect("Could not parse value"), TrailerValue::Int(5));
+        assert!(int.parse("6").is_err());
+        assert!(int.parse("three").is_err());
+
+        // invalid values are retained by conversions
+        assert_eq!(int.convert(TrailerValue::Int(6)), TrailerValue::Int(6));
+        assert_eq!(boolean.convert(TrailerValue::Int(1)), TrailerValue::Bool(true));
+    }
+
+    #[test]
+    fn semantic_matching() {
+        let matcher = ValueType::Identity
+            .matcher(ValueMatcher::Equals(TrailerValue::from_slice("Foo.Bar@example.com")))
+            .expect("Could not convert matcher");
+        assert!(matcher.matches(&ValueType::Identity.convert(TrailerValue::from_slice("Foo <foo.bar@example.com>"))));
+        assert!(!matcher.matches(&ValueType::Identity.convert(TrailerValue::from_slice("Foo <foo@example.com>"))));
+
+        let matcher = ValueType::Date
+            .matcher(ValueMatcher::Less(TrailerValue::from_slice("2017-09-01")))
+            .expect("Could not convert matcher");
+        assert!(matcher.matches(&ValueType::Date.convert(TrailerValue::from_slice("2017-09-01T01:00:00+02:00"))));
+        assert!(!matcher.matches(&ValueType::Date.convert(TrailerValue::from_slice("2017-09-01T01:00:00Z"))));
+
+        let matcher = ValueType::Bool
+            .matcher(ValueMatcher::Equals(TrailerValue::from_slice("yes")))
+            .expect("Could not convert matcher");
+        assert!(matcher.matches(&ValueType::Bool.convert(TrailerValue::from_slice("on"))));
+
+        assert!(ValueType::Date.matcher(ValueMatcher::Less(TrailerValue::from_slice("soon"))).is_err());
+    }
+}
+
@@ -12,6 +12,9 @@
 //! This module provides utilities private to this library.
 //!
 
+use git2;
+use regex::Regex;
+use std::path::PathBuf;
 use std::result::Result as RResult;
 
 
@@ -42,3 +45,192 @@ impl<I, E, J> ResultIterExt<I, E> for J
     }
 }
 
+
+
+/// Get the directory holding git-dit specific data of a repository
+///
+/// The directory is located inside the repository's git directory. It is not
+/// guaranteed to exist.
+///
+pub fn dit_dir(repo: &git2::Repository) -> PathBuf {
+    repo.path().join("dit")
+}
+
+
+/// Parse a date
+///
+/// This function parses dates of the form `YYYY-MM-DD`, optionally followed by
+/// a time of the form `HH:MM` or `HH:MM:SS`, separated by a `T` or a single
+/// space. Seconds may have a fractional part, which is discarded. A time may be
+/// followed by a timezone designator, e.g. `Z` or an offset of the form
+/// `+HH:MM` or `-HHMM`. Dates without a timezone designator are considered to
+/// be UTC.
+///
+/// The date is returned as the number of seconds since the unix epoch.
+///
+pub fn parse_date(date: &str) -> Option<i64> {
+    use chrono::{DateTime, NaiveDate, NaiveDateTime};
+
+    if let Ok(date) = NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+        return date.and_hms_opt(0, 0, 0).map(|date| date.and_utc().timestamp());
+    }
+
+    // normalize the separator and the designator for UTC
+    let mut date = date.replacen(' ', "T", 1);
+    if date.ends_with('Z') {
+        date.pop();
+        date.push_str("+00:00");
+    }
+
+    for format in &["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%dT%H:%M"] {
+        if let Ok(date) = DateTime::parse_from_str(&date, &format!("{}%#z", format)) {
+            return Some(date.timestamp());
+        }
+        if let Ok(date) = NaiveDateTime::parse_from_str(&date, format) {
+            return Some(date.and_utc().timestamp());
+        }
+    }
+    None
+}
+
+
+/// Parse a date as found in the `Date` header of an email
+///
+/// This function parses dates as specified in RFC 2822, e.g. dates of the form
+/// `Mon, 2 Jan 2017 15:04:05 +0100`. The day of the week and the seconds are
+/// optional. Obsolete timezone names such as `GMT` or `EST` are accepted, too.
+///
+/// The date is returned as the number of seconds since the unix epoch along
+/// with the timezone's offset in minutes.
+///
+pub fn parse_mail_date(date: &str) -> Option<(i64, i32)> {
+    use chrono::{Month, NaiveDate};
+
+    lazy_static! {
+        static ref RE: Regex = Regex::new(concat!(
+            r"^\s*(?:[[:alpha:]]{3},)?\s*(\d{1,2})\s+([[:alpha:]]{3})\s+(\d{2,4})",
+            r"\s+(\d{1,2}):(\d{2})(?::(\d{2}))?\s*([+-]\d{4}|[[:alpha:]]+)?"
+        )).unwrap();
+    }
+
+    let parts = RE.captures(date)?;
+    let num = |i: usize| parts
+        .get(i)
+        .map_or(Some(0), |m| m.as_str().parse::<u32>().ok());
+
+    let month = parts[2].parse::<Month>().ok()?.number_from_month();
+    let (day, year) = (num(1)?, num(3)? as i32);
+
+    // two-digit years are interpreted as specified in RFC 2822
+    let year = match year {
+        0..=49   => year + 2000,
+        50..=999 => year + 1900,
+        _        => year,
+    };
+
+    let offset = match parts.get(7).map(|m| m.as_str().to_uppercase()) {
+        None => 0,
+        Some(zone) => match zone.as_str() {
+            "UT" | "GMT" | "Z"  => 0,
+            "EDT"               => -4 * 60,
+            "EST" | "CDT"       => -5 * 60,
+            "CST" | "MDT"       => -6 * 60,
+            "MST" | "PDT"       => -7 * 60,
+            "PST"               => -8 * 60,
+            zone if zone.starts_with(['+', '-']) => {
+                let value = zone[1..].parse::<i32>().ok()?;
+                let offset = (value / 100) * 60 + value % 100;
+                if zone.starts_with('-') { -offset } else { offset }
+            },
+            _ => 0,
+        },
+    };
+
+    let local = NaiveDate::from_ymd_opt(year, month, day)?
+        .and_hms_opt(num(4)?, num(5)?, num(6)?)?;
+    Some((local.and_utc().timestamp() - i64::from(offset) * 60, offset))
+}
+
+
+/// Format a date for the `Date` header of an email
+///
+/// The date is given as the number of seconds since the unix epoch along with
+/// the timezone's offset in minutes. It is formatted as specified in RFC 2822,
+/// e.g. `Mon, 2 Jan 2017 15:04:05 +0100`.
+///
+pub fn format_mail_date(seconds: i64, offset: i32) -> String {
+    use chrono::FixedOffset;
+
+    let offset = FixedOffset::east_opt(offset * 60).unwrap_or_else(|| FixedOffset::east_opt(0).unwrap());
+    utc_date(seconds)
+        .with_timezone(&offset)
+        .format("%a, %-d %b %Y %H:%M:%S %z")
+        .to_string()
+}
+
+
+/// Format a date for the separator lines of mailboxes in the mbox format
+///
+/// The date is given as the number of seconds since the unix epoch. It is
+/// formatted in UTC, in the format produced by the C function `asctime()`, e.g.
+/// `Mon Jan  2 14:04:05 2017`.
+///
+pub fn format_mbox_date(seconds: i64) -> String {
+    utc_date(seconds).format("%a %b %e %H:%M:%S %Y").to_string()
+}
+
+
+/// Format a date according to RFC 3339
+///
+/// The date is given as the number of seconds since the unix epoch. It is
+/// formatted in UTC, e.g. `2017-01-02T14:04:05Z`. The result may be parsed via
+/// `parse_date()`.
+///
+pub fn format_date(seconds: i64) -> String {
+    utc_date(seconds).format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+
+/// Get the date in UTC from the number of seconds since the unix epoch
+///
+/// Dates out of the range supported are mapped to the epoch.
+///
+fn utc_date(seconds: i64) -> chrono::DateTime<chrono::Utc> {
+    chrono::DateTime::from_timestamp(seconds, 0).unwrap_or_default()
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates() {
+        assert_eq!(parse_date("2017-09-01"), Some(1504224000));
+        assert_eq!(parse_date("2017-09-01T02:00+02:00"), Some(1504224000));
+        assert_eq!(parse_date("2017-08-31 20:00:00-0400"), Some(1504224000));
+        assert_eq!(parse_date("2017-09-01T00:00:00.250Z"), Some(1504224000));
+        assert_eq!(parse_date("2017-09-01T00:00:01Z"), Some(1504224001));
+        assert_eq!(parse_date("2024-02-29"), Some(1709164800));
+        assert_eq!(parse_date("2024-02-31"), None);
+        assert_eq!(parse_date("2023-02-29T12:00Z"), None);
+        assert_eq!(parse_date("2017-09-01T24:00Z"), None);
+        assert_eq!(parse_date("2017-09-01x"), None);
+
+        assert_eq!(format_date(1504224000), "2017-09-01T00:00:00Z");
+        assert_eq!(parse_date(&format_date(1709164799)), Some(1709164799));
+    }
+
+    #[test]
+    fn mail_dates() {
+        assert_eq!(parse_mail_date("Mon, 2 Jan 2017 15:04:05 +0100"), Some((1483365845, 60)));
+        assert_eq!(parse_mail_date("2 Jan 17 14:04 GMT"), Some((1483365840, 0)));
+        assert_eq!(parse_mail_date("Tue, 31 Feb 2017 15:04:05 +0100"), None);
+
+        assert_eq!(format_mail_date(1483365845, 60), "Mon, 2 Jan 2017 15:04:05 +0100");
+        assert_eq!(format_mail_date(1483365845, -90), "Mon, 2 Jan 2017 12:34:05 -0130");
+        assert_eq!(format_mbox_date(1483365845), "Mon Jan  2 14:04:05 2017");
+    }
+}
@@ -0,0 +1,393 @@
+// git-dit - the distributed issue tracker for git
+// Copyright (C) 2017 Matthias Beyer <mail@beyermatthias.de>
+// Copyright (C) 2017 Julian Ganz <neither@nut.email>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+
+//! Status workflows
+//!
+//! By default, the status of an issue is free text. A project may declare a
+//! workflow restricting the status to a set of states and the changes of the
+//! status to a set of transitions. A workflow is declared in the `dit.workflow`
+//! section of a git configuration. For example:
+//!
+//! ```ignore
+//! [dit "workflow"]
+//!     state = open
+//!     state = in-progress
+//!     state = closed
+//!     initial = open
+//!     terminal = closed
+//!     transition = open -> in-progress
+//!     transition = * -> closed: assignee, moderators
+//!     transition = closed -> open: reporter
+//! ```
+//!
+//! Each `state` variable declares a state. Issues without a status are
+//! considered to be in the `initial` state, which defaults to the first state
+//! declared. Issues in a `terminal` state are considered resolved.
+//!
+//! Each `transition` variable declares a transition from one state to another,
+//! optionally followed by a colon and the subjects allowed to perform it, as
+//! understood by access control policies. Transitions without subjects may be
+//! performed by anyone. A transition from `*` applies to all states which are
+//! not terminal: a terminal state can only be left via transitions declared
+//! explicitly for that state.
+//!
+//! Issues whose status is not one of the states, e.g. because it was set
+//! before the workflow was declared, may change to any state.
+//!
+
+use git2;
+use std::fmt;
+use std::result::Result as RResult;
+use std::str::FromStr;
+
+use policy::Subject;
+
+use error::*;
+use error::ErrorKind as EK;
+
+
+/// Transition between two states
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transition {
+    /// The source state, or `*` for all states which are not terminal
+    pub from: String,
+    /// The target state
+    pub to: String,
+    /// The subjects allowed to perform the transition, empty for anyone
+    pub allowed: Vec<Subject>,
+}
+
+impl Transition {
+    /// Check whether the transition applies to a source state
+    ///
+    fn applies_to(&self, from: &str, workflow: &Workflow) -> bool {
+        self.from == from || (self.from == "*" && !workflow.is_terminal(from))
+    }
+}
+
+impl FromStr for Transition {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let malformed = || Error::from_kind(EK::MalformedWorkflow(s.to_owned()));
+
+        let mut parts = s.splitn(2, ':');
+        let mut states = parts.next().unwrap_or_default().splitn(2, "->").map(str::trim);
+        let (from, to) = match (states.next(), states.next()) {
+            (Some(from), Some(to)) if !from.is_empty() && !to.is_empty() => (from.to_owned(), to.to_owned()),
+            _ => return Err(malformed()),
+        };
+        let allowed = parts
+            .next()
+            .into_iter()
+            .flat_map(|subjects| subjects.split(','))
+            .filter(|subject| !subject.trim().is_empty())
+            .map(|subject| subject.parse().map_err(|_| malformed()))
+            .collect::<Result<Vec<Subject>>>()?;
+
+        Ok(Transition { from, to, allowed })
+    }
+}
+
+impl fmt::Display for Transition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> RResult<(), fmt::Error> {
+        write!(f, "{} -> {}", self.from, self.to)?;
+        for (i, subject) in self.allowed.iter().enumerate() {
+            write!(f, "{} {}", if i == 0 { ":" } else { "," }, subject)?;
+        }
+        Ok(())
+    }
+}
+
+
+/// Status workflow
+///
+/// A workflow without any states is considered disabled. Such a workflow
+/// permits any change of the status.
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Workflow {
+    states: Vec<String>,
+    initial: Option<String>,
+    terminal: Vec<String>,
+    transitions: Vec<Transition>,
+}
+
+impl Workflow {
+    /// Read a workflow from a configuration
+    ///
+    /// Transitions and terminal or initial states referring to states which
+    /// are not declared result in an error.
+    ///
+    pub fn from_config(config: &git2::Config) -> Result<Self> {
+        let mut retval = Self::default();
+
+        for entry in &config.entries(Some(r"^dit\.workflow\."))? {
+            let entry = entry?;
+            let (name, value) = match (entry.name(), entry.value()) {
+                (Some(name), Some(value)) => (name, value.trim()),
+                _ => continue,
+            };
+
+            match name {
+                "dit.workflow.state"        => retval.states.push(value.to_owned()),
+                "dit.workflow.initial"      => retval.initial = Some(value.to_owned()),
+                "dit.workflow.terminal"     => retval.terminal.push(value.to_owned()),
+                "dit.workflow.transition"   => retval.transitions.push(value.parse()?),
+                _ => return Err(Error::from_kind(EK::MalformedWorkflow(name.to_owned()))),
+            }
+        }
+
+        let unknown = retval
+            .initial
+            .iter()
+            .chain(retval.terminal.iter())
+            .chain(retval.transitions.iter().map(|t| &t.to))
+            .chain(retval.transitions.iter().map(|t| &t.from).filter(|from| *from != "*"))
+            .find(|state| !retval.states.contains(state));
+        if let Some(state) = unknown {
+            return Err(Error::from_kind(EK::MalformedWorkflow(format!("unknown state '{}'", state))));
+        }
+
+        Ok(retval)
+    }
+
+    /// Check whether the workflow is enabled, e.g. declares any states
+    ///
+    pub fn is_enabled(&self) -> bool {
+        !self.states.is_empty()
+    }
+
+    /// Get the states, in the order of their declaration
+    ///
+    pub fn states(&self) -> &[String] {
+        &self.states
+    }
+
+    /// Get the state of issues without a status
+    ///
+    pub fn initial(&self) -> Option<&str> {
+        self.initial
+            .as_ref()
+            .or_else(|| self.states.first())
+            .map(String::as_str)
+    }
+
+    /// Get the terminal states
+    ///
+    pub fn terminal(&self) -> &[String] {
+        &self.terminal
+    }
+
+    /// Check whether a state is terminal
+    ///
+    pub fn is_terminal(&self, state: &str) -> bool {
+        self.terminal.iter().any(|terminal| terminal == state)
+    }
+
+    /// Get all transitions
+    ///
+    pub fn transitions(&self) -> &[Transition] {
+        &self.transitions
+    }
+
+    /// Get the transitions applying to a state
+    ///
+    /// If no state is given, the initial state is assumed.
+    ///
+    pub fn legal_transitions(&self, from: Option<&str>) -> Vec<&Transition> {
+        let from = match from.or_else(|| self.initial()) {
+            Some(from) => from,
+            None => return Vec::new(),
+        };
+        self.transitions
+            .iter()
+            .filter(|transition| transition.applies_to(from, self))
+            .collect()
+    }
+
+    /// Check whether a change of the status is legal and permitted
+    ///
+    /// The status changes from the state `from`, or the initial state if none
+    /// is given, to the state `to`. If the change is legal, the function
+    /// supplied is used for determining whether the person performing the
+    /// change is one of the subjects allowed to perform it. It is not invoked
+    /// for transitions which may be performed by anyone.
+    ///
+    /// An error of the kind `IllegalStatusTransition`, listing all legal
+    /// transitions, is returned if the change is not legal. An error of the
+    /// kind `PolicyViolation` is returned if it is not permitted.
+    ///
+    pub fn check<F>(&self, from: Option<&str>, to: &str, permits: F) -> Result<()>
+        where F: FnOnce(&[Subject]) -> Result<bool>
+    {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        let from = match from.or_else(|| self.initial()) {
+            Some(from) => from,
+            None => return Ok(()),
+        };
+        if from == to {
+            return Ok(());
+        }
+
+        let illegal = |legal: Vec<String>| Error::from_kind(EK::IllegalStatusTransition(from.to_owned(), to.to_owned(), legal));
+
+        // issues in an unknown state may change to any state
+        if !self.states.iter().any(|state| state == from) {
+            return if self.states.iter().any(|state| state == to) {
+                Ok(())
+            } else {
+                Err(illegal(self.states.iter().map(|state| format!("{} -> {}", from, state)).collect()))
+            };
+        }
+
+        let legal = self.legal_transitions(Some(from));
+        let transition = match legal.iter().find(|transition| transition.to == to) {
+            Some(transition) => transition,
+            None => return Err(illegal(legal.iter().map(|t| format!("{} -> {}", from, t.to)).collect())),
+        };
+
+        if transition.allowed.is_empty() || transition.allowed.contains(&Subject::Anyone) || permits(&transition.allowed)? {
+            Ok(())
+        } else {
+            let allowed: Vec<String> = transition.allowed.iter().map(ToString::to_string).collect();
+            Err(Error::from_kind(EK::PolicyViolation(format!(
+                "Only {} may change the status from '{}' to '{}'",
+                allowed.join(", "),
+                from,
+                to
+            ))))
+        }
+    }
+
+    /// Render the workflow in the Graphviz dot language
+    ///
+    /// Terminal states are drawn with a double border. Transitions from `*`
+    /// are rendered as edges from each state they apply to.
+    ///
+    pub fn to_dot(&self) -> String {
+        let mut retval = String::from("digraph workflow {\n");
+        for state in self.states.iter() {
+            let shape = if self.is_terminal(state) { "doublecircle" } else { "circle" };
+            retval.push_str(&format!("    \"{}\" [shape={}];\n", escape(state), shape));
+        }
+        if let Some(initial) = self.initial() {
+            retval.push_str("    \"\" [shape=point];\n");
+            retval.push_str(&format!("    \"\" -> \"{}\";\n", escape(initial)));
+        }
+        for state in self.states.iter() {
+            for transition in self.legal_transitions(Some(state)) {
+                let label: Vec<String> = transition.allowed.iter().map(ToString::to_string).collect();
+                retval.push_str(&format!(
+                    "    \"{}\" -> \"{}\" [label=\"{}\"];\n",
+                    escape(state),
+                    escape(&transition.to),
+                    escape(&label.join(", "))
+                ));
+            }
+        }
+        retval.push_str("}\n");
+        retval
+    }
+}
+
+
+/// Escape a string for use in a quoted dot identifier
+///
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::TestingRepo;
+
+    fn workflow(testing_repo: &mut TestingRepo) -> Result<Workflow> {
+        let repo = testing_repo.repo();
+        let mut config = repo.config().expect("Could not get config");
+        for state in &["open", "in-progress", "closed"] {
+            config.set_multivar("dit.workflow.state", "^$", state).unwrap();
+        }
+        config.set_str("dit.workflow.terminal", "closed").unwrap();
+        config.set_multivar("dit.workflow.transition", "^$", "open -> in-progress").unwrap();
+        config.set_multivar("dit.workflow.transition", "^$", "* -> closed: assignee, moderators").unwrap();
+        config.set_multivar("dit.workflow.transition", "^$", "closed -> open: reporter").unwrap();
+        Workflow::from_config(&config)
+    }
+
+    #[test]
+    fn parse_workflow() {
+        let mut testing_repo = TestingRepo::new("parse_workflow");
+        let workflow = workflow(&mut testing_repo).expect("Could not read workflow");
+
+        assert!(workflow.is_enabled());
+        assert_eq!(workflow.initial(), Some("open"));
+        assert!(workflow.is_terminal("closed"));
+        assert_eq!(
+            workflow.transitions()[1],
+            Transition {
+                from: "*".to_owned(),
+                to: "closed".to_owned(),
+                allowed: vec![Subject::Assignee, Subject::Moderators],
+            }
+        );
+        assert_eq!(workflow.transitions()[1].to_string(), "* -> closed: assignee, moderators");
+
+        let legal: Vec<String> = workflow
+            .legal_transitions(Some("closed"))
+            .into_iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(legal, vec!["closed -> open: reporter"]);
+
+        let mut config = testing_repo.repo().config().expect("Could not get config");
+        config.set_multivar("dit.workflow.transition", "^$", "open -> done").unwrap();
+        assert!(Workflow::from_config(&config).is_err());
+        config.set_multivar("dit.workflow.transition", "^open -> done$", "open - done").unwrap();
+        assert!(Workflow::from_config(&config).is_err());
+    }
+
+    #[test]
+    fn check_transitions() {
+        let mut testing_repo = TestingRepo::new("check_transitions");
+        let workflow = workflow(&mut testing_repo).expect("Could not read workflow");
+
+        let anyone = |_: &[Subject]| Ok(false);
+        let assignee = |subjects: &[Subject]| Ok(subjects.contains(&Subject::Assignee));
+
+        assert!(workflow.check(None, "in-progress", anyone).is_ok());
+        assert!(workflow.check(Some("open"), "open", anyone).is_ok());
+        assert!(workflow.check(Some("in-progress"), "closed", assignee).is_ok());
+        assert!(workflow.check(Some("legacy"), "closed", anyone).is_ok());
+
+        match workflow.check(Some("in-progress"), "closed", anyone).map_err(|err| err.0) {
+            Err(EK::PolicyViolation(_)) => {},
+            other => panic!("Unexpected result: {:?}", other),
+        }
+        match workflow.check(Some("in-progress"), "open", assignee).map_err(|err| err.0) {
+            Err(EK::IllegalStatusTransition(from, to, legal)) => {
+                assert_eq!((from.as_str(), to.as_str()), ("in-progress", "open"));
+                assert_eq!(legal, vec!["in-progress -> closed"]);
+            },
+            other => panic!("Unexpected result: {:?}", other),
+        }
+
+        // the wildcard does not apply to terminal states
+        assert!(workflow.check(Some("closed"), "in-progress", assignee).is_err());
+        assert!(Workflow::default().check(Some("foo"), "bar", anyone).is_ok());
+    }
+}
+
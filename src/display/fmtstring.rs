@@ -0,0 +1,228 @@
+//   git-dit - the distributed issue tracker for git
+//   Copyright (C) 2017 Matthias Beyer <mail@beyermatthias.de>
+//   Copyright (C) 2017 Julian Ganz <neither@nut.email>
+//
+//   This program is free software; you can redistribute it and/or modify
+//   it under the terms of the GNU General Public License version 2 as
+//   published by the Free Software Foundation.
+//
+
+//! Format strings for messages
+//!
+//! This module provides a parser for user-supplied format strings, which are
+//! translated to sequences of formatting tokens. A format string consists of
+//! literal text and placeholders introduced by a `%`:
+//!
+//!  * `%id`: the full id of the message
+//!  * `%h`: the id of the message, abbreviated as requested via `--abbrev`
+//!  * `%s`: the subject
+//!  * `%an` and `%ae`: the author's name and email address
+//!  * `%ad`: the date, in the locale's format
+//!  * `%b`: the body
+//!  * `%n`: a line break
+//!  * `%%`: a literal `%`
+//!  * `%(author)`: the author's name and email address
+//!  * `%(date:<format>)`: the date, formatted using a strftime format
+//!  * `%(text)`: the body, without trailers and quotes
+//!  * `%(trailers)`: all trailers, one per line
+//!  * `%(trailer:<key>)`: the trailers with the given key, one per line
+//!
+//! The key of a trailer may also be the name of a piece of metadata declared
+//! in the configuration.
+//!
+//! Conditional output is expressed by `%(if)<cond>%(then)<a>%(else)<b>%(end)`,
+//! where the `%(else)` part is optional. If `<cond>` expands to any text other
+//! than whitespace, `<a>` is printed. Otherwise, `<b>` is printed.
+//!
+
+use chrono::format::Item;
+use chrono::format::strftime::StrftimeItems;
+use git2::Commit;
+use libgitdit::trailer::accumulation::AccumulationPolicy;
+use libgitdit::trailer::spec::TrailerSpec;
+use std::collections::HashMap;
+
+use error::*;
+use error::ErrorKind as EK;
+use super::{FormattingToken, JsonFormat, MessageFmtToken};
+
+
+/// Formatting tokens for messages
+///
+pub type MessageTokens<'a> = Vec<FormattingToken<MessageFmtToken<'a>, Commit<'a>>>;
+
+
+/// Output format specified by the user
+///
+pub enum OutputFormat {
+    /// One of the JSON formats
+    Json(JsonFormat),
+    /// A format string
+    Custom(String),
+}
+
+
+/// Parse a format string
+///
+/// The abbreviated id is formatted using the length supplied. Trailer keys are
+/// looked up in the trailer specifications supplied.
+///
+pub fn parse_format<'a>(format: &'a str, id_len: usize, trailers: &HashMap<String, TrailerSpec>) -> Result<MessageTokens<'a>> {
+    let mut parser = FormatParser { format, rest: format, id_len, trailers };
+    match parser.sequence()? {
+        (tokens, None) => Ok(tokens),
+        (_, Some(_)) => Err(parser.unbalanced()),
+    }
+}
+
+
+/// Recursive descent parser for format strings
+///
+struct FormatParser<'a, 't> {
+    format: &'a str,
+    rest: &'a str,
+    id_len: usize,
+    trailers: &'t HashMap<String, TrailerSpec>,
+}
+
+impl<'a, 't> FormatParser<'a, 't> {
+    /// Parse a sequence of text and placeholders
+    ///
+    /// The sequence ends either at the end of the format string or at one of
+    /// the directives `then`, `else` or `end`, which is returned along with the
+    /// tokens.
+    ///
+    fn sequence(&mut self) -> Result<(MessageTokens<'a>, Option<&'a str>)> {
+        let mut tokens = Vec::new();
+        loop {
+            // literal text
+            let text_len = self.rest.find(['%', '\n']).unwrap_or(self.rest.len());
+            if text_len > 0 {
+                tokens.push(self.rest[..text_len].into());
+                self.rest = &self.rest[text_len..];
+            }
+
+            let mut chars = self.rest.chars();
+            match chars.next() {
+                None => return Ok((tokens, None)),
+                Some('\n') => {
+                    tokens.push(FormattingToken::LineEnd);
+                    self.rest = chars.as_str();
+                    continue;
+                },
+                Some(_) => self.rest = chars.as_str(),
+            }
+
+            // placeholder
+            if let Some(name) = self.take_prefix(&["%", "n", "id", "h", "s", "an", "ae", "ad", "b"]) {
+                tokens.push(match name {
+                    "%"  => "%".into(),
+                    "n"  => FormattingToken::LineEnd,
+                    "id" => MessageFmtToken::Id(40).into(),
+                    "h"  => MessageFmtToken::Id(self.id_len).into(),
+                    "s"  => MessageFmtToken::Subject.into(),
+                    "an" => MessageFmtToken::AuthorName.into(),
+                    "ae" => MessageFmtToken::AuthorEMail.into(),
+                    "ad" => MessageFmtToken::Date(StrftimeItems::new("%c")).into(),
+                    _    => MessageFmtToken::Body.into(),
+                });
+                continue;
+            }
+
+            let directive = self.directive()?;
+            let (name, arg) = match directive.find(':') {
+                Some(pos) => (&directive[..pos], Some(&directive[pos + 1..])),
+                None      => (directive, None),
+            };
+            tokens.push(match (name, arg) {
+                ("author", None)        => MessageFmtToken::Author.into(),
+                ("date", Some(format))  => MessageFmtToken::Date(self.date_format(format)?).into(),
+                ("text", None)          => MessageFmtToken::BodyText.into(),
+                ("trailers", None)      => MessageFmtToken::Trailers.into(),
+                ("trailer", Some(key))  => MessageFmtToken::Trailer(self.trailer_spec(key)).into(),
+                ("if", None)            => self.conditional()?.into(),
+                ("then", None) | ("else", None) | ("end", None) => return Ok((tokens, Some(name))),
+                _ => return Err(Error::from_kind(EK::UnknownPlaceholder(directive.to_owned()))),
+            });
+        }
+    }
+
+    /// Parse the remainder of a conditional
+    ///
+    /// This function is called after the `if` directive was consumed.
+    ///
+    fn conditional(&mut self) -> Result<MessageFmtToken<'a>> {
+        let condition = match self.sequence()? {
+            (tokens, Some("then")) => tokens,
+            _ => return Err(self.unbalanced()),
+        };
+        let (then, otherwise) = match self.sequence()? {
+            (tokens, Some("end")) => (tokens, Vec::new()),
+            (tokens, Some("else")) => match self.sequence()? {
+                (otherwise, Some("end")) => (tokens, otherwise),
+                _ => return Err(self.unbalanced()),
+            },
+            _ => return Err(self.unbalanced()),
+        };
+        Ok(MessageFmtToken::If(condition, then, otherwise))
+    }
+
+    /// Consume one of a list of alternatives from the remaining input
+    ///
+    /// The longest alternative matching the remaining input is consumed and
+    /// returned.
+    ///
+    fn take_prefix(&mut self, alternatives: &[&'static str]) -> Option<&'static str> {
+        let retval = alternatives
+            .iter()
+            .filter(|alternative| self.rest.starts_with(*alternative))
+            .max_by_key(|alternative| alternative.len())
+            .cloned();
+        if let Some(prefix) = retval {
+            self.rest = &self.rest[prefix.len()..];
+        }
+        retval
+    }
+
+    /// Consume a directive of the form `(<name>[:<argument>])`
+    ///
+    fn directive(&mut self) -> Result<&'a str> {
+        let rest = self.rest;
+        if !rest.starts_with('(') {
+            let placeholder = rest.chars().next().map(String::from).unwrap_or_default();
+            return Err(Error::from_kind(EK::UnknownPlaceholder(placeholder)));
+        }
+        let end = rest
+            .find(')')
+            .ok_or_else(|| Error::from_kind(EK::MalformedFormat(self.format.to_owned())))?;
+        self.rest = &rest[end + 1..];
+        Ok(&rest[1..end])
+    }
+
+    /// Check a strftime format
+    ///
+    fn date_format(&self, format: &'a str) -> Result<StrftimeItems<'a>> {
+        let items = StrftimeItems::new(format);
+        if items.clone().any(|item| item == Item::Error) {
+            Err(Error::from_kind(EK::MalformedFormat(self.format.to_owned())))
+        } else {
+            Ok(items)
+        }
+    }
+
+    /// Get the trailer specification for a key or name of a piece of metadata
+    ///
+    fn trailer_spec(&self, key: &str) -> TrailerSpec {
+        self.trailers
+            .get(key)
+            .cloned()
+            .unwrap_or_else(|| TrailerSpec::new(key.to_owned(), AccumulationPolicy::List))
+    }
+
+    /// Create an error for a misplaced or missing directive
+    ///
+    fn unbalanced(&self) -> Error {
+        Error::from_kind(EK::UnbalancedConditional(self.format.to_owned()))
+    }
+}
+
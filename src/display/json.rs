@@ -0,0 +1,286 @@
+//   git-dit - the distributed issue tracker for git
+//   Copyright (C) 2017 Matthias Beyer <mail@beyermatthias.de>
+//   Copyright (C) 2017 Julian Ganz <neither@nut.email>
+//
+//   This program is free software; you can redistribute it and/or modify
+//   it under the terms of the GNU General Public License version 2 as
+//   published by the Free Software Foundation.
+//
+
+//! JSON output facility
+//!
+//! This module provides a minimal JSON representation as well as conversions
+//! for issues, messages and trailers. The schema produced is documented in
+//! `doc/json.md`.
+//!
+
+use git2::{Commit, ObjectType, Oid, Signature, Time};
+use libgitdit::{Issue, Message};
+use libgitdit::issue::IssueRefType;
+use libgitdit::trailer::{Trailer, TrailerValue};
+use libgitdit::trailer::accumulation::ValueAccumulator;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::result::Result as RResult;
+
+use error::*;
+
+
+/// JSON value
+///
+/// Objects are represented as ordered maps, resulting in a deterministic
+/// output.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(i64),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(BTreeMap<String, JsonValue>),
+}
+
+impl JsonValue {
+    /// Create an object from a list of key-value pairs
+    ///
+    pub fn object<I, K>(members: I) -> Self
+        where I: IntoIterator<Item = (K, JsonValue)>,
+              K: Into<String>
+    {
+        JsonValue::Object(members.into_iter().map(|(k, v)| (k.into(), v)).collect())
+    }
+
+    /// Add a member to an object
+    ///
+    /// If the value is not an object, it is returned unaltered.
+    ///
+    pub fn with_member<K>(mut self, key: K, value: JsonValue) -> Self
+        where K: Into<String>
+    {
+        if let JsonValue::Object(ref mut members) = self {
+            members.insert(key.into(), value);
+        }
+        self
+    }
+}
+
+impl fmt::Display for JsonValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> RResult<(), fmt::Error> {
+        match *self {
+            JsonValue::Null             => write!(f, "null"),
+            JsonValue::Bool(b)          => write!(f, "{}", b),
+            JsonValue::Number(n)        => write!(f, "{}", n),
+            JsonValue::String(ref s)    => write_string(f, s),
+            JsonValue::Array(ref items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            },
+            JsonValue::Object(ref members) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            },
+        }
+    }
+}
+
+impl<'a> From<&'a str> for JsonValue {
+    fn from(s: &'a str) -> Self {
+        JsonValue::String(s.to_owned())
+    }
+}
+
+impl From<String> for JsonValue {
+    fn from(s: String) -> Self {
+        JsonValue::String(s)
+    }
+}
+
+impl From<Oid> for JsonValue {
+    fn from(id: Oid) -> Self {
+        JsonValue::String(id.to_string())
+    }
+}
+
+impl<T> From<Option<T>> for JsonValue
+    where T: Into<JsonValue>
+{
+    fn from(value: Option<T>) -> Self {
+        value.map(Into::into).unwrap_or(JsonValue::Null)
+    }
+}
+
+impl<T> From<Vec<T>> for JsonValue
+    where T: Into<JsonValue>
+{
+    fn from(values: Vec<T>) -> Self {
+        JsonValue::Array(values.into_iter().map(Into::into).collect())
+    }
+}
+
+impl From<TrailerValue> for JsonValue {
+    fn from(value: TrailerValue) -> Self {
+        match value {
+            TrailerValue::Int(i)    => JsonValue::Number(i),
+            TrailerValue::String(s) => JsonValue::String(s),
+            TrailerValue::Bool(b)   => JsonValue::Bool(b),
+            other                   => JsonValue::String(other.to_string()),
+        }
+    }
+}
+
+impl From<Trailer> for JsonValue {
+    fn from(trailer: Trailer) -> Self {
+        JsonValue::object(vec![
+            ("key", trailer.key.to_string().into()),
+            ("value", trailer.value.into()),
+        ])
+    }
+}
+
+impl From<ValueAccumulator> for JsonValue {
+    fn from(acc: ValueAccumulator) -> Self {
+        match acc {
+            acc @ ValueAccumulator::Latest(_) => acc.into_iter().next().into(),
+            ValueAccumulator::List(values)  => values.into(),
+            acc @ ValueAccumulator::Set(_)  => acc.into_iter().collect::<Vec<_>>().into(),
+        }
+    }
+}
+
+
+/// Write a string as a JSON string literal
+///
+fn write_string(f: &mut fmt::Formatter, s: &str) -> RResult<(), fmt::Error> {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"'             => write!(f, "\\\"")?,
+            '\\'            => write!(f, "\\\\")?,
+            '\n'            => write!(f, "\\n")?,
+            '\r'            => write!(f, "\\r")?,
+            '\t'            => write!(f, "\\t")?,
+            c if c < ' '    => write!(f, "\\u{:04x}", c as u32)?,
+            c               => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+
+/// JSON output variants
+///
+pub enum JsonFormat {
+    /// A single JSON document
+    Json,
+    /// Newline-delimited JSON, e.g. one JSON value per line
+    NdJson,
+}
+
+impl JsonFormat {
+    /// Get the JSON format for a format name
+    ///
+    /// Returns `None` if the name does not denote a JSON format.
+    ///
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "json"      => Some(JsonFormat::Json),
+            "ndjson"    => Some(JsonFormat::NdJson),
+            _           => None,
+        }
+    }
+
+    /// Transform a sequence of items into the values to print, one per line
+    ///
+    /// For `Json`, the items are combined to an array. For `NdJson`, each item
+    /// is printed on its own line.
+    ///
+    pub fn lines(&self, items: Vec<JsonValue>) -> Vec<JsonValue> {
+        match *self {
+            JsonFormat::Json    => vec![JsonValue::Array(items)],
+            JsonFormat::NdJson  => items,
+        }
+    }
+}
+
+
+/// Get the JSON representation of a point in time
+///
+/// The time is represented as an RFC 3339 formatted string. If the time is
+/// not representable, `null` is returned.
+///
+pub fn time_json(time: Time) -> JsonValue {
+    use chrono::{FixedOffset, TimeZone};
+
+    FixedOffset::east_opt(time.offset_minutes() * 60)
+        .and_then(|offset| offset.timestamp_opt(time.seconds(), 0).single())
+        .map(|date| date.to_rfc3339())
+        .into()
+}
+
+
+/// Get the JSON representation of a signature
+///
+pub fn signature_json(signature: &Signature) -> JsonValue {
+    JsonValue::object(vec![
+        ("name", signature.name().into()),
+        ("email", signature.email().into()),
+        ("date", time_json(signature.when())),
+    ])
+}
+
+
+/// Get the JSON representation of a message
+///
+pub fn message_json(message: &Commit) -> JsonValue {
+    let body: Vec<String> = message.body_lines().collect();
+    JsonValue::object(vec![
+        ("id", message.id().into()),
+        ("parents", message.parent_ids().collect::<Vec<_>>().into()),
+        ("author", signature_json(&message.author())),
+        ("committer", signature_json(&message.committer())),
+        ("subject", message.summary().into()),
+        ("body", body.join("\n").into()),
+        ("trailers", message.trailers().collect::<Vec<_>>().into()),
+    ])
+}
+
+
+/// Get the JSON representation of an issue
+///
+/// The representation includes the issue's id, the selected head, all leaves
+/// and the metadata accumulated from the head. The initial message is
+/// included as well.
+///
+pub fn issue_json(issue: &Issue, head: Option<Oid>, metadata: HashMap<String, ValueAccumulator>) -> Result<JsonValue> {
+    let mut leaves = Vec::new();
+    for leaf in issue.all_refs(IssueRefType::Leaf)? {
+        let id = leaf?.peel(ObjectType::Commit)?.id();
+        if !leaves.contains(&id) {
+            leaves.push(id);
+        }
+    }
+
+    let metadata = metadata.into_iter().map(|(key, value)| (key, value.into()));
+
+    Ok(JsonValue::object(vec![
+        ("id", issue.id().into()),
+        ("head", head.into()),
+        ("leaves", leaves.into()),
+        ("metadata", JsonValue::object(metadata)),
+        ("initial", message_json(&issue.initial_message()?)),
+    ]))
+}
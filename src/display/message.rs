@@ -11,13 +11,15 @@
 //!
 
 use chrono::format::strftime::StrftimeItems;
-use git2::{Commit, Oid};
+use git2::{Commit, DiffStatsFormat, Oid, Repository};
 use libgitdit::Message;
 use libgitdit::message::block::Block;
+use libgitdit::patch::patch;
+use libgitdit::signature::{Program, verify};
 use libgitdit::trailer::spec::TrailerSpec;
 
 use error::*;
-use super::formatter::{TokenExpander, FormattingToken, LineTokens};
+use super::formatter::{TokenExpander, FormattingToken, LineFormatter, LineTokens};
 
 /// Tokens for formatting messages
 ///
@@ -33,8 +35,15 @@ pub enum MessageFmtToken<'a> {
     Body,
     BodyText,
     Trailers,
-    Trailer(TrailerSpec<'a>),
+    Trailer(TrailerSpec),
+    DiffStat(&'a Repository),
+    Signature(&'a Repository, &'a Program),
     IfId(Oid, Vec<FormattingToken<MessageFmtToken<'a>, Commit<'a>>>),
+    If(
+        Vec<FormattingToken<MessageFmtToken<'a>, Commit<'a>>>,
+        Vec<FormattingToken<MessageFmtToken<'a>, Commit<'a>>>,
+        Vec<FormattingToken<MessageFmtToken<'a>, Commit<'a>>>,
+    ),
 }
 
 impl<'a,> TokenExpander for MessageFmtToken<'a> {
@@ -93,14 +102,46 @@ impl<'a,> TokenExpander for MessageFmtToken<'a> {
                 .collect(),
             &MessageFmtToken::Trailer(ref spec) => message
                 .trailers()
-                .filter(|trailer| trailer.key.as_ref() == spec.key)
+                .filter(|trailer| trailer.key.as_ref() == spec.key.as_ref())
                 .line_tokens()
                 .collect(),
+            &MessageFmtToken::DiffStat(repo) => match patch(message) {
+                Some(commit) => {
+                    // the changes introduced by the commit carried, preceded
+                    // by a blank line
+                    let old = match commit.parents().next() {
+                        Some(parent) => Some(parent.tree()?),
+                        None => None,
+                    };
+                    let stats = repo
+                        .diff_tree_to_tree(old.as_ref(), Some(&commit.tree()?), None)?
+                        .stats()?
+                        .to_buf(DiffStatsFormat::FULL, 72)?;
+                    Some(String::new())
+                        .into_iter()
+                        .chain(String::from_utf8_lossy(&stats).lines().map(String::from))
+                        .line_tokens()
+                        .collect()
+                },
+                None => Vec::new(),
+            },
+            &MessageFmtToken::Signature(repo, verifier) => tokenvec![verify(repo, message, verifier)?.to_string()],
             &MessageFmtToken::IfId(ref id, ref tokens) => if *id == message.id() {
                 tokens.clone()
             } else {
                 Vec::new()
             },
+            &MessageFmtToken::If(ref condition, ref then, ref otherwise) => {
+                // The condition holds if it expands to anything but whitespace
+                let mut holds = false;
+                for line in condition.iter().formatted_lines(message) {
+                    if !line?.trim().is_empty() {
+                        holds = true;
+                        break;
+                    }
+                }
+                if holds { then.clone() } else { otherwise.clone() }
+            },
         })
     }
 }
@@ -9,10 +9,14 @@
 
 #[macro_use] mod formatter;
 
+mod fmtstring;
+mod json;
 mod message;
 mod msgtree;
 
+pub use self::fmtstring::*;
 pub use self::formatter::*;
+pub use self::json::*;
 pub use self::message::*;
 pub use self::msgtree::*;
 
@@ -26,7 +26,8 @@
 //!
 
 
-use git2::{self, Commit};
+use git2::{self, Commit, Oid};
+use std::collections::HashMap;
 use std::fmt::{self, Write};
 use std::iter::FromIterator;
 
@@ -173,6 +174,29 @@ pub struct TreeGraphElemLineIterator<'r, I>
 {
     inner: I, // inner iterator over commits for which to display the graph
     parents: Vec<Option<git2::Oid>>, // currently tracked parents
+    aliases: HashMap<Oid, Oid>, // ids under which commits are tracked
+}
+
+impl<'r, I> TreeGraphElemLineIterator<'r, I>
+    where I: Iterator<Item = Commit<'r>>
+{
+    /// Track commits under different ids
+    ///
+    /// The ids of both the commits and their parents are replaced by the
+    /// aliases supplied, if present. This way, multiple commits may be
+    /// represented by a single one, e.g. the revisions of an amended message
+    /// by the latest revision.
+    ///
+    pub fn with_aliases(mut self, aliases: HashMap<Oid, Oid>) -> Self {
+        self.aliases = aliases;
+        self
+    }
+
+    /// Get the id under which a commit is tracked
+    ///
+    fn alias(&self, id: Oid) -> Oid {
+        self.aliases.get(&id).cloned().unwrap_or(id)
+    }
 }
 
 impl<'r, I> Iterator for TreeGraphElemLineIterator<'r, I>
@@ -185,12 +209,13 @@ impl<'r, I> Iterator for TreeGraphElemLineIterator<'r, I>
             // We will definitely require the parent id in order to draw a graph.
             // However, we only want to track the parent once, so we end up with
             // nice horizontal merges in our graph.
-            let mut parent_update = commit.parent(0).as_ref().map(Commit::id).ok();
+            let mut parent_update = commit.parent_id(0).ok().map(|id| self.alias(id));
+            let commit_id = self.alias(commit.id());
 
             // generate graph elements for the parents currently tracked
             let mut elems : TreeGraphElemLine = self.parents.iter_mut().map(|parent| {
                 match *parent {
-                    Some(id) => if commit.id() == id {
+                    Some(id) => if commit_id == id {
                             // the current commit is a parent we were awaiting
                             *parent = parent_update.take();
                             let mark_type = if parent.is_some() { MarkType::Mid } else { MarkType::End };
@@ -236,7 +261,7 @@ impl<'r, I> IntoTreeGraph<'r, I> for I
     where I: Iterator<Item = Commit<'r>>
 {
     fn into_tree_graph(self) -> TreeGraphElemLineIterator<'r, Self> {
-        TreeGraphElemLineIterator { inner: self, parents: vec![] }
+        TreeGraphElemLineIterator { inner: self, parents: vec![], aliases: HashMap::new() }
     }
 }
 
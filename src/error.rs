@@ -32,11 +32,61 @@ error_chain! {
             display("Malformed date: {}", date)
         }
 
+        UnknownFormat(format: String) {
+            description("Unknown output format")
+            display("Unknown output format: {}", format)
+        }
+
+        MalformedSortSpec(spec: String) {
+            description("Malformed sort spec")
+            display("Malformed sort spec: {}", spec)
+        }
+
+        MalformedFormat(format: String) {
+            description("Malformed format string")
+            display("Malformed format string: {}", format)
+        }
+
+        UnknownPlaceholder(placeholder: String) {
+            description("Unknown placeholder in format string")
+            display("Unknown placeholder in format string: {}", placeholder)
+        }
+
+        UnbalancedConditional(format: String) {
+            description("Unbalanced conditional in format string")
+            display("Unbalanced conditional in format string: {}", format)
+        }
+
+        EmptyRange(range: String) {
+            description("The range does not contain any commits")
+            display("No commits in range: {}", range)
+        }
+
+        UnsignedCommit(id: String) {
+            description("The commit does not carry a valid signature")
+            display("Commit {} does not carry a valid signature", id)
+        }
+
         UnknownMetadataKey(key: String) {
             description("Unknown metadata key")
             display("Unknown metadata key: {}", key)
         }
 
+        MissingDependencies(key: String) {
+            description("Dependencies between issues are required but were not supplied")
+            display("Cannot determine '{}' without the dependencies between issues", key)
+        }
+
+        UnknownAction(action: String) {
+            description("Unknown bulk action")
+            display("Unknown bulk action: {}", action)
+        }
+
+        MalformedAction(action: String) {
+            description("Malformed bulk action")
+            display("Malformed bulk action: {}", action)
+        }
+
         WrappedIOError {
             description("IO Error")
             display("IO Error")
@@ -8,30 +8,58 @@
 //
 
 use libgitdit::Issue;
-use libgitdit::trailer::filter::{TrailerFilter, ValueMatcher};
-use libgitdit::trailer::{TrailerValue, spec};
-use regex::{Regex, Match};
-use std::str::FromStr;
+use libgitdit::cache::MetadataCache;
+use libgitdit::dependency::DependencyGraph;
+use git2::{Oid, Repository};
+use libgitdit::query::{self, Condition, Expr};
+use libgitdit::signature::{Program, verify};
+use libgitdit::trailer::TrailerValue;
+use libgitdit::trailer::accumulation::ValueAccumulator;
+use libgitdit::trailer::spec::TrailerSpec;
+use std::collections::{HashMap, HashSet};
 
 use error::*;
 use error::ErrorKind as EK;
 use gitext::{RemotePriorization, ReferrencesExt};
-use system::{Abortable, IteratorExt};
+use system::Abortable;
+use util::MetadataAuthorization;
 
 
 /// Representation of non-trailer metadata
 ///
-enum NonTrailer {
+pub enum NonTrailer {
     ReporterName,
     ReporterEMail,
+    Created,
+    Activity,
 }
 
 impl NonTrailer {
+    /// Get the non-trailer metadata associated with a key
+    ///
+    pub fn from_key(key: &str) -> Option<Self> {
+        match key {
+            "reporter-name"     => Some(NonTrailer::ReporterName),
+            "reporter-email"    => Some(NonTrailer::ReporterEMail),
+            "created"           => Some(NonTrailer::Created),
+            "activity"          => Some(NonTrailer::Activity),
+            _                   => None,
+        }
+    }
+
+    /// Check whether the metadata represents a date
+    ///
+    pub fn is_date(&self) -> bool {
+        matches!(*self, NonTrailer::Created | NonTrailer::Activity)
+    }
+
     /// Retrieve the value for a given issue
     ///
+    /// Dates are represented as the number of seconds since the epoch.
+    ///
     pub fn for_issue(&self, issue: &Issue) -> Result<TrailerValue> {
-        match self {
-            &NonTrailer::ReporterName => {
+        match *self {
+            NonTrailer::ReporterName => {
                 let initial = issue.initial_message()?;
                 let value = initial
                     .author()
@@ -40,7 +68,7 @@ impl NonTrailer {
                     .unwrap_or_default();
                 Ok(value)
             },
-            &NonTrailer::ReporterEMail => {
+            NonTrailer::ReporterEMail => {
                 let initial = issue.initial_message()?;
                 let value = initial
                     .author()
@@ -49,112 +77,169 @@ impl NonTrailer {
                     .unwrap_or_default();
                 Ok(value)
             },
+            NonTrailer::Created => {
+                let initial = issue.initial_message()?;
+                Ok(TrailerValue::Int(initial.time().seconds()))
+            },
+            NonTrailer::Activity => {
+                let mut latest = issue.initial_message()?.time().seconds();
+                for message in issue.messages()? {
+                    latest = ::std::cmp::max(latest, message?.time().seconds());
+                }
+                Ok(TrailerValue::Int(latest))
+            },
         }
     }
 }
 
 
-/// Filter specification
+/// Dependencies between issues
 ///
-/// This type represents a filter rule for a single piece of metadata.
+/// In addition to the dependency graph, this type records which issues are
+/// closed. Only dependencies on open issues block an issue.
 ///
-pub struct FilterSpec {
-    /// Metadata to filter
-    key: String,
-    /// Matcher for the value
-    matcher: ValueMatcher,
-    /// Indicator whether the filter shall be negated or not
-    negated: bool,
+pub struct Dependencies {
+    pub graph: DependencyGraph,
+    pub closed: HashSet<Oid>,
 }
 
-impl FilterSpec {
-    fn into_trailer<'a>(self, spec: spec::TrailerSpec<'a>) -> (TrailerFilter<'a>, bool) {
-        (TrailerFilter::new(spec, self.matcher), self.negated)
+impl Dependencies {
+    /// Get the open dependencies of an issue
+    ///
+    pub fn blockers(&self, issue: Oid) -> Vec<Oid> {
+        self.graph.blockers(issue, |dependency| self.closed.contains(&dependency))
     }
 
-    fn into_nontrailer<'a>(self, spec: NonTrailer) -> (NonTrailer, ValueMatcher, bool) {
-        (spec, self.matcher, self.negated)
+    /// Check whether an issue is open and not blocked by any dependency
+    ///
+    pub fn is_ready(&self, issue: Oid) -> bool {
+        !self.closed.contains(&issue) && self.blockers(issue).is_empty()
     }
 }
 
-impl FromStr for FilterSpec {
-    type Err = Error;
-
-    fn from_str(s: &str) -> Result<Self> {
-        lazy_static! {
-            // regex for parsing a trailer spec
-            static ref RE: Regex = Regex::new(r"^(!)?([[:alnum:]-]+)((=|~)(.*))?$").unwrap();
-        }
-
-        let parts = RE
-            .captures(s)
-            .ok_or_else(|| Error::from_kind(EK::MalformedFilterSpec(s.to_owned())))?;
-
-        let key = parts
-            .get(2)
-            .as_ref()
-            .map(Match::as_str)
-            .ok_or_else(|| Error::from_kind(EK::MalformedFilterSpec(s.to_owned())))?;
 
-        let matcher = if parts.get(3).is_some() {
-            let op = parts
-                .get(4)
-                .as_ref()
-                .map(Match::as_str)
-                .ok_or_else(|| Error::from_kind(EK::MalformedFilterSpec(s.to_owned())))?;
-
-            let value = parts
-                .get(5)
-                .as_ref()
-                .map(Match::as_str)
-                .ok_or_else(|| Error::from_kind(EK::MalformedFilterSpec(s.to_owned())))?;
+/// Representation of metadata derived from dependencies between issues
+///
+pub enum DependencyMetadata {
+    Blocked,
+    Ready,
+}
 
-            match op {
-                "=" => ValueMatcher::Equals(TrailerValue::from_slice(value)),
-                "~" => ValueMatcher::Contains(value.to_string()),
-                _   => return Err(Error::from_kind(EK::MalformedFilterSpec(s.to_owned()))),
-            }
-        } else {
-            ValueMatcher::Any
-        };
+impl DependencyMetadata {
+    /// Get the dependency metadata associated with a key
+    ///
+    pub fn from_key(key: &str) -> Option<Self> {
+        match key {
+            "blocked"   => Some(DependencyMetadata::Blocked),
+            "ready"     => Some(DependencyMetadata::Ready),
+            _           => None,
+        }
+    }
 
-        Ok(FilterSpec {
-            key: key.to_string(),
-            matcher: matcher,
-            negated: parts.get(1).is_some()
-        })
+    /// Retrieve the values for a given issue
+    ///
+    /// For `blocked`, the values are the ids of the open dependencies of the
+    /// issue. For `ready`, there is a single value, "true", if the issue is
+    /// ready and no value otherwise.
+    ///
+    pub fn for_issue(&self, issue: Oid, dependencies: &Dependencies) -> Vec<TrailerValue> {
+        match *self {
+            DependencyMetadata::Blocked => dependencies
+                .blockers(issue)
+                .into_iter()
+                .map(|id| TrailerValue::String(id.to_string()))
+                .collect(),
+            DependencyMetadata::Ready if dependencies.is_ready(issue) => vec![TrailerValue::from_slice("true")],
+            DependencyMetadata::Ready => Vec::new(),
+        }
     }
 }
 
 
 /// Metadata filter
 ///
+/// The filter is based on a query expression. Keys in the expression's
+/// conditions may either refer to non-trailer metadata or to trailers declared
+/// in the configuration.
+///
+/// Optionally, only trailers from messages carrying a good signature or from
+/// messages authorized by an authorization are considered.
+///
+/// Keys referring to dependency metadata require the dependencies to be
+/// supplied via `with_dependencies()`. Whether this is the case may be
+/// determined via `needs_dependencies()`. Conditions on such keys can not be
+/// evaluated without the dependencies and are reported as an error.
+///
 pub struct MetadataFilter<'a> {
     prios: &'a RemotePriorization,
-    nontrailers: Vec<(NonTrailer, ValueMatcher, bool)>,
-    trailers: Vec<(TrailerFilter<'a>, bool)>,
+    cache: Option<&'a MetadataCache>,
+    verifier: Option<(&'a Repository, &'a Program)>,
+    authorization: Option<&'a MetadataAuthorization<'a>>,
+    dependencies: Option<&'a Dependencies>,
+    expr: Option<Expr>,
+    trailers: HashMap<String, TrailerSpec>,
 }
 
 impl<'a> MetadataFilter<'a> {
     /// Create a new metadata filter
     ///
-    pub fn new<I>(prios: &'a RemotePriorization, spec: I) -> Result<Self>
-        where I: IntoIterator<Item = FilterSpec>
+    /// The filter will only let pass issues matching all the expressions
+    /// supplied. Keys of the expressions are resolved to pieces of metadata
+    /// using the trailer specifications supplied, which map names to
+    /// specifications.
+    ///
+    pub fn new<I>(prios: &'a RemotePriorization, trailer_specs: &HashMap<String, TrailerSpec>, exprs: I) -> Result<Self>
+        where I: IntoIterator<Item = Expr>
     {
-        let mut nontrailers = Vec::new();
-        let mut trailers = Vec::new();
-
-        for s in spec.into_iter() {
-            match s.key.as_ref() {
-                "status"            => trailers.push(s.into_trailer(spec::ISSUE_STATUS_SPEC.clone())),
-                "type"              => trailers.push(s.into_trailer(spec::ISSUE_TYPE_SPEC.clone())),
-                "reporter-name"     => nontrailers.push(s.into_nontrailer(NonTrailer::ReporterName)),
-                "reporter-email"    => nontrailers.push(s.into_nontrailer(NonTrailer::ReporterEMail)),
-                _                   => return Err(Error::from_kind(EK::UnknownMetadataKey(s.key.to_string()))),
+        let expr = match exprs.into_iter().fold(None, |acc: Option<Expr>, expr| match acc {
+            Some(acc)   => Some(acc.and(expr)),
+            None        => Some(expr),
+        }) {
+            Some(expr)  => expr,
+            None        => return Ok(Self::empty(prios)),
+        };
+
+        // Resolve keys which don't refer to non-trailer metadata
+        let mut trailers = HashMap::new();
+        for condition in expr.conditions() {
+            if NonTrailer::from_key(&condition.key).is_some() ||
+                DependencyMetadata::from_key(&condition.key).is_some() {
+                continue;
+            }
+            match trailer_specs.get(&condition.key) {
+                Some(spec)  => { trailers.insert(condition.key.clone(), spec.clone()); },
+                None        => return Err(Error::from_kind(EK::UnknownMetadataKey(condition.key.clone()))),
             }
         }
 
-        Ok(MetadataFilter { prios: prios, nontrailers: nontrailers, trailers: trailers })
+        // Values for dates need to be converted to timestamps and values for
+        // typed metadata to the appropriate kind of value
+        let expr = expr.map_conditions(&mut |condition: Condition| {
+            match NonTrailer::from_key(&condition.key) {
+                Some(ref nontrailer) if nontrailer.is_date() => Ok(Condition {
+                    matcher: query::date_matcher(condition.matcher)?,
+                    key: condition.key,
+                }),
+                Some(_) => Ok(condition),
+                None => match trailers.get(&condition.key) {
+                    Some(spec) => Ok(Condition {
+                        matcher: spec.value_type.matcher(condition.matcher)?,
+                        key: condition.key,
+                    }),
+                    None => Ok(condition),
+                },
+            }
+        })?;
+
+        Ok(MetadataFilter {
+            prios,
+            cache: None,
+            verifier: None,
+            authorization: None,
+            dependencies: None,
+            expr: Some(expr),
+            trailers,
+        })
     }
 
     /// Create an empty metadata filter
@@ -163,52 +248,172 @@ impl<'a> MetadataFilter<'a> {
     ///
     pub fn empty(prios: &'a RemotePriorization) -> Self {
         MetadataFilter {
-            prios: prios,
-            nontrailers: Vec::new(),
-            trailers: Vec::new(),
+            prios,
+            cache: None,
+            verifier: None,
+            authorization: None,
+            dependencies: None,
+            expr: None,
+            trailers: HashMap::new(),
         }
     }
 
+    /// Use a metadata cache for retrieving trailers
+    ///
+    pub fn with_cache(mut self, cache: Option<&'a MetadataCache>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Only consider trailers from messages carrying a good signature
+    ///
+    /// Signatures are verified using the verifier supplied. Since the cache
+    /// does not record signatures, it is not used for such a filter.
+    ///
+    pub fn with_verifier(mut self, verifier: Option<(&'a Repository, &'a Program)>) -> Self {
+        self.verifier = verifier;
+        self
+    }
+
+    /// Only consider trailers from messages authorized by an authorization
+    ///
+    /// The authorization is supplied along with the key of the assignee
+    /// trailers. Since the cache does not record authors, it is not used for
+    /// such a filter.
+    ///
+    pub fn with_authorization(mut self, authorization: Option<&'a MetadataAuthorization<'a>>) -> Self {
+        self.authorization = authorization;
+        self
+    }
+
+    /// Use dependencies between issues for dependency metadata
+    ///
+    pub fn with_dependencies(mut self, dependencies: Option<&'a Dependencies>) -> Self {
+        self.dependencies = dependencies;
+        self
+    }
+
+    /// Check whether the filter refers to dependency metadata
+    ///
+    /// Dependencies need to be supplied to such a filter.
+    ///
+    pub fn needs_dependencies(&self) -> bool {
+        self.expr.as_ref().is_some_and(|expr| expr
+            .conditions()
+            .iter()
+            .any(|condition| DependencyMetadata::from_key(&condition.key).is_some()))
+    }
+
     /// Filter an issue
     ///
+    /// Errors encountered while filtering abort the program.
+    ///
     pub fn filter(&self, issue: &Issue) -> bool {
         // NOTE: if we ever add the filters crate as a dependency, this method
         //       may be transferred to an implementatio nof the Filter trait
-        use git2::ObjectType;
-        use libgitdit::iter::MessagesExt;
-        use std::collections::HashMap;
+        self.try_filter(issue).unwrap_or_abort()
+    }
 
-        // Check non-trailer metadata first
-        for metadata in self.nontrailers.iter() {
-            let value = metadata.0.for_issue(issue).unwrap_or_abort();
-            if !metadata.1.matches(&value) ^ metadata.2 {
-                return false;
-            }
+    /// Filter an issue, reporting errors to the caller
+    ///
+    pub fn try_filter(&self, issue: &Issue) -> Result<bool> {
+        let expr = match self.expr {
+            Some(ref expr)  => expr,
+            None            => return Ok(true),
+        };
+
+        // Accumulating trailers may be expensive, so we only do it if we
+        // encounter a condition which requires it.
+        let mut acc = None;
+
+        // The predicate can't report errors. Hence, we keep the first error
+        // encountered and report it after the evaluation.
+        let mut error = None;
+        let result = expr.evaluate(&mut |condition| self
+            .matches(issue, condition, &mut acc)
+            .unwrap_or_else(|err| {
+                error.get_or_insert(err);
+                false
+            })
+        );
+        match error {
+            Some(err)   => Err(err),
+            None        => Ok(result),
+        }
+    }
+
+    /// Check whether an issue matches a single condition
+    ///
+    /// The trailers relevant for the filter are accumulated on demand and
+    /// stored in `acc` for subsequent conditions.
+    ///
+    fn matches(&self,
+               issue: &Issue,
+               condition: &Condition,
+               acc: &mut Option<HashMap<String, ValueAccumulator>>
+    ) -> Result<bool> {
+        if let Some(nontrailer) = NonTrailer::from_key(&condition.key) {
+            return Ok(condition.matcher.matches(&nontrailer.for_issue(issue)?));
         }
 
-        // Filtering may be expensive, so it makes sense to return early if the
-        // filter is empty.
-        if self.trailers.is_empty() {
-            return true;
+        if let Some(metadata) = DependencyMetadata::from_key(&condition.key) {
+            let dependencies = self
+                .dependencies
+                .ok_or_else(|| Error::from_kind(EK::MissingDependencies(condition.key.clone())))?;
+            return Ok(condition.matcher.matches_any(metadata.for_issue(issue.id(), dependencies)));
         }
 
+        if acc.is_none() {
+            *acc = Some(self.accumulate(issue)?);
+        }
+        Ok(self.trailers
+            .get(&condition.key)
+            .and_then(|spec| acc
+                .as_ref()
+                .and_then(|acc| acc.get(spec.key.as_ref()))
+                .map(|values| (spec, values)))
+            .is_some_and(|(spec, values)| condition.matcher.matches_any(values
+                .clone()
+                .into_iter()
+                .map(|value| spec.value_type.convert(value)))))
+    }
+
+    /// Accumulate the trailers relevant for the filter
+    ///
+    fn accumulate(&self, issue: &Issue) -> Result<HashMap<String, ValueAccumulator>> {
+        use git2::ObjectType;
+        use libgitdit::iter::MessagesExt;
+
         // Get the head reference
-        let head = issue
-            .heads()
-            .abort_on_err()
-            .select_ref(self.prios)
-            .map(|head| head.peel(ObjectType::Commit).unwrap_or_abort().id());
-
-        // Accumulate all the metadata we care about
-        let acc: HashMap<_, _> = head
-            .into_iter()
-            .flat_map(|head| issue.messages_from(head).abort_on_err())
-            .accumulate_trailers(self.trailers.iter().map(|i| i.0.spec()));
-
-        // Compute whether all constraints are met
-        self.trailers
-            .iter()
-            .all(|spec| spec.0.matches(&acc) ^ spec.1)
+        let heads: Vec<_> = issue.heads()?.collect::<::std::result::Result<_, _>>()?;
+        let head = match heads.select_ref(self.prios) {
+            Some(head)  => Some(head.peel(ObjectType::Commit)?.id()),
+            None        => None,
+        };
+
+        let specs = self.trailers.values();
+        if let (None, None, Some(cache), Some(head)) = (self.verifier, self.authorization, self.cache, head) {
+            return cache.accumulate(issue, head, specs).map_err(Error::from);
+        }
+
+        let mut messages = Vec::new();
+        if let Some(head) = head {
+            for message in issue.messages_from(head)? {
+                let message = message?;
+                if let Some((repo, verifier)) = self.verifier {
+                    if !verify(repo, &message, verifier)?.is_good() {
+                        continue;
+                    }
+                }
+                messages.push(message);
+            }
+        }
+        match self.authorization {
+            Some((authorization, assignee_key)) => Ok(messages
+                .into_iter()
+                .accumulate_authorized_trailers(specs, authorization, assignee_key)?),
+            None => Ok(messages.into_iter().accumulate_trailers(specs)),
+        }
     }
 }
 
@@ -10,7 +10,6 @@
 #[macro_use] extern crate clap;
 #[macro_use] extern crate error_chain;
 #[macro_use] extern crate is_match;
-#[macro_use] extern crate lazy_static;
 #[macro_use] extern crate log;
 extern crate atty;
 extern crate chrono;
@@ -23,8 +22,10 @@ extern crate regex;
 mod error;
 mod filters;
 mod gitext;
+mod sorting;
 mod system;
 mod util;
+mod web;
 
 use clap::App;
 use git2::Commit;
@@ -108,11 +109,18 @@ fn create_message(matches: &clap::ArgMatches) {
     // read all from stdin
     let mut message = String::new();
     io::stdin().read_to_string(&mut message).unwrap_or_abort();
-    let id = match issue {
-        Some(i) => i.add_message(&author, &committer, message, &tree, parent_refs)
+    let signer = repo.cli_signer(matches);
+    let id = match (issue, signer) {
+        (Some(i), Some(signer)) => i.add_signed_message(&author, &committer, message, &tree, parent_refs, &signer)
                     .unwrap_or_abort()
                     .id(),
-        None => repo.create_issue(&author, &committer, message, &tree, parent_refs)
+        (Some(i), None) => i.add_message(&author, &committer, message, &tree, parent_refs)
+                    .unwrap_or_abort()
+                    .id(),
+        (None, Some(signer)) => repo.create_signed_issue(&author, &committer, message, &tree, parent_refs, &signer)
+                    .unwrap_or_abort()
+                    .id(),
+        (None, None) => repo.create_issue(&author, &committer, message, &tree, parent_refs)
                     .unwrap_or_abort()
                     .id(),
     };
@@ -142,16 +150,59 @@ fn get_issue_metadata(matches: &clap::ArgMatches) {
 
     // note: "head" is always present since it is a required parameter
     let head = repo.value_to_commit(matches.value_of("head").unwrap());
-    let trailers = repo
-        .issue_messages_iter(head)
-        .abort_on_err()
-        .flat_map(|commit| commit.trailers());
 
-    if let Some(key) = matches.value_of("key") {
+    // Retrieve the trailers from the cache if possible. The cache is keyed by
+    // issue, so we can only use it if the head is part of an issue.
+    let cached = repo
+        .enabled_metadata_cache()
+        .and_then(|cache| repo
+            .issue_with_message(&head)
+            .ok()
+            .map(|issue| cache.trailers(&issue, head.id()).unwrap_or_abort())
+        );
+    let trailers: Vec<_> = match cached {
+        Some(trailers) => trailers,
+        None => repo
+            .issue_messages_iter(head)
+            .abort_on_err()
+            .flat_map(|commit| commit.trailers())
+            .collect(),
+    };
+
+    if let Some(format) = util::json_format_from_args(matches) {
+        use display::JsonValue;
+
+        let items: Vec<JsonValue> = if let Some(key) = matches.value_of("key") {
+            let trailers = trailers
+                .into_iter()
+                .filter(|trailer| trailer.key.as_ref() == key);
+            if matches.is_present("accumulate-latest") {
+                trailers.take(1).map(JsonValue::from).collect()
+            } else {
+                trailers.map(JsonValue::from).collect()
+            }
+        } else {
+            trailers.into_iter().map(JsonValue::from).collect()
+        };
+        let items = if matches.is_present("values-only") {
+            items
+                .into_iter()
+                .map(|item| match item {
+                    JsonValue::Object(mut members) => members.remove("value").unwrap_or(JsonValue::Null),
+                    other => other,
+                })
+                .collect()
+        } else {
+            items
+        };
+        format.lines(items).print_lines().unwrap_or_abort();
+    } else if let Some(key) = matches.value_of("key") {
         let policy = if matches.is_present("accumulate-latest") {
             accumulation::AccumulationPolicy::Latest
         } else if matches.is_present("accumulate-list") {
             accumulation::AccumulationPolicy::List
+        } else if matches.is_present("accumulate-set") {
+            accumulation::AccumulationPolicy::Set
         } else {
             accumulation::AccumulationPolicy::List
         };
@@ -177,8 +228,446 @@ fn get_issue_tree_init_hashes(_: &clap::ArgMatches) {
 }
 
 
+/// hook subcommand implementation
+///
+fn hook(matches: &clap::ArgMatches) {
+    match matches.subcommand() {
+        ("update", Some(sub_matches)) => hook_update(sub_matches),
+        _ => {
+            writeln!(io::stderr(), "{}", matches.usage()).ok();
+            std::process::exit(1);
+        },
+    }
+}
+
+
+/// hook update subcommand implementation
+///
+fn hook_update(matches: &clap::ArgMatches) {
+    use error::{ErrorKind as EK, LoggableError};
+    use libgitdit::policy::{Policy, RefUpdate};
+    use libgitdit::signature::verify;
+    use libgitdit::trailer::spec::ISSUE_STATUS_SPEC;
+    use std::collections::HashMap;
+
+    let repo = util::open_dit_repo();
+    let policy = Policy::for_repository(&repo).unwrap_or_abort();
+
+    // NOTE: all the arguments are required
+    let update = RefUpdate::new(
+        matches.value_of("refname").unwrap(),
+        git2::Oid::from_str(matches.value_of("old").unwrap()).unwrap_or_abort(),
+        git2::Oid::from_str(matches.value_of("new").unwrap()).unwrap_or_abort(),
+    );
+
+    // The author of a commit may be set to anything. Hence, we identify the
+    // pushing user via the signatures of the messages pushed, unless the
+    // user is supplied. Messages already reachable from any reference are
+    // not verified again.
+    let user = matches.value_of("user").map(str::to_owned);
+    let mut identities = HashMap::new();
+    if let (true, Some(new)) = (policy.requires_signature() || user.is_none(), update.new) {
+        let verifier = repo.signature_verifier();
+        let mut revwalk = repo.revwalk().unwrap_or_abort();
+        revwalk.push(new).unwrap_or_abort();
+        revwalk.hide_glob("refs/*").unwrap_or_abort();
+
+        for id in revwalk {
+            let commit = id
+                .and_then(|id| repo.find_commit(id))
+                .unwrap_or_abort();
+            let identity = verify(&repo, &commit, &verifier)
+                .unwrap_or_abort()
+                .identity(&commit);
+            match identity {
+                Some(identity) => { identities.insert(commit.id(), identity); },
+                None if policy.requires_signature() => {
+                    error::Error::from_kind(EK::UnsignedCommit(commit.id().to_string())).log();
+                    std::process::exit(1);
+                },
+                None => {},
+            }
+        }
+    }
+    let pusher = user
+        .clone()
+        .or_else(|| update.new.and_then(|new| identities.get(&new).cloned()));
+
+    let mut specs = repo.trailer_specs().unwrap_or_abort();
+    let assignee_key = specs
+        .remove("assignee")
+        .map(|spec| spec.key.into_owned())
+        .unwrap_or_else(|| "Dit-assignee".to_owned());
+    let status_key = specs
+        .remove("status")
+        .unwrap_or(ISSUE_STATUS_SPEC)
+        .key
+        .into_owned();
+    policy
+        .check(&repo, &update, pusher.as_deref(), &assignee_key)
+        .unwrap_or_abort();
+
+    // changes of the status of an issue need to conform to the workflow
+    let workflow = repo.workflow().unwrap_or_abort();
+    let identify = |message: &Commit| Ok(user
+        .clone()
+        .or_else(|| identities.get(&message.id()).cloned()));
+    policy
+        .check_workflow(&repo, &workflow, &update, identify, &status_key, &assignee_key)
+        .unwrap_or_abort();
+}
+
+
 // Porcelain subcommand implementations
 
+/// amend subcommand implementation
+///
+fn amend_impl(matches: &clap::ArgMatches) {
+    use libgitdit::signature::Signer;
+    use libgitdit::trailer::spec::MESSAGE_SUPERSEDES_SPEC;
+    use util::message_from_args;
+
+    let repo = util::open_dit_repo();
+    let committer = repo.signature().unwrap_or_abort();
+
+    // the unwrap is safe since `original` is a required value
+    let original = repo.value_to_commit(matches.value_of("original").unwrap());
+    let issue = repo.issue_with_message(&original).unwrap_or_abort();
+
+    // Like `git commit --amend`, we keep the original author by default.
+    let author = if matches.is_present("author") || matches.is_present("date") {
+        repo.cli_author(matches)
+    } else {
+        original.author().to_owned()
+    };
+
+    // get the message, either from the command line argument or an editor
+    let message = if let Some(m) = message_from_args(matches) {
+        // the message was supplied via the command line
+        m.into_iter()
+         .chain(repo.prepare_trailers(matches)
+                    .into_iter()
+                    .map(|t| t.to_string()))
+         .collect()
+    } else {
+        // we need an editor
+
+        // get the path where we want to edit the message
+        let path = repo.commitmsg_edit_path(matches);
+
+        { // write
+            // The reference to the message superseded by the original message
+            // will be replaced.
+            let supersedes = format!("{}:", MESSAGE_SUPERSEDES_SPEC.key);
+            let mut file = File::create(path.as_path()).unwrap_or_abort();
+            original
+                .message_lines()
+                .filter(|line| !line.starts_with(supersedes.as_str()))
+                .write_lines(&mut file)
+                .unwrap_or_abort();
+            repo.prepare_trailers(matches)
+                .write_lines(&mut file)
+                .unwrap_or_abort();
+            file.flush().unwrap_or_abort();
+        }
+
+        repo.get_commit_msg(path)
+    }.into_iter().collect_string();
+
+    let signer = repo.cli_signer(matches);
+    let amended = issue
+        .amend_message(&author, &committer, message.trim(), &original, signer.as_ref().map(|s| s as &dyn Signer))
+        .unwrap_or_abort();
+    println!("[dit][amend] {}", amended.id());
+}
+
+
+/// apply subcommand implementation
+///
+fn apply_impl(matches: &clap::ArgMatches) {
+    use libgitdit::patch::series_tip;
+
+    let repo = util::open_dit_repo();
+
+    // the unwrap is safe since `message` is a required value
+    let message = repo.value_to_commit(matches.value_of("message").unwrap());
+    let issue = repo.issue_with_message(&message).unwrap_or_abort();
+    let tip = series_tip(&issue, &message).unwrap_or_abort();
+
+    let name = matches
+        .value_of("branch")
+        .map(String::from)
+        .unwrap_or_else(|| format!("patch/{:.7}", message.id()));
+    let mut branch = repo.branch(&name, &tip, false).unwrap_or_abort();
+
+    if !repo.is_bare() && !matches.is_present("no-checkout") {
+        use error::{Error, LoggableError};
+
+        let mut options = git2::build::CheckoutBuilder::new();
+        options.safe();
+        if let Err(err) = repo.checkout_tree(tip.as_object(), Some(&mut options)) {
+            // we don't want to leave the branch behind if we can't check it out
+            branch.delete().unwrap_or_abort();
+            Error::from(err).log();
+            std::process::exit(1);
+        }
+        // the unwrap is safe since the branch was created with a valid name
+        repo.set_head(branch.get().name().unwrap()).unwrap_or_abort();
+    }
+    println!("[dit][apply] {} {}", name, tip.id());
+}
+
+
+/// assign subcommand implementation
+///
+fn assign_impl(matches: &clap::ArgMatches) {
+    use libgitdit::trailer::Trailer;
+
+    let repo = util::open_dit_repo();
+    let spec = repo.assignee_spec();
+
+    // NOTE: the assignee is a required parameter
+    let trailer = spec
+        .parse_value(matches.value_of("assignee").unwrap())
+        .map(|value| Trailer::new(spec.key.as_ref(), &value.to_string()))
+        .unwrap_or_abort();
+    repo.change_metadata(matches, vec![trailer]);
+}
+
+
+/// bulk subcommand implementation
+///
+fn bulk_impl(matches: &clap::ArgMatches) {
+    use error::{Error, ErrorKind as EK, LoggableError};
+    use filters::MetadataFilter;
+    use libgitdit::Issue;
+    use libgitdit::trailer::Trailer;
+    use libgitdit::trailer::spec::ISSUE_STATUS_SPEC;
+    use sorting::IssueSorter;
+
+    let repo = util::open_dit_repo();
+    let remote_prios = repo.remote_priorization();
+    let cache = repo.enabled_metadata_cache();
+    let authorization = repo.metadata_authorization();
+    let trailer_specs = repo.trailer_specs().unwrap_or_abort();
+    let author = repo.cli_author(matches);
+    let id_len = repo.abbreviation_length(matches);
+
+    // NOTE: the action is a required parameter
+    let action: Vec<&str> = matches.values_of("action").unwrap().collect();
+    let malformed = || Error::from_kind(EK::MalformedAction(action.join(" ")));
+
+    // We construct the trailers before selecting any issue, since we want to
+    // report malformed actions early.
+    let (trailers, verbs) = match action[0] {
+        "tag" => {
+            if action.len() < 2 {
+                malformed().log();
+                std::process::exit(1);
+            }
+            (repo.parse_trailers(action[1..].iter().cloned()), ("Tagged", "tag"))
+        },
+        "close" => {
+            let spec = trailer_specs.get("status").cloned().unwrap_or(ISSUE_STATUS_SPEC);
+            let status = match action.len() {
+                1 => repo.closed_status().remove(0),
+                2 => action[1].to_owned(),
+                _ => {
+                    malformed().log();
+                    std::process::exit(1);
+                },
+            };
+            let trailer = spec
+                .parse_value(&status)
+                .map(|value| Trailer::new(spec.key.as_ref(), &value.to_string()))
+                .unwrap_or_abort();
+            (vec![trailer], ("Closed", "close"))
+        },
+        "comment" => (Vec::new(), ("Commented on", "comment on")),
+        "push" => {
+            if action.len() != 2 {
+                malformed().log();
+                std::process::exit(1);
+            }
+            (Vec::new(), ("Pushed", "push"))
+        },
+        name => {
+            Error::from_kind(EK::UnknownAction(name.to_owned())).log();
+            std::process::exit(1);
+        },
+    };
+
+    // select the issues via the filter
+    let filter = {
+        // NOTE: the filter is a required parameter
+        let exprs = matches.values_of("filter").unwrap().map(str::parse).abort_on_err();
+        MetadataFilter::new(&remote_prios, &trailer_specs, exprs).unwrap_or_abort()
+    }.with_cache(cache.as_ref()).with_authorization(authorization.as_ref());
+    let dependencies = if filter.needs_dependencies() {
+        Some(repo.dependencies(&remote_prios))
+    } else {
+        None
+    };
+    let filter = filter.with_dependencies(dependencies.as_ref());
+    let issues: Vec<Issue> = repo
+        .issues()
+        .unwrap_or_abort()
+        .into_iter()
+        .filter(|issue| filter.filter(issue))
+        .collect();
+    let issues = IssueSorter::new(&remote_prios, &trailer_specs, vec!["created:desc"])
+        .unwrap_or_abort()
+        .with_cache(cache.as_ref())
+        .with_authorization(authorization.as_ref())
+        .sort(issues)
+        .unwrap_or_abort();
+    if issues.is_empty() {
+        warn!("No issue matches the filter.");
+        return;
+    }
+
+    // present the issues affected to the user
+    let mut stdout = io::stdout();
+    for issue in issues.iter() {
+        let initial = issue.initial_message().unwrap_or_abort();
+        writeln!(stdout, "{:.*} {}", id_len, issue.id(), initial.summary().unwrap_or_default())
+            .unwrap_or_abort();
+    }
+
+    // Illegal changes of the status are reported even in a dry run. We check
+    // all of them before creating any message, since we don't want to end up
+    // with only some of the issues changed.
+    repo.check_status_transitions(&issues, &author, &trailers);
+
+    if matches.is_present("dry-run") {
+        writeln!(stdout, "Would {} {} issue(s).", verbs.1, issues.len()).unwrap_or_abort();
+        return;
+    }
+
+    if action[0] == "push" {
+        repo.push_issues(action[1], &issues);
+    } else {
+        // A comment is mandatory only for the comment action. Its arguments
+        // are the paragraphs of the comment.
+        let comment = if action[0] == "comment" && action.len() > 1 {
+            Some(action[1..].join("\n\n"))
+        } else {
+            repo.cli_comment(matches)
+        };
+        if action[0] == "comment" && comment.is_none() {
+            malformed().log();
+            std::process::exit(1);
+        }
+
+        let signer = repo.cli_signer(matches);
+        for issue in issues.iter() {
+            repo.add_status_message(issue, &author, comment.as_deref(), trailers.clone(), &[], signer.as_ref());
+        }
+    }
+    writeln!(stdout, "{} {} issue(s).", verbs.0, issues.len()).unwrap_or_abort();
+}
+
+
+/// close subcommand implementation
+///
+fn close_impl(matches: &clap::ArgMatches) {
+    use libgitdit::trailer::Trailer;
+    use libgitdit::trailer::spec::ISSUE_STATUS_SPEC;
+
+    let repo = util::open_dit_repo();
+    let spec = repo
+        .trailer_specs()
+        .unwrap_or_abort()
+        .remove("status")
+        .unwrap_or(ISSUE_STATUS_SPEC);
+
+    let status = matches
+        .value_of("status")
+        .map(str::to_owned)
+        .unwrap_or_else(|| repo.closed_status().remove(0));
+    let trailer = spec
+        .parse_value(&status)
+        .map(|value| Trailer::new(spec.key.as_ref(), &value.to_string()))
+        .unwrap_or_abort();
+    repo.change_metadata(matches, vec![trailer]);
+}
+
+
+/// deps subcommand implementation
+///
+fn deps_impl(matches: &clap::ArgMatches) {
+    let repo = util::open_dit_repo();
+    let remote_prios = repo.remote_priorization();
+    let id_len = repo.abbreviation_length(matches);
+    let dependencies = repo.dependencies(&remote_prios);
+
+    let subject = |id| repo
+        .find_commit(id)
+        .unwrap_or_abort()
+        .summary()
+        .unwrap_or_default()
+        .to_owned();
+
+    let mut stdout = io::stdout();
+    if matches.is_present("dot") {
+        let dot = dependencies.graph.to_dot(|id| format!("{:.*} {}", id_len, id, subject(id)));
+        write!(stdout, "{}", dot).unwrap_or_abort();
+        return;
+    }
+
+    if matches.is_present("check") {
+        let cycles = dependencies.graph.cycles();
+        for cycle in cycles.iter() {
+            let ids: Vec<String> = cycle.iter().map(|id| format!("{:.*}", id_len, id)).collect();
+            writeln!(stdout, "cycle {}", ids.join(" ")).unwrap_or_abort();
+        }
+        if !cycles.is_empty() {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // NOTE: the issue is required unless one of the flags handled above is
+    //       present
+    let issue = repo.cli_issue(matches).unwrap().id();
+
+    let lines = dependencies
+        .graph
+        .dependencies(issue)
+        .into_iter()
+        .map(|id| ("depends-on", id))
+        .chain(dependencies.graph.dependents(issue).into_iter().map(|id| ("blocks", id)));
+    for (relation, id) in lines {
+        let status = if dependencies.closed.contains(&id) { "closed" } else { "open" };
+        writeln!(stdout, "{} {:.*} {} {}", relation, id_len, id, status, subject(id)).unwrap_or_abort();
+    }
+}
+
+/// export-mbox subcommand implementation
+///
+fn export_mbox_impl(matches: &clap::ArgMatches) {
+    use libgitdit::mbox::Mail;
+    use util::value_to_issue;
+
+    let repo = util::open_dit_repo();
+
+    // the unwrap is safe since `issue` is a required value
+    for issue in matches.values_of("issue").unwrap().map(|value| value_to_issue(&repo, value)) {
+        // emails are written in the order in which they were created
+        let mut messages = issue.messages().unwrap_or_abort();
+        messages
+            .revwalk
+            .set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::REVERSE)
+            .unwrap_or_abort();
+
+        for message in messages.abort_on_err() {
+            let mail = Mail::from_message(&issue, &message).unwrap_or_abort();
+            print!("{}", mail.to_mbox());
+        }
+    }
+}
+
+
 /// fetch subcommand implementation
 ///
 fn fetch_impl(matches: &clap::ArgMatches) {
@@ -220,6 +709,57 @@ fn fetch_impl(matches: &clap::ArgMatches) {
 }
 
 
+/// fixes subcommand implementation
+///
+fn fixes_impl(matches: &clap::ArgMatches) {
+    use libgitdit::fixes;
+    use libgitdit::trailer::Trailer;
+    use std::collections::HashSet;
+
+    let repo = util::open_dit_repo();
+    let prios = repo.remote_priorization();
+    let id_len = repo.abbreviation_length(matches);
+    let fixes = fixes::fixes(&repo, repo.fix_tips(matches.values_of("branch"))).unwrap_or_abort();
+
+    let mut stdout = io::stdout();
+    for fix in fixes.iter() {
+        let subject = repo
+            .find_commit(fix.issue)
+            .unwrap_or_abort()
+            .summary()
+            .unwrap_or_default()
+            .to_owned();
+        writeln!(stdout, "{:.*} fixes {:.*} {}", id_len, fix.commit, id_len, fix.issue, subject).unwrap_or_abort();
+    }
+
+    if !matches.is_present("close") {
+        return;
+    }
+
+    // Close each open issue fixed, mentioning the most recent fixing commit
+    let status_spec = repo.status_spec();
+    let closed_status = repo.closed_status();
+    let author = repo.signature().unwrap_or_abort();
+    let signer = repo.cli_signer(matches);
+    let mut seen = HashSet::new();
+    for fix in fixes.iter().filter(|fix| seen.insert(fix.issue)) {
+        let issue = repo.find_issue(fix.issue).unwrap_or_abort();
+        let head = match repo.issue_head(&issue, &prios) {
+            Some(head) => head,
+            None => continue,
+        };
+        if util::is_closed(&repo.accumulated_metadata(&issue, head), &status_spec.key, &closed_status) {
+            continue;
+        }
+
+        let trailer = Trailer::new(status_spec.key.as_ref(), &closed_status[0]);
+        let body = format!("Fixed by commit {}.", fix.commit);
+        repo.add_status_message(&issue, &author, Some(&body), vec![trailer], &[], signer.as_ref());
+        writeln!(stdout, "closed {:.*}", id_len, fix.issue).unwrap_or_abort();
+    }
+}
+
+
 /// gc subcommand implementation
 ///
 fn gc_impl(matches: &clap::ArgMatches) {
@@ -239,9 +779,19 @@ fn gc_impl(matches: &clap::ArgMatches) {
             .collect_heads(collect_heads)
     };
 
-    let refs = repo
-        .cli_issues(matches)
-        .unwrap_or_else(|| repo.issues().unwrap_or_abort())
+    let issues = repo.cli_issues(matches);
+    let prune_cache = issues.is_none() && !matches.is_present("dry-run");
+    let issues = issues.unwrap_or_else(|| repo.issues().unwrap_or_abort());
+
+    // Entries of the metadata cache for issues which are gone are not needed
+    // anymore. We only know about all issues if none were specified, though.
+    if prune_cache {
+        if let Some(cache) = repo.enabled_metadata_cache() {
+            cache.prune(issues.iter()).unwrap_or_abort();
+        }
+    }
+
+    let refs = issues
         .into_iter()
         .map(|issue| collect.for_issue(&issue))
         .abort_on_err()
@@ -259,6 +809,186 @@ fn gc_impl(matches: &clap::ArgMatches) {
 }
 
 
+/// head subcommand implementation
+///
+fn head_impl(matches: &clap::ArgMatches) {
+    use libgitdit::error::{Error, ErrorKind};
+    use libgitdit::signature::Signer;
+    use libgitdit::trailer::spec::MESSAGE_SUPERSEDES_SPEC;
+
+    let repo = util::open_dit_repo();
+    let prios = repo.remote_priorization();
+
+    // NOTE: the issue is a required parameter
+    let issue = repo.cli_issue(matches).unwrap();
+
+    if let Some(message) = matches.value_of("set") {
+        let message = repo.value_to_commit(message);
+        issue.set_head(message.id()).unwrap_or_abort();
+        return;
+    }
+
+    let head = repo
+        .issue_head(&issue, &prios)
+        .ok_or_else(|| Error::from_kind(ErrorKind::CannotFindIssueHead(issue.id())))
+        .unwrap_or_abort();
+
+    let merged = match matches.value_of("merge") {
+        Some(merged) => repo.value_to_commit(merged),
+        None => {
+            // we only print the current head
+            println!("{}", head);
+            return;
+        },
+    };
+
+    // The metadata of the merged message is adopted, possibly overridden by
+    // metadata specified on the command line.
+    let mut head_commit = repo.find_commit(head).unwrap_or_abort();
+    let trailers: Vec<_> = merged
+        .trailers()
+        .filter(|trailer| trailer.key.as_ref() != MESSAGE_SUPERSEDES_SPEC.key.as_ref())
+        .chain(repo.parse_trailers(matches
+            .values_of("set-status")
+            .into_iter()
+            .flatten()
+        ))
+        .collect();
+
+    let author = repo.cli_author(matches);
+    repo.check_status_transition(&issue, head, &author, &trailers);
+
+    let message = [head_commit.reply_subject().unwrap_or_default(), String::new()]
+        .to_vec()
+        .into_iter()
+        .chain(trailers.into_iter().map(|t| t.to_string()))
+        .collect_string();
+
+    let committer = repo.signature().unwrap_or_abort();
+    let signer = repo.cli_signer(matches);
+    let new = issue
+        .merge_into_head(&author, &committer, message.trim(), &head_commit, &merged, signer.as_ref().map(|s| s as &dyn Signer))
+        .unwrap_or_abort();
+    println!("{}", new.id());
+}
+
+
+/// import-mbox subcommand implementation
+///
+fn import_mbox_impl(matches: &clap::ArgMatches) {
+    use libgitdit::mbox::{Imported, Importer, parse_mbox};
+    use libgitdit::signature::Signer;
+
+    let repo = util::open_dit_repo();
+    let committer = repo.signature().unwrap_or_abort();
+
+    // read all the mailboxes, defaulting to stdin
+    let mut mails = Vec::new();
+    let files: Vec<&str> = matches.values_of("files").map(Iterator::collect).unwrap_or_default();
+    if files.is_empty() {
+        let mut buffer = Vec::new();
+        io::stdin().read_to_end(&mut buffer).unwrap_or_abort();
+        mails.extend(parse_mbox(&String::from_utf8_lossy(&buffer)));
+    }
+    for file in files {
+        let mut buffer = Vec::new();
+        File::open(file)
+            .and_then(|mut file| file.read_to_end(&mut buffer))
+            .unwrap_or_abort();
+        mails.extend(parse_mbox(&String::from_utf8_lossy(&buffer)));
+    }
+
+    let signer = repo.cli_signer(matches);
+    let imported = Importer::new(&repo)
+        .map(|importer| importer.with_signer(signer.as_ref().map(|s| s as &dyn Signer)))
+        .and_then(|mut importer| importer.import_all(&committer, mails))
+        .unwrap_or_abort();
+    for (mail, result) in imported {
+        let kind = match result {
+            Imported::Issue(_)      => "new",
+            Imported::Reply(_)      => "reply",
+            Imported::Skipped(_)    => "skip",
+        };
+        println!("[dit][{}] {} {}", kind, result.id(), mail.message_id.unwrap_or_default());
+    }
+}
+
+
+/// label subcommand implementation
+///
+fn label_impl(matches: &clap::ArgMatches) {
+    use libgitdit::trailer::Trailer;
+    use libgitdit::trailer::spec::ISSUE_LABEL_SPEC;
+
+    let repo = util::open_dit_repo();
+
+    // NOTE: the issue is a required parameter
+    let issue = repo.cli_issue(matches).unwrap();
+    let spec = repo
+        .trailer_specs()
+        .unwrap_or_abort()
+        .remove("label")
+        .unwrap_or(ISSUE_LABEL_SPEC);
+    let key = spec.key.to_string();
+
+    let changes = match matches.values_of("change") {
+        Some(changes) => changes,
+        None => {
+            // we only list the current labels
+            let mut metadata = repo
+                .issue_head(&issue, &repo.remote_priorization())
+                .map(|head| repo.accumulated_metadata(&issue, head))
+                .unwrap_or_default();
+            metadata
+                .remove(&key)
+                .into_iter()
+                .flat_map(|labels| labels.into_iter())
+                .print_lines()
+                .unwrap_or_abort();
+            return;
+        },
+    };
+
+    // labels without an operation are added
+    let trailers = changes
+        .map(|change| if change.starts_with('+') || change.starts_with('-') {
+            change.to_owned()
+        } else {
+            format!("+{}", change)
+        })
+        .map(|change| spec.parse_value(&change).map(|value| Trailer::new(&key, &value.to_string())))
+        .abort_on_err()
+        .collect();
+    repo.add_status_message(&issue, &repo.cli_author(matches), None, trailers, &[], repo.cli_signer(matches).as_ref());
+}
+
+/// links subcommand implementation
+///
+fn links_impl(matches: &clap::ArgMatches) {
+    let repo = util::open_dit_repo();
+    let id_len = repo.abbreviation_length(matches);
+
+    // NOTE: the issue is a required parameter
+    let issue = repo.cli_issue(matches).unwrap();
+
+    let (references, referrers) = repo.related_issues(&issue);
+    let lines = references
+        .into_iter()
+        .map(|id| ("references", id))
+        .chain(referrers.into_iter().map(|id| ("referenced-by", id)));
+
+    let mut stdout = io::stdout();
+    for (relation, id) in lines {
+        let subject = repo
+            .find_commit(id)
+            .unwrap_or_abort()
+            .summary()
+            .unwrap_or_default()
+            .to_owned();
+        writeln!(stdout, "{} {:.*} {}", relation, id_len, id, subject).unwrap_or_abort();
+    }
+}
+
 /// list subcommand implementation
 ///
 fn list_impl(matches: &clap::ArgMatches) {
@@ -266,24 +996,56 @@ fn list_impl(matches: &clap::ArgMatches) {
     use libgitdit::Issue;
 
     use display::{FormattingToken as FT, MessageFmtToken as MFT, LineFormatter};
+    use display::{OutputFormat, parse_format};
     use filters::MetadataFilter;
+    use sorting::IssueSorter;
 
     let repo = util::open_dit_repo();
     let remote_prios = repo.remote_priorization();
+    let cache = repo.enabled_metadata_cache();
+    let authorization = repo.metadata_authorization();
 
     // construct filter
     let filter = match matches.values_of("filter") {
         Some(values) => {
-            let specs = values.map(str::parse).abort_on_err();
-            MetadataFilter::new(&remote_prios, specs).unwrap_or_abort()
+            let trailer_specs = repo.trailer_specs().unwrap_or_abort();
+            let exprs = values.map(str::parse).abort_on_err();
+            MetadataFilter::new(&remote_prios, &trailer_specs, exprs).unwrap_or_abort()
         },
         None         => MetadataFilter::empty(&remote_prios),
+    }.with_cache(cache.as_ref()).with_authorization(authorization.as_ref());
+    let verifier = if matches.is_present("signed-only") {
+        Some(repo.signature_verifier())
+    } else {
+        None
     };
+    let filter = filter.with_verifier(verifier.as_ref().map(|verifier| (&repo, verifier)));
+    let dependencies = if filter.needs_dependencies() {
+        Some(repo.dependencies(&remote_prios))
+    } else {
+        None
+    };
+    let filter = filter.with_dependencies(dependencies.as_ref());
+
+    // optionally restrict the issues to those fixed on a branch
+    let fixed: Option<Vec<git2::Oid>> = matches.value_of("fixed-on").map(|branch| {
+        let tip = repo.value_to_commit(branch).id();
+        libgitdit::fixes::fixes(&repo, Some(tip))
+            .unwrap_or_abort()
+            .into_iter()
+            .map(|fix| fix.issue)
+            .collect()
+    });
 
     let id_len = repo.abbreviation_length(matches);
+    let format = repo.output_format(matches);
 
-    let formatter = if matches.is_present("long") {
-        tokenvec![
+    let formatter = match format {
+        Some(OutputFormat::Custom(ref format)) => {
+            let trailer_specs = repo.trailer_specs().unwrap_or_abort();
+            parse_format(format, id_len, &trailer_specs).unwrap_or_abort()
+        },
+        _ if matches.is_present("long") => tokenvec![
             MFT::Id(id_len), FT::LineEnd,
             "Author: ", MFT::Author, FT::LineEnd,
             "Date: ", MFT::Date(StrftimeItems::new("%+")), FT::LineEnd,
@@ -291,25 +1053,30 @@ fn list_impl(matches: &clap::ArgMatches) {
             MFT::Subject, FT::LineEnd,
             FT::LineEnd,
             MFT::BodyText,
-            FT::LineEnd]
-    } else {
-        tokenvec![MFT::Id(id_len), " (", MFT::Date(StrftimeItems::new("%c")), ") ", MFT::Subject]
+            FT::LineEnd],
+        _ => tokenvec![MFT::Id(id_len), " (", MFT::Date(StrftimeItems::new("%c")), ") ", MFT::Subject],
     };
 
+    // construct sorter, sorting by creation date in descending order unless
+    // specified otherwise
+    let sort_specs: Vec<&str> = match matches.values_of("sort") {
+        Some(values) => values.collect(),
+        None         => vec!["created:desc"],
+    };
+    let sorter = IssueSorter::new(&remote_prios, &repo.trailer_specs().unwrap_or_abort(), sort_specs)
+        .unwrap_or_abort()
+        .with_cache(cache.as_ref())
+        .with_authorization(authorization.as_ref());
+
     // get initial commits
-    let mut issues : Vec<Issue> = repo
+    let issues : Vec<Issue> = repo
         .issues()
         .unwrap_or_abort()
         .into_iter()
+        .filter(|issue| fixed.as_ref().is_none_or(|fixed| fixed.contains(&issue.id())))
         .filter(|issue| filter.filter(issue))
         .collect();
-
-    // descending order
-    let mut sort_key : Box<FnMut(&Issue) -> git2::Time> = Box::new(|ref issue| issue
-        .initial_message()
-        .unwrap_or_abort()
-        .time());
-    issues.sort_by(|a, b| sort_key(b).cmp(&sort_key(a)));
+    let mut issues = sorter.sort(issues).unwrap_or_abort();
 
     // optionally limit to some number specified by the user
     if let Some(number) = matches.value_of("n") {
@@ -317,6 +1084,24 @@ fn list_impl(matches: &clap::ArgMatches) {
         issues.truncate(str::parse(number).unwrap_or_abort());
     }
 
+    if let Some(OutputFormat::Json(ref format)) = format {
+        use display::issue_json;
+
+        let items = issues
+            .iter()
+            .map(|issue| {
+                let head = repo.issue_head(issue, &remote_prios);
+                let metadata = head
+                    .map(|head| repo.accumulated_metadata(issue, head))
+                    .unwrap_or_default();
+                issue_json(issue, head, metadata)
+            })
+            .abort_on_err()
+            .collect();
+        format.lines(items).print_lines().unwrap_or_abort();
+        return;
+    }
+
     // present the list to the user
     let result = issues
         .into_iter()
@@ -466,9 +1251,10 @@ fn new_impl(matches: &clap::ArgMatches) {
 
     // commit the message
     let tree = repo.empty_tree().unwrap_or_abort();
-    let id = repo
-        .create_issue(&author, &committer, message.trim(), &tree, Vec::new())
-        .unwrap_or_abort();
+    let id = match repo.cli_signer(matches) {
+        Some(signer) => repo.create_signed_issue(&author, &committer, message.trim(), &tree, Vec::new(), &signer),
+        None => repo.create_issue(&author, &committer, message.trim(), &tree, Vec::new()),
+    }.unwrap_or_abort();
     println!("[dit][new] {}", id);
 }
 
@@ -478,33 +1264,46 @@ fn new_impl(matches: &clap::ArgMatches) {
 fn push_impl(matches: &clap::ArgMatches) {
     let repo = util::open_dit_repo();
 
-    // note: "remote" is always present since it is a required parameter
-    let mut remote = repo.find_remote(matches.value_of("remote").unwrap()).unwrap_or_abort();
-
-    // accumulate the refspecs to push
-    let refspecs : Vec<String> = repo
+    let issues: Vec<_> = repo
         .cli_issues(matches)
         .unwrap_or_else(|| repo.issues().unwrap_or_abort())
         .into_iter()
-        .map(|issue| issue.local_refs(IssueRefType::Any))
-        .abort_on_err()
-        .flat_map(|mut refs| {
-            let names: Vec<_> = refs
-                .names()
-                .abort_on_err()
-                .map(String::from)
-                .collect();
-            names
-        })
         .collect();
 
-    // set the options for the push
-    let mut fetch_options = git2::PushOptions::new();
-    fetch_options.remote_callbacks(gitext::callbacks());
+    // note: "remote" is always present since it is a required parameter
+    repo.push_issues(matches.value_of("remote").unwrap(), &issues);
+}
 
-    let refspec_refs : Vec<&str> = refspecs.iter().map(String::as_str).collect();
-    remote.push(refspec_refs.as_ref(), Some(&mut fetch_options))
-          .unwrap_or_abort();
+
+/// reopen subcommand implementation
+///
+fn reopen_impl(matches: &clap::ArgMatches) {
+    use libgitdit::trailer::Trailer;
+    use libgitdit::trailer::spec::ISSUE_STATUS_SPEC;
+
+    let repo = util::open_dit_repo();
+    let spec = repo
+        .trailer_specs()
+        .unwrap_or_abort()
+        .remove("status")
+        .unwrap_or(ISSUE_STATUS_SPEC);
+
+    // Issues are reopened by setting the status to the initial state of the
+    // workflow, unless specified otherwise.
+    let status = match matches.value_of("status") {
+        Some(status) => status.to_owned(),
+        None => repo
+            .workflow()
+            .unwrap_or_abort()
+            .initial()
+            .unwrap_or("open")
+            .to_owned(),
+    };
+    let trailer = spec
+        .parse_value(&status)
+        .map(|value| Trailer::new(spec.key.as_ref(), &value.to_string()))
+        .unwrap_or_abort();
+    repo.change_metadata(matches, vec![trailer]);
 }
 
 
@@ -533,6 +1332,9 @@ fn reply_impl(matches: &clap::ArgMatches) {
     // figure out to what issue we reply
     let issue = repo.issue_with_message(&parent).unwrap_or_abort();
 
+    // changes of the status need to conform to the workflow
+    repo.check_status_transition(&issue, parent.id(), &author, &repo.prepare_trailers(matches));
+
     // get the references specified on the command line
     let references = repo.cli_references(matches);
 
@@ -582,8 +1384,97 @@ fn reply_impl(matches: &clap::ArgMatches) {
     let parent_refs = Some(&parent).into_iter().chain(references.iter());
 
     // finally, create the message
-    issue.add_message(&author, &committer, message.trim(), &tree, parent_refs)
-         .unwrap_or_abort();
+    match repo.cli_signer(matches) {
+        Some(signer) => issue.add_signed_message(&author, &committer, message.trim(), &tree, parent_refs, &signer),
+        None => issue.add_message(&author, &committer, message.trim(), &tree, parent_refs),
+    }.unwrap_or_abort();
+}
+
+/// search subcommand implementation
+///
+fn search_impl(matches: &clap::ArgMatches) {
+    use libgitdit::Issue;
+    use libgitdit::search::{self, Pattern};
+
+    let repo = util::open_dit_repo();
+    let id_len = repo.abbreviation_length(matches);
+
+    let pattern = Pattern::new(
+        matches.value_of("pattern").unwrap(),
+        matches.is_present("ignore-case"),
+    ).unwrap_or_abort();
+
+    let use_index = matches.is_present("index") || repo
+        .config()
+        .unwrap_or_abort()
+        .get_bool("dit.search-index")
+        .unwrap_or(false);
+
+    let issues: Vec<Issue> = repo.issues().unwrap_or_abort().into_iter().collect();
+    let found = if use_index {
+        let mut index = repo.search_index();
+        index.update(issues.iter()).unwrap_or_abort();
+        index.search(&repo, &pattern).unwrap_or_abort()
+    } else {
+        issues
+            .iter()
+            .flat_map(|issue| search::search_issue(issue, &pattern).unwrap_or_abort())
+            .collect()
+    };
+
+    let mut stdout = io::stdout();
+    for found in found {
+        for snippet in found.snippets {
+            writeln!(stdout, "{0:.2$} {1:.2$} {3}", found.issue, found.message.id(), id_len, snippet)
+                .unwrap_or_abort();
+        }
+    }
+}
+
+/// serve subcommand implementation
+///
+fn serve_impl(matches: &clap::ArgMatches) {
+    use error::{ErrorKind as EK, ResultExt};
+
+    let repo = util::open_dit_repo();
+
+    let address = matches.value_of("address").unwrap_or("localhost:8080");
+    let listener = std::net::TcpListener::bind(address)
+        .chain_err(|| EK::WrappedIOError)
+        .unwrap_or_abort();
+    println!("[dit][serve] listening on http://{}/", address);
+    io::stdout().flush().unwrap_or_abort();
+
+    let server = web::Server::new(
+        &repo,
+        repo.remote_priorization(),
+        repo.enabled_metadata_cache(),
+        repo.trailer_specs().unwrap_or_abort(),
+        repo.status_spec().key.into_owned(),
+        repo.closed_status(),
+    ).with_authorization(repo.metadata_authorization());
+    server.run(&listener).unwrap_or_abort();
+}
+
+/// set-type subcommand implementation
+///
+fn set_type_impl(matches: &clap::ArgMatches) {
+    use libgitdit::trailer::Trailer;
+    use libgitdit::trailer::spec::ISSUE_TYPE_SPEC;
+
+    let repo = util::open_dit_repo();
+    let spec = repo
+        .trailer_specs()
+        .unwrap_or_abort()
+        .remove("type")
+        .unwrap_or(ISSUE_TYPE_SPEC);
+
+    // NOTE: the type is a required parameter
+    let trailer = spec
+        .parse_value(matches.value_of("type").unwrap())
+        .map(|value| Trailer::new(spec.key.as_ref(), &value.to_string()))
+        .unwrap_or_abort();
+    repo.change_metadata(matches, vec![trailer]);
 }
 
 /// show subcommand implementation
@@ -593,6 +1484,7 @@ fn show_impl(matches: &clap::ArgMatches) {
 
     use display::{FormattingToken as FT, MessageFmtToken as MFT, LineFormatter};
     use display::{IntoTreeGraph, TreeGraphElem, TreeGraphElemLine};
+    use display::{OutputFormat, parse_format};
     use gitext::ReferrencesExt;
 
     let repo = util::open_dit_repo();
@@ -602,11 +1494,65 @@ fn show_impl(matches: &clap::ArgMatches) {
     // NOTE: the issue is a required parameter
     let issue = repo.cli_issue(matches).unwrap();
 
+    let format = repo.output_format(matches);
+    let verifier = if matches.is_present("verify-gpg") {
+        Some(repo.signature_verifier())
+    } else {
+        None
+    };
+
+    if let Some(OutputFormat::Json(ref format)) = format {
+        use display::{JsonFormat, JsonValue, issue_json, message_json};
+        use libgitdit::signature::verify;
+
+        let to_json = |message: &Commit| match verifier {
+            Some(ref verifier) => {
+                let trust = verify(&repo, message, verifier).unwrap_or_abort();
+                message_json(message).with_member("signature", trust.to_string().into())
+            },
+            None => message_json(message),
+        };
+
+        // messages are presented in chronological order
+        let mut messages: Vec<JsonValue> = if matches.is_present("initial") {
+            vec![to_json(&issue.initial_message().unwrap_or_abort())]
+        } else {
+            issue
+                .messages()
+                .abort_on_err()
+                .map(|message| to_json(&message))
+                .collect()
+        };
+        messages.reverse();
+
+        match *format {
+            JsonFormat::Json => {
+                let head = repo.issue_head(&issue, &prios);
+                let metadata = head
+                    .map(|head| repo.accumulated_metadata(&issue, head))
+                    .unwrap_or_default();
+                let value = issue_json(&issue, head, metadata)
+                    .unwrap_or_abort()
+                    .with_member("messages", JsonValue::Array(messages));
+                println!("{}", value);
+            },
+            JsonFormat::NdJson => messages.print_lines().unwrap_or_abort(),
+        }
+        return;
+    }
+
     // translate commit to lines representing the commit
-    let formatter : Vec<FT<_,_>> = if matches.is_present("msgtree") {
+    let formatter : Vec<FT<_,_>> = if let Some(OutputFormat::Custom(ref format)) = format {
+        let trailer_specs = repo.trailer_specs().unwrap_or_abort();
+        parse_format(format, id_len, &trailer_specs).unwrap_or_abort()
+    } else if matches.is_present("msgtree") {
         // With the "tree" option, we only display subjects in a short
         // format
-        tokenvec![MFT::Id(id_len), " ", MFT::Author, " ", MFT::Subject]
+        let mut tokens = tokenvec![MFT::Id(id_len), " ", MFT::Author, " ", MFT::Subject];
+        if let Some(ref verifier) = verifier {
+            tokens.extend(tokenvec![" [", MFT::Signature(&repo, verifier), "]"]);
+        }
+        tokens
     } else {
         let head = issue
             .heads()
@@ -616,16 +1562,22 @@ fn show_impl(matches: &clap::ArgMatches) {
             .target()
             .unwrap(); // TODO: abort gracefully
 
-        tokenvec![
+        let mut tokens = tokenvec![
             MFT::Id(id_len), MFT::IfId(head, tokenvec![" (head)"]), FT::LineEnd,
             "Author: ", MFT::Author, FT::LineEnd,
-            "Date: ", MFT::Date(StrftimeItems::new("%+")), FT::LineEnd,
+            "Date: ", MFT::Date(StrftimeItems::new("%+")), FT::LineEnd];
+        if let Some(ref verifier) = verifier {
+            tokens.extend(tokenvec!["Signature: ", MFT::Signature(&repo, verifier), FT::LineEnd]);
+        }
+        tokens.extend(tokenvec![
             FT::LineEnd,
             MFT::Subject, FT::LineEnd,
             FT::LineEnd,
             MFT::Body,
+            MFT::DiffStat(&repo),
             FT::LineEnd,
-            FT::LineEnd]
+            FT::LineEnd]);
+        tokens
     };
 
     // first, get us an iterator over all the commits
@@ -635,12 +1587,40 @@ fn show_impl(matches: &clap::ArgMatches) {
                 TreeGraphElemLine::empty(),
                 issue.initial_message().unwrap_or_abort()
             )]
-        } else {
+        } else if matches.is_present("history") {
             issue
                 .messages()
                 .abort_on_err()
                 .into_tree_graph()
                 .collect()
+        } else {
+            use libgitdit::revision::Revisions;
+            use std::collections::HashMap;
+
+            let messages: Vec<Commit> = issue.messages().abort_on_err().collect();
+            let revisions = Revisions::new(&messages);
+
+            // Each message is displayed only once, in its latest revision. We
+            // display it at the position of the last of its revisions, which
+            // is after the replies to any of the revisions.
+            let mut positions = HashMap::new();
+            for (pos, message) in messages.iter().enumerate() {
+                positions.insert(revisions.original(message.id()), pos);
+            }
+            let aliases = messages
+                .iter()
+                .map(|message| (message.id(), revisions.original(message.id())))
+                .collect();
+
+            messages
+                .iter()
+                .enumerate()
+                .filter(|&(pos, message)| positions[&revisions.original(message.id())] == pos)
+                .map(|(_, message)| repo.find_commit(revisions.latest(message.id())))
+                .abort_on_err()
+                .into_tree_graph()
+                .with_aliases(aliases)
+                .collect()
         };
 
     // Decide on the order in which the messages will be printed.
@@ -652,6 +1632,38 @@ fn show_impl(matches: &clap::ArgMatches) {
         }
     };
 
+    // In the default format, the issues related to the issue and the commits
+    // fixing it are listed after the messages. Since scanning branches for
+    // fixes may be expensive, we only scan the branches configured explicitly.
+    let related: Vec<String> = if format.is_none() && !matches.is_present("msgtree") {
+        let (references, referrers) = repo.related_issues(&issue);
+        let fix_tips = repo.configured_fix_tips();
+        let fixing: Vec<git2::Oid> = libgitdit::fixes::fixes(&repo, fix_tips)
+            .unwrap_or_abort()
+            .into_iter()
+            .filter(|fix| fix.issue == issue.id())
+            .map(|fix| fix.commit)
+            .collect();
+        let subject = |id| repo
+            .find_commit(id)
+            .unwrap_or_abort()
+            .summary()
+            .unwrap_or_default()
+            .to_owned();
+        references
+            .into_iter()
+            .map(|id| format!("References: {:.*} {}", id_len, id, subject(id)))
+            .chain(referrers
+                .into_iter()
+                .map(|id| format!("Referenced by: {:.*} {}", id_len, id, subject(id))))
+            .chain(fixing
+                .into_iter()
+                .map(|id| format!("Fixed by: {:.*} {}", id_len, id, subject(id))))
+            .collect()
+    } else {
+        Vec::new()
+    };
+
     // Transform the simple graph element line into an iterator over lines to
     // print via multiple steps.
     let result = commits
@@ -671,30 +1683,84 @@ fn show_impl(matches: &clap::ArgMatches) {
         )
         // combine each line of graph elements and message
         .map(|line| format!("{} {}", line.0, line.1))
+        .chain(related)
         .pipe_lines(repo.pager())
         .unwrap_or_abort();
 
     std::process::exit(result);
 }
 
+/// submit-patches subcommand implementation
+///
+fn submit_patches_impl(matches: &clap::ArgMatches) {
+    use error::{ErrorKind as EK, LoggableError};
+    use libgitdit::error::{Error, ErrorKind};
+    use libgitdit::signature::Signer;
+
+    let repo = util::open_dit_repo();
+    let prios = repo.remote_priorization();
+    let committer = repo.signature().unwrap_or_abort();
+
+    // NOTE: the issue is a required parameter
+    let issue = repo.cli_issue(matches).unwrap();
+
+    // the patches reply to the issue's head unless specified otherwise
+    let parent = match matches.value_of("reply-to") {
+        Some(message) => repo.value_to_commit(message),
+        None => repo
+            .issue_head(&issue, &prios)
+            .map(|head| repo.find_commit(head).unwrap_or_abort())
+            .unwrap_or_else(|| issue.initial_message().unwrap_or_abort()),
+    };
+    if !issue.contains_message(parent.id()).unwrap_or_abort() {
+        Error::from_kind(ErrorKind::MessageNotInIssue(parent.id(), issue.id())).log();
+        std::process::exit(1);
+    }
+
+    // the unwrap is safe since `range` is a required value
+    let range = matches.value_of("range").unwrap();
+    let commits: Vec<Commit> = if range.contains("..") {
+        let mut revwalk = repo.revwalk().unwrap_or_abort();
+        revwalk.push_range(range).unwrap_or_abort();
+        revwalk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::REVERSE).unwrap_or_abort();
+        revwalk
+            .map(|id| id.and_then(|id| repo.find_commit(id)))
+            .abort_on_err()
+            .collect()
+    } else {
+        vec![repo.value_to_commit(range)]
+    };
+    if commits.is_empty() {
+        error::Error::from_kind(EK::EmptyRange(range.to_owned())).log();
+        std::process::exit(1);
+    }
+
+    let signer = repo.cli_signer(matches);
+    let messages = issue
+        .add_patches(&committer, &parent, &commits, signer.as_ref().map(|s| s as &dyn Signer))
+        .unwrap_or_abort();
+    for message in messages {
+        println!("[dit][patch] {}", message.id());
+    }
+}
+
+
 /// tag subcommand implementation
 ///
 fn tag_impl(matches: &clap::ArgMatches) {
     use libgitdit::trailer::Trailer;
-    use std::str::FromStr;
 
     use gitext::ReferrencesExt;
 
     let repo = util::open_dit_repo();
     let author = repo.cli_author(matches);
-    let committer = repo.signature().unwrap_or_abort();
     let prios = repo.remote_priorization();
 
     // get the head for the issue to tag
 
     // NOTE: the issue is a required parameter
     let issue = repo.cli_issue(matches).unwrap();
-    let mut head_commit = issue
+    let head_commit = issue
         .heads()
         .abort_on_err()
         .select_ref(&prios)
@@ -707,6 +1773,16 @@ fn tag_impl(matches: &clap::ArgMatches) {
 
     if matches.is_present("list") {
         // we only list the metadata
+        if let Some(format) = util::json_format_from_args(matches) {
+            let items = repo
+                .issue_messages_iter(head_commit)
+                .abort_on_err()
+                .flat_map(|c| c.trailers())
+                .map(display::JsonValue::from)
+                .collect();
+            format.lines(items).print_lines().unwrap_or_abort();
+            return;
+        }
         repo.issue_messages_iter(head_commit)
             .abort_on_err()
             .flat_map(|c| c.trailers())
@@ -719,31 +1795,83 @@ fn tag_impl(matches: &clap::ArgMatches) {
 
     // get references and trailers for the new commit
     let references = repo.cli_references(matches);
-    let trailers : Vec<Trailer> = matches.values_of("set-status")
-                                         .into_iter()
-                                         .flat_map(|values| values)
-                                         .map(Trailer::from_str)
-                                         .abort_on_err()
-                                         .collect();
+    let trailers : Vec<Trailer> = repo.parse_trailers(matches
+        .values_of("set-status")
+        .into_iter()
+        .flat_map(|values| values));
     if references.is_empty() && trailers.is_empty() {
         warn!("No commit was created because no reference or tags were supplied.");
         return;
     }
 
-    // construct the message
-    let message = [head_commit.reply_subject().unwrap_or_default(), String::new()]
-        .to_vec()
-        .into_iter()
-        .chain(trailers.into_iter().map(|t| t.to_string()))
-        .collect_string();
-    let tree = repo.empty_tree().unwrap_or_abort();
-    let parent_refs : Vec<&Commit> = Some(&head_commit).into_iter().chain(references.iter()).collect();
-    let new = repo
-        .commit(None, &author, &committer, message.trim(), &tree, &parent_refs)
+    repo.add_status_message(&issue, &author, None, trailers, &references, repo.cli_signer(matches).as_ref());
+}
+
+/// unassign subcommand implementation
+///
+fn unassign_impl(matches: &clap::ArgMatches) {
+    use libgitdit::trailer::Trailer;
+
+    let repo = util::open_dit_repo();
+    let spec = repo.assignee_spec();
+
+    // An assignee trailer without a value removes the assignee.
+    let trailer = spec
+        .parse_value("")
+        .map(|value| Trailer::new(spec.key.as_ref(), &value.to_string()))
         .unwrap_or_abort();
+    repo.change_metadata(matches, vec![trailer]);
+}
+
+/// workflow subcommand implementation
+///
+fn workflow_impl(matches: &clap::ArgMatches) {
+    match matches.subcommand() {
+        ("show", Some(sub_matches)) => workflow_show(sub_matches),
+        _ => {
+            writeln!(io::stderr(), "{}", matches.usage()).ok();
+            std::process::exit(1);
+        },
+    }
+}
+
+/// workflow show subcommand implementation
+///
+fn workflow_show(matches: &clap::ArgMatches) {
+    let repo = util::open_dit_repo();
+    let workflow = repo.workflow().unwrap_or_abort();
+    if !workflow.is_enabled() {
+        warn!("No workflow is declared.");
+        return;
+    }
+
+    if matches.is_present("dot") {
+        print!("{}", workflow.to_dot());
+        return;
+    }
 
-    // update the head reference
-    issue.update_head(new, true).unwrap_or_abort();
+    let states = workflow
+        .states()
+        .iter()
+        .map(|state| {
+            let mut line = state.clone();
+            if workflow.initial() == Some(state.as_str()) {
+                line.push_str(" (initial)");
+            }
+            if workflow.is_terminal(state) {
+                line.push_str(" (terminal)");
+            }
+            line
+        });
+    let transitions = workflow
+        .transitions()
+        .iter()
+        .map(ToString::to_string);
+    states
+        .chain(Some(String::new()))
+        .chain(transitions)
+        .print_lines()
+        .unwrap_or_abort();
 }
 
 
@@ -789,16 +1917,36 @@ fn main() {
         ("find-tree-init-hash",         Some(sub_matches)) => find_tree_init_hash(sub_matches),
         ("get-issue-metadata",          Some(sub_matches)) => get_issue_metadata(sub_matches),
         ("get-issue-tree-init-hashes",  Some(sub_matches)) => get_issue_tree_init_hashes(sub_matches),
+        ("hook",                        Some(sub_matches)) => hook(sub_matches),
         // Porcelain subcommands
-        ("fetch",   Some(sub_matches)) => fetch_impl(sub_matches),
-        ("gc",      Some(sub_matches)) => gc_impl(sub_matches),
-        ("list",    Some(sub_matches)) => list_impl(sub_matches),
-        ("mirror",  Some(sub_matches)) => mirror_impl(sub_matches),
-        ("new",     Some(sub_matches)) => new_impl(sub_matches),
-        ("push",    Some(sub_matches)) => push_impl(sub_matches),
-        ("reply",   Some(sub_matches)) => reply_impl(sub_matches),
-        ("show",    Some(sub_matches)) => show_impl(sub_matches),
-        ("tag",     Some(sub_matches)) => tag_impl(sub_matches),
+        ("amend",          Some(sub_matches)) => amend_impl(sub_matches),
+        ("apply",          Some(sub_matches)) => apply_impl(sub_matches),
+        ("assign",         Some(sub_matches)) => assign_impl(sub_matches),
+        ("bulk",           Some(sub_matches)) => bulk_impl(sub_matches),
+        ("close",          Some(sub_matches)) => close_impl(sub_matches),
+        ("deps",           Some(sub_matches)) => deps_impl(sub_matches),
+        ("export-mbox",    Some(sub_matches)) => export_mbox_impl(sub_matches),
+        ("fetch",          Some(sub_matches)) => fetch_impl(sub_matches),
+        ("fixes",          Some(sub_matches)) => fixes_impl(sub_matches),
+        ("gc",             Some(sub_matches)) => gc_impl(sub_matches),
+        ("head",           Some(sub_matches)) => head_impl(sub_matches),
+        ("import-mbox",    Some(sub_matches)) => import_mbox_impl(sub_matches),
+        ("label",          Some(sub_matches)) => label_impl(sub_matches),
+        ("links",          Some(sub_matches)) => links_impl(sub_matches),
+        ("list",           Some(sub_matches)) => list_impl(sub_matches),
+        ("mirror",         Some(sub_matches)) => mirror_impl(sub_matches),
+        ("new",            Some(sub_matches)) => new_impl(sub_matches),
+        ("push",           Some(sub_matches)) => push_impl(sub_matches),
+        ("reopen",         Some(sub_matches)) => reopen_impl(sub_matches),
+        ("reply",          Some(sub_matches)) => reply_impl(sub_matches),
+        ("search",         Some(sub_matches)) => search_impl(sub_matches),
+        ("serve",          Some(sub_matches)) => serve_impl(sub_matches),
+        ("set-type",       Some(sub_matches)) => set_type_impl(sub_matches),
+        ("show",           Some(sub_matches)) => show_impl(sub_matches),
+        ("submit-patches", Some(sub_matches)) => submit_patches_impl(sub_matches),
+        ("tag",            Some(sub_matches)) => tag_impl(sub_matches),
+        ("unassign",       Some(sub_matches)) => unassign_impl(sub_matches),
+        ("workflow",       Some(sub_matches)) => workflow_impl(sub_matches),
         // Unknown subcommands
         ("", _) => {
             writeln!(io::stderr(), "{}", matches.usage()).ok();
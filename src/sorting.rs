@@ -0,0 +1,238 @@
+//   git-dit - the distributed issue tracker for git
+//   Copyright (C) 2017 Matthias Beyer <mail@beyermatthias.de>
+//   Copyright (C) 2017 Julian Ganz <neither@nut.email>
+//
+//   This program is free software; you can redistribute it and/or modify
+//   it under the terms of the GNU General Public License version 2 as
+//   published by the Free Software Foundation.
+//
+
+//! Issue sorting
+//!
+//! This module provides sorting of issues by one or more keys. A sort spec
+//! consists of a key, optionally followed by a colon and a direction, e.g.
+//! `activity:desc`. Keys may either refer to properties of an issue, such as
+//! its creation date or the number of its messages, or to pieces of metadata
+//! declared in the configuration.
+//!
+
+use git2::{ObjectType, Reference};
+use libgitdit::Issue;
+use libgitdit::cache::MetadataCache;
+use libgitdit::iter::MessagesExt;
+use libgitdit::trailer::TrailerValue;
+use libgitdit::trailer::spec::TrailerSpec;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use error::*;
+use error::ErrorKind as EK;
+use filters::NonTrailer;
+use gitext::{RemotePriorization, ReferrencesExt};
+use util::MetadataAuthorization;
+
+
+/// Key issues may be sorted by
+///
+enum SortKey {
+    /// Metadata not provided by trailers, e.g. the creation date
+    NonTrailer(NonTrailer),
+    /// The reporter, formatted as `Name <email>`
+    Reporter,
+    /// The number of messages
+    Messages,
+    /// A piece of metadata declared in the configuration
+    Trailer(TrailerSpec),
+}
+
+impl SortKey {
+    /// Retrieve the value of the key for an issue
+    ///
+    /// Issues for which there is no value, e.g. because a piece of metadata is
+    /// not set, yield `None`.
+    ///
+    fn for_issue(&self, issue: &Issue, sorter: &IssueSorter) -> Result<Option<TrailerValue>> {
+        match *self {
+            SortKey::NonTrailer(ref nontrailer) => nontrailer.for_issue(issue).map(Some),
+            SortKey::Reporter => {
+                let author = issue.initial_message()?.author().to_string();
+                Ok(Some(TrailerValue::String(author)))
+            },
+            SortKey::Messages => {
+                let count = issue.messages()?.count();
+                Ok(Some(TrailerValue::Int(count as i64)))
+            },
+            SortKey::Trailer(ref spec) => {
+                let head = match sorter.head(issue)? {
+                    Some(head) => head,
+                    None => return Ok(None),
+                };
+                let specs = vec![spec];
+                let mut accumulated = match (sorter.authorization, sorter.cache) {
+                    (Some((authorization, assignee_key)), _) => issue
+                        .messages_from(head)?
+                        .collect::<::std::result::Result<Vec<_>, _>>()?
+                        .into_iter()
+                        .accumulate_authorized_trailers(specs, authorization, assignee_key)?,
+                    (None, Some(cache)) => cache.accumulate(issue, head, specs)?,
+                    (None, None) => issue
+                        .messages_from(head)?
+                        .collect::<::std::result::Result<Vec<_>, _>>()?
+                        .into_iter()
+                        .accumulate_trailers(specs),
+                };
+                Ok(accumulated
+                    .remove(spec.key.as_ref())
+                    .and_then(|values| values.into_iter().next())
+                    .map(|value| spec.value_type.convert(value)))
+            },
+        }
+    }
+}
+
+
+/// Direction in which issues are sorted
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    Ascending,
+    Descending,
+}
+
+impl FromStr for Direction {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "asc"   => Ok(Direction::Ascending),
+            "desc"  => Ok(Direction::Descending),
+            _       => Err(Error::from_kind(EK::MalformedSortSpec(s.to_owned()))),
+        }
+    }
+}
+
+
+/// Sorter for issues
+///
+/// The sorter sorts issues by a sequence of keys. Issues with equal values for
+/// a key are sorted by the next one. Issues without a value for a key are
+/// always sorted after those having one.
+///
+pub struct IssueSorter<'a> {
+    prios: &'a RemotePriorization,
+    cache: Option<&'a MetadataCache>,
+    authorization: Option<&'a MetadataAuthorization<'a>>,
+    keys: Vec<(SortKey, Direction)>,
+}
+
+impl<'a> IssueSorter<'a> {
+    /// Create a new sorter from sort specs
+    ///
+    /// Keys which don't refer to properties of issues are resolved to pieces
+    /// of metadata using the trailer specifications supplied.
+    ///
+    pub fn new<'s, I>(prios: &'a RemotePriorization, trailer_specs: &HashMap<String, TrailerSpec>, specs: I) -> Result<Self>
+        where I: IntoIterator<Item = &'s str>
+    {
+        let mut keys = Vec::new();
+        for spec in specs {
+            let mut parts = spec.splitn(2, ':');
+            let name = parts.next().unwrap_or_default();
+            let direction = match parts.next() {
+                Some(direction) => direction
+                    .parse()
+                    .chain_err(|| EK::MalformedSortSpec(spec.to_owned()))?,
+                None => Direction::Ascending,
+            };
+
+            let key = match name {
+                "reporter" => SortKey::Reporter,
+                "messages" => SortKey::Messages,
+                _ => match NonTrailer::from_key(name) {
+                    Some(nontrailer) => SortKey::NonTrailer(nontrailer),
+                    None => match trailer_specs.get(name) {
+                        Some(trailer_spec) => SortKey::Trailer(trailer_spec.clone()),
+                        None => return Err(Error::from_kind(EK::UnknownMetadataKey(name.to_owned()))),
+                    },
+                },
+            };
+            keys.push((key, direction));
+        }
+        Ok(IssueSorter { prios, cache: None, authorization: None, keys })
+    }
+
+    /// Use a metadata cache for retrieving trailers
+    ///
+    pub fn with_cache(mut self, cache: Option<&'a MetadataCache>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Only consider trailers from messages authorized by an authorization
+    ///
+    /// The authorization is supplied along with the key of the assignee
+    /// trailers. The cache is not used for retrieving trailers in this case.
+    ///
+    pub fn with_authorization(mut self, authorization: Option<&'a MetadataAuthorization<'a>>) -> Self {
+        self.authorization = authorization;
+        self
+    }
+
+    /// Sort issues
+    ///
+    /// The values of the keys are retrieved once for each issue.
+    ///
+    pub fn sort<'r>(&self, issues: Vec<Issue<'r>>) -> Result<Vec<Issue<'r>>> {
+        let mut keyed = Vec::with_capacity(issues.len());
+        for issue in issues {
+            let values = self
+                .keys
+                .iter()
+                .map(|(key, _)| key.for_issue(&issue, self))
+                .collect::<Result<Vec<_>>>()?;
+            keyed.push((values, issue));
+        }
+
+        keyed.sort_by(|(a, _), (b, _)| self
+            .keys
+            .iter()
+            .zip(a.iter().zip(b.iter()))
+            .map(|((_, direction), (a, b))| compare(a, b, *direction))
+            .find(|ordering| *ordering != Ordering::Equal)
+            .unwrap_or(Ordering::Equal)
+        );
+        Ok(keyed.into_iter().map(|(_, issue)| issue).collect())
+    }
+
+    /// Get the head of an issue
+    ///
+    fn head(&self, issue: &Issue) -> Result<Option<::git2::Oid>> {
+        let heads: Vec<Reference> = issue.heads()?.collect::<::std::result::Result<_, _>>()?;
+        heads
+            .select_ref(self.prios)
+            .map(|head| head.peel(ObjectType::Commit).map(|commit| commit.id()))
+            .transpose()
+            .map_err(Error::from)
+    }
+}
+
+
+/// Compare two values in a given direction
+///
+/// Integer values and dates are compared numerically and other values
+/// lexicographically.
+/// Missing values are considered greater than any value, regardless of the
+/// direction.
+///
+fn compare(a: &Option<TrailerValue>, b: &Option<TrailerValue>, direction: Direction) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => match direction {
+            Direction::Ascending => a.cmp(b),
+            Direction::Descending => b.cmp(a),
+        },
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
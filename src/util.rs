@@ -8,22 +8,32 @@
 //
 
 use clap::{ArgMatches, Values};
-use git2::{self, Commit, Repository};
+use git2::{self, Commit, Oid, Repository};
 use regex::{Regex, Match};
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io;
 use std::path::PathBuf;
 use std::process::Child;
 use std::str::FromStr;
 
+use libgitdit::cache::MetadataCache;
+use libgitdit::dependency::DependencyGraph;
+use libgitdit::error::{Error as LibError, ErrorKind as LibEK};
+use libgitdit::issue::IssueRefType;
 use libgitdit::message::LineIteratorExt;
 use libgitdit::repository::UniqueIssues;
+use libgitdit::signature::{Program, VerifiedPeople, create_signed_commit};
 use libgitdit::trailer::Trailer;
-use libgitdit::{Issue, RepositoryExt};
+use libgitdit::trailer::accumulation::{AuthorizedPeople, ValueAccumulator};
+use libgitdit::trailer::spec::TrailerSpec;
+use libgitdit::{Issue, Message, RepositoryExt};
 
+use display::{JsonFormat, OutputFormat};
 use error::*;
 use error::ErrorKind as EK;
-use gitext::RemotePriorization;
+use filters::Dependencies;
+use gitext::{RemotePriorization, ReferrencesExt, callbacks};
 use system::{Abortable, IteratorExt, programs};
 
 /// Open the DIT repo
@@ -37,12 +47,21 @@ pub fn open_dit_repo() -> Repository {
 }
 
 
+/// Authorization for accumulating metadata
+///
+/// The authorization is accompanied by the key of the assignee trailers.
+///
+pub type MetadataAuthorization<'r> = (VerifiedPeople<'r, Program>, String);
+
+
 /// Utility trait for some repository-specific functionality
 ///
 pub trait RepositoryUtil<'r> {
     /// Get a commit from a rev
     ///
-    /// This function returns a commit for a rev-string.
+    /// This function returns a commit for a rev-string. In addition to the
+    /// revisions understood by git, a rev-string may refer to the head of an
+    /// issue via `<issue>@head`, where the issue id may be abbreviated.
     ///
     fn value_to_commit(&'r self, rev: &str) -> Commit<'r>;
 
@@ -83,10 +102,27 @@ pub trait RepositoryUtil<'r> {
     ///
     fn get_commit_msg(&self, path: PathBuf) -> Vec<String>;
 
+    /// Get a comment specified on the command line
+    ///
+    /// The comment is either constructed from the paragraphs passed via the
+    /// `"message"` field or, if the `"edit"` flag is present, written by the
+    /// user in an editor. Returns `None` if no comment was specified or if
+    /// the comment written is empty.
+    ///
+    fn cli_comment(&self, matches: &ArgMatches) -> Option<String>;
+
     /// Retrieve metadata from command line arguments
     ///
     fn prepare_trailers(&self, matches: &ArgMatches) -> Vec<Trailer>;
 
+    /// Parse trailers specified on the command line
+    ///
+    /// If the key of a trailer is the name of a piece of metadata declared in
+    /// the configuration, it is replaced by the key of that piece of metadata.
+    ///
+    fn parse_trailers<'a, I>(&self, values: I) -> Vec<Trailer>
+        where I: IntoIterator<Item = &'a str>;
+
     /// Retrieve the author to use for messages
     ///
     fn cli_author(&self, matches: &ArgMatches) -> git2::Signature;
@@ -95,9 +131,174 @@ pub trait RepositoryUtil<'r> {
     ///
     fn abbreviation_length(&self, matches: &ArgMatches) -> usize;
 
+    /// Get the signer to use for messages
+    ///
+    /// Messages are signed if requested via the `"gpgsign"` flag or the
+    /// `commit.gpgSign` configuration option. Returns `None` if messages are
+    /// not to be signed.
+    ///
+    fn cli_signer(&self, matches: &ArgMatches) -> Option<Program>;
+
+    /// Get the verifier for message signatures
+    ///
+    fn signature_verifier(&self) -> Program;
+
+    /// Get the output format specified on the command line
+    ///
+    /// The format specified via the `"format"` field may either be the name of
+    /// a JSON format, the name of a format string configured via
+    /// `dit.format.<name>` or a format string. Returns `None` if no output
+    /// format was specified.
+    ///
+    fn output_format(&self, matches: &ArgMatches) -> Option<OutputFormat>;
+
     /// Get remote priorization from the config
     fn remote_priorization(&self) -> RemotePriorization;
 
+    /// Get the metadata cache, if enabled
+    ///
+    /// The cache is enabled unless `dit.cache` is set to `false`.
+    ///
+    fn enabled_metadata_cache(&self) -> Option<MetadataCache>;
+
+    /// Get the authorization for accumulating metadata, if enabled
+    ///
+    /// If `dit.trusted-metadata` is set to `true`, only trailers from messages
+    /// carrying a good signature by one of the maintainers listed via
+    /// `dit.maintainer`, the reporter or the current assignee of an issue are
+    /// honoured. Signers are identified via `Trust::identity()`. The
+    /// authorization is returned along with the key of the assignee trailers.
+    ///
+    fn metadata_authorization<'a>(&'a self) -> Option<MetadataAuthorization<'a>>;
+
+    /// Get the head of an issue
+    ///
+    /// The head is selected from the issue's head references according to
+    /// the remote priorization supplied.
+    ///
+    fn issue_head(&self, issue: &Issue, prios: &RemotePriorization) -> Option<Oid>;
+
+    /// Accumulate the metadata declared in the configuration for an issue
+    ///
+    /// The metadata is accumulated from the head supplied, using the metadata
+    /// cache if it is enabled. If trusted metadata is enabled, only trailers
+    /// of authorized messages are honoured and the cache is not used.
+    ///
+    fn accumulated_metadata(&self, issue: &Issue, head: Oid) -> HashMap<String, ValueAccumulator>;
+
+    /// Accumulate the metadata specified for an issue
+    ///
+    /// Like `accumulated_metadata()`, but only the trailers specified are
+    /// accumulated.
+    ///
+    fn accumulated_trailers(&self, issue: &Issue, head: Oid, specs: &[&TrailerSpec]) -> HashMap<String, ValueAccumulator>;
+
+    /// Get the issues related to an issue
+    ///
+    /// Returns the ids of the issues referred to by the issue supplied and the
+    /// ids of the issues referring to it, in this order. Each issue is listed
+    /// only once.
+    ///
+    fn related_issues(&self, issue: &Issue) -> (Vec<Oid>, Vec<Oid>);
+
+    /// Get the dependencies between all issues
+    ///
+    /// Dependencies are taken from the metadata accumulated from the heads
+    /// selected according to the remote priorization supplied. Issues whose
+    /// status is one of the values of `dit.closed-status`, or "closed" if it
+    /// is not set, are considered closed.
+    ///
+    fn dependencies(&self, prios: &RemotePriorization) -> Dependencies;
+
+    /// Get the status values marking an issue as closed
+    ///
+    /// The values are taken from `dit.closed-status`. If the option is not
+    /// set, the terminal states of the workflow are used or, if there are
+    /// none, "closed".
+    ///
+    fn closed_status(&self) -> Vec<String>;
+
+    /// Check a change of an issue's status against the workflow
+    ///
+    /// The status is changed by the latest status trailer among the trailers
+    /// supplied, in a message replying to the message `head` and authored by
+    /// the author supplied. The current status and assignee are taken from
+    /// the metadata accumulated via `accumulated_trailers()`. Aborts if the
+    /// change is not legal or not permitted by the repository's policy.
+    /// Trailers not changing the status are not checked.
+    ///
+    fn check_status_transition(&self, issue: &Issue, head: Oid, author: &git2::Signature, trailers: &[Trailer]);
+
+    /// Check the changes of the status of multiple issues against the workflow
+    ///
+    /// The status of each issue is changed by the trailers supplied in a
+    /// message replying to the head of the issue, selected according to the
+    /// remote priorization. Aborts if any of the changes is not legal or not
+    /// permitted.
+    ///
+    fn check_status_transitions(&self, issues: &[Issue], author: &git2::Signature, trailers: &[Trailer]);
+
+    /// Get the spec for the status of issues
+    ///
+    /// The spec may be declared as piece of metadata named "status". If it is
+    /// not declared, the default status spec is used.
+    ///
+    fn status_spec(&self) -> TrailerSpec;
+
+    /// Get the spec for the assignee of issues
+    ///
+    /// The spec may be declared as piece of metadata named "assignee". If it
+    /// is not declared, `Dit-assignee` trailers are used, the latest of which
+    /// determines the assignee.
+    ///
+    fn assignee_spec(&self) -> TrailerSpec;
+
+    /// Get the tips of the branches to scan for fixes
+    ///
+    /// If no branches are supplied, the branches are taken from
+    /// `dit.fix-branch`. If the option is not set, all local branches are
+    /// scanned.
+    ///
+    fn fix_tips(&self, branches: Option<Values>) -> Vec<Oid>;
+
+    /// Get the tips of the branches configured via `dit.fix-branch`
+    ///
+    /// If the option is not set, no tips are returned.
+    ///
+    fn configured_fix_tips(&self) -> Vec<Oid>;
+
+    /// Add a status message to an issue
+    ///
+    /// The message replies to the head of the issue, selected according to
+    /// the remote priorization, and becomes the new head. It consists of a
+    /// reply subject, an optional body and the trailers supplied. The commits
+    /// supplied are referenced via additional parents. If a signer is
+    /// supplied, the message is signed. Returns the id of the new message.
+    ///
+    fn add_status_message(&'r self,
+                          issue: &Issue<'r>,
+                          author: &git2::Signature,
+                          body: Option<&str>,
+                          trailers: Vec<Trailer>,
+                          references: &[Commit<'r>],
+                          signer: Option<&Program>) -> Oid;
+
+    /// Change the metadata of the issues specified on the command line
+    ///
+    /// A status message holding the trailers supplied and the comment
+    /// specified on the command line, if any, is added to each of the issues
+    /// specified via the `"issue"` field. The changes of all issues are
+    /// checked against the workflow before any message is created.
+    ///
+    fn change_metadata(&'r self, matches: &ArgMatches, trailers: Vec<Trailer>);
+
+    /// Push the references of issues to a remote
+    ///
+    /// All local references of the issues supplied are pushed to the remote
+    /// with the name supplied.
+    ///
+    fn push_issues(&self, remote: &str, issues: &[Issue]);
+
     /// Get a pager
     ///
     /// Get a pager suitable for paging output
@@ -107,7 +308,22 @@ pub trait RepositoryUtil<'r> {
 
 impl<'r> RepositoryUtil<'r> for Repository {
     fn value_to_commit(&'r self, rev: &str) -> Commit<'r> {
-        self.revparse_single(rev)
+        // A revision of the form `<issue>@head` refers to the head of an
+        // issue. It may be followed by further suffixes understood by git,
+        // e.g. `<issue>@head~1`.
+        let rev = match rev.find("@head") {
+            Some(pos) => {
+                let issue = value_to_issue(self, &rev[..pos]);
+                let head = self
+                    .issue_head(&issue, &self.remote_priorization())
+                    .ok_or_else(|| Error::from(LibError::from_kind(LibEK::CannotFindIssueHead(issue.id()))))
+                    .unwrap_or_abort();
+                format!("{}{}", head, &rev[pos + "@head".len()..])
+            },
+            None => rev.to_owned(),
+        };
+
+        self.revparse_single(&rev)
             .and_then(|oid| self.find_commit(oid.id()))
             .unwrap_or_abort()
     }
@@ -144,26 +360,8 @@ impl<'r> RepositoryUtil<'r> for Repository {
     }
 
     fn get_commit_msg(&self, path: PathBuf) -> Vec<String> {
-        use system::programs::run_editor;
-
-        // let the user write the message
-        if !run_editor(self.config().unwrap_or_abort(), &path)
-            .unwrap_or_abort()
-            .wait()
-            .unwrap_or_abort()
-            .success()
-        {
-            Error::from_kind(EK::ChildError).log();
-            ::std::process::exit(1);
-        }
-
-        // read the message back, check for validity
-        use io::BufRead;
-        let lines : Vec<String> = io::BufReader::new(File::open(path).unwrap_or_abort())
-            .lines()
-            .abort_on_err()
-            .stripped()
-            .collect();
+        // let the user write the message, check for validity
+        let lines = edit_lines(self, path);
 
         lines
             .iter()
@@ -173,6 +371,35 @@ impl<'r> RepositoryUtil<'r> for Repository {
         lines
     }
 
+    fn cli_comment(&self, matches: &ArgMatches) -> Option<String> {
+        let lines = if let Some(paragraphs) = message_from_args(matches) {
+            paragraphs
+        } else if matches.is_present("edit") {
+            use std::io::Write;
+
+            let path = self.commitmsg_edit_path(matches);
+            { // write
+                let mut file = File::create(path.as_path()).unwrap_or_abort();
+                write!(&mut file, "\n\
+                    # Please enter a comment. Lines starting with '#' will be ignored.\n\
+                    # An empty comment is omitted.\n").unwrap_or_abort();
+                file.flush().unwrap_or_abort();
+            }
+
+            edit_lines(self, path)
+        } else {
+            return None;
+        };
+
+        let comment = lines.into_iter().collect_string();
+        let comment = comment.trim();
+        if comment.is_empty() {
+            None
+        } else {
+            Some(comment.to_owned())
+        }
+    }
+
     fn prepare_trailers(&self, matches: &ArgMatches) -> Vec<Trailer> {
         let mut trailers = Vec::new();
 
@@ -183,14 +410,38 @@ impl<'r> RepositoryUtil<'r> for Repository {
 
         // append misc metadata
         if let Some(metadata) = matches.values_of("metadata") {
-            for trailer in metadata.map(Trailer::from_str) {
-                trailers.push(trailer.unwrap_or_abort());
-            }
+            trailers.append(&mut self.parse_trailers(metadata));
         }
 
         trailers
     }
 
+    fn parse_trailers<'a, I>(&self, values: I) -> Vec<Trailer>
+        where I: IntoIterator<Item = &'a str>
+    {
+        let specs = self.trailer_specs().unwrap_or_abort();
+        values
+            .into_iter()
+            .map(Trailer::from_str)
+            .abort_on_err()
+            .map(|mut trailer| {
+                if let Some(spec) = specs.get(trailer.key.as_ref()) {
+                    trailer.key = spec.key.to_string().into();
+                }
+
+                // values of known pieces of metadata are validated and
+                // normalized according to their type
+                if let Some(spec) = specs.values().find(|spec| spec.key.as_ref() == trailer.key.as_ref().as_str()) {
+                    trailer.value = spec
+                        .parse_value(&trailer.value.to_string())
+                        .map_err(Error::from)
+                        .unwrap_or_abort();
+                }
+                trailer
+            })
+            .collect()
+    }
+
     fn cli_author(&self, matches: &ArgMatches) -> git2::Signature {
         use chrono::DateTime;
 
@@ -242,6 +493,20 @@ impl<'r> RepositoryUtil<'r> for Repository {
         }
     }
 
+    fn cli_signer(&self, matches: &ArgMatches) -> Option<Program> {
+        let config = self.config().unwrap_or_abort();
+        let sign = matches.is_present("gpgsign") || config.get_bool("commit.gpgsign").unwrap_or(false);
+        if sign {
+            Some(Program::for_repository(self).unwrap_or_abort())
+        } else {
+            None
+        }
+    }
+
+    fn signature_verifier(&self) -> Program {
+        Program::for_repository(self).unwrap_or_abort()
+    }
+
     fn abbreviation_length(&self, matches: &ArgMatches) -> usize {
         if !matches.is_present("abbrev") {
             // If the abbreviation option was not used, we can just use the
@@ -258,13 +523,45 @@ impl<'r> RepositoryUtil<'r> for Repository {
             return str::parse(number).unwrap_or_abort();
         }
 
-        if let Some(number) = self.config().unwrap_or_abort().get_i32("core.abbrev").ok() {
-            // The abbreviation flag might have been specified as a configuration option
-            return number as usize;
+        // The abbreviation length might have been specified as a
+        // configuration option, either as a number or as "auto".
+        match self.config().unwrap_or_abort().get_string("core.abbrev").ok() {
+            Some(ref value) if value == "no" => return 40,
+            Some(ref value) if value != "auto" => return str::parse(value).unwrap_or_abort(),
+            _ => {},
         }
 
-        // TODO: use a larger number based on the number of objects in the repo
-        7
+        // Like git, we scale the length with the approximate number of objects
+        // in the repository, such that abbreviated ids are unlikely to
+        // collide.
+        let count = approximate_object_count(self);
+        let bits = 64 - count.leading_zeros() as usize;
+        std::cmp::max(7, bits.div_ceil(2))
+    }
+
+    fn output_format(&self, matches: &ArgMatches) -> Option<OutputFormat> {
+        let format = matches.value_of("format")?;
+
+        if let Some(json) = JsonFormat::from_name(format) {
+            return Some(OutputFormat::Json(json));
+        }
+
+        let preset = self
+            .dit_config()
+            .unwrap_or_abort()
+            .get_string(&format!("dit.format.{}", format))
+            .ok();
+        if let Some(preset) = preset {
+            return Some(OutputFormat::Custom(preset));
+        }
+
+        // Anything else has to be a format string, i.e. contain a placeholder
+        Some(format)
+            .filter(|format| format.contains('%'))
+            .map(|format| OutputFormat::Custom(format.to_owned()))
+            .ok_or_else(|| Error::from_kind(EK::UnknownFormat(format.to_owned())))
+            .map(Some)
+            .unwrap_or_abort()
     }
 
     fn remote_priorization(&self) -> RemotePriorization {
@@ -275,6 +572,323 @@ impl<'r> RepositoryUtil<'r> for Repository {
             .into()
     }
 
+    fn enabled_metadata_cache(&self) -> Option<MetadataCache> {
+        let enabled = self
+            .config()
+            .unwrap_or_abort()
+            .get_bool("dit.cache")
+            .unwrap_or(true);
+        if enabled {
+            Some(self.metadata_cache())
+        } else {
+            None
+        }
+    }
+
+    fn metadata_authorization<'a>(&'a self) -> Option<MetadataAuthorization<'a>> {
+        let config = self.config().unwrap_or_abort();
+        if !config.get_bool("dit.trusted-metadata").unwrap_or(false) {
+            return None;
+        }
+
+        let maintainers: Vec<String> = config
+            .entries(Some(r"^dit\.maintainer$"))
+            .unwrap_or_abort()
+            .into_iter()
+            .filter_map(|entry| entry.unwrap_or_abort().value().map(|value| value.trim().to_owned()))
+            .collect();
+        let people = AuthorizedPeople::new(maintainers)
+            .with_reporter()
+            .with_assignee();
+        let authorization = VerifiedPeople::new(self, self.signature_verifier(), people);
+        Some((authorization, self.assignee_spec().key.into_owned()))
+    }
+
+    fn issue_head(&self, issue: &Issue, prios: &RemotePriorization) -> Option<Oid> {
+        issue
+            .heads()
+            .abort_on_err()
+            .select_ref(prios)
+            .map(|head| head.peel(git2::ObjectType::Commit).unwrap_or_abort().id())
+    }
+
+    fn accumulated_metadata(&self, issue: &Issue, head: Oid) -> HashMap<String, ValueAccumulator> {
+        let specs = self.trailer_specs().unwrap_or_abort();
+        let specs: Vec<&TrailerSpec> = specs.values().collect();
+        self.accumulated_trailers(issue, head, &specs)
+    }
+
+    fn accumulated_trailers(&self, issue: &Issue, head: Oid, specs: &[&TrailerSpec]) -> HashMap<String, ValueAccumulator> {
+        use libgitdit::iter::MessagesExt;
+
+        if let Some((authorization, assignee_key)) = self.metadata_authorization() {
+            return issue
+                .messages_from(head)
+                .abort_on_err()
+                .accumulate_authorized_trailers(specs.iter().cloned(), authorization, &assignee_key)
+                .unwrap_or_abort();
+        }
+
+        match self.enabled_metadata_cache() {
+            Some(cache) => cache.accumulate(issue, head, specs.iter().cloned()).unwrap_or_abort(),
+            None => issue
+                .messages_from(head)
+                .abort_on_err()
+                .accumulate_trailers(specs.iter().cloned()),
+        }
+    }
+
+    fn related_issues(&self, issue: &Issue) -> (Vec<Oid>, Vec<Oid>) {
+        use libgitdit::links;
+
+        let mut references: Vec<Oid> = links::links(self, issue)
+            .unwrap_or_abort()
+            .into_iter()
+            .map(|link| link.target)
+            .collect();
+        references.sort();
+        references.dedup();
+
+        let issues: Vec<Issue> = self.issues().unwrap_or_abort().into_iter().collect();
+        let mut referrers: Vec<Oid> = links::backlinks(self, issue, issues.iter())
+            .unwrap_or_abort()
+            .into_iter()
+            .map(|link| link.source)
+            .collect();
+        referrers.dedup();
+
+        (references, referrers)
+    }
+
+    fn dependencies(&self, prios: &RemotePriorization) -> Dependencies {
+        use libgitdit::trailer::spec::{ISSUE_BLOCKS_SPEC, ISSUE_DEPENDS_ON_SPEC};
+
+        let status_spec = self.status_spec();
+        let closed_status = self.closed_status();
+        let mut specs = self.trailer_specs().unwrap_or_abort();
+        let depends_on = specs.remove("depends-on").unwrap_or(ISSUE_DEPENDS_ON_SPEC);
+        let blocks = specs.remove("blocks").unwrap_or(ISSUE_BLOCKS_SPEC);
+
+        let mut graph = DependencyGraph::new();
+        let mut closed = HashSet::new();
+        for issue in self.issues().unwrap_or_abort() {
+            let metadata = match self.issue_head(&issue, prios) {
+                Some(head) => self.accumulated_metadata(&issue, head),
+                None => continue,
+            };
+
+            if is_closed(&metadata, &status_spec.key, &closed_status) {
+                closed.insert(issue.id());
+            }
+
+            graph.add_issue(self, issue.id(), &metadata, &depends_on, &blocks);
+        }
+
+        Dependencies { graph, closed }
+    }
+
+    fn closed_status(&self) -> Vec<String> {
+        let retval: Vec<String> = self
+            .config()
+            .unwrap_or_abort()
+            .entries(Some(r"^dit\.closed-status$"))
+            .unwrap_or_abort()
+            .into_iter()
+            .filter_map(|entry| entry.unwrap_or_abort().value().map(str::to_owned))
+            .collect();
+        if !retval.is_empty() {
+            return retval;
+        }
+
+        let terminal = self.workflow().unwrap_or_abort().terminal().to_vec();
+        if terminal.is_empty() {
+            vec!["closed".to_owned()]
+        } else {
+            terminal
+        }
+    }
+
+    fn check_status_transition(&self, issue: &Issue, head: Oid, author: &git2::Signature, trailers: &[Trailer]) {
+        use libgitdit::policy::Policy;
+
+        let workflow = self.workflow().unwrap_or_abort();
+        if !workflow.is_enabled() {
+            return;
+        }
+
+        let status_spec = self.status_spec();
+        let assignee_spec = self.assignee_spec();
+
+        let to = match trailers
+            .iter()
+            .rfind(|trailer| trailer.key.as_ref() == status_spec.key.as_ref()) {
+            Some(trailer) => trailer.value.to_string().trim().to_owned(),
+            None => return,
+        };
+
+        // The status and assignee are determined like any other metadata,
+        // i.e. honouring `dit.trusted-metadata`.
+        let mut metadata = self.accumulated_trailers(issue, head, &[&status_spec, &assignee_spec]);
+        let mut latest = |spec: &TrailerSpec| metadata
+            .remove(spec.key.as_ref())
+            .and_then(|values| values.into_iter().next())
+            .map(|value| value.to_string().trim().to_owned());
+        let from = latest(&status_spec);
+        let assignee = latest(&assignee_spec);
+
+        let policy = Policy::for_repository(self).unwrap_or_abort();
+        let person = author.to_string();
+        workflow
+            .check(from.as_deref(), &to, |subjects| {
+                policy.permits(self, subjects, Some(&person), Some(issue.id()), || Ok(assignee.clone()))
+            })
+            .map_err(Error::from)
+            .unwrap_or_abort();
+    }
+
+    fn check_status_transitions(&self, issues: &[Issue], author: &git2::Signature, trailers: &[Trailer]) {
+        let prios = self.remote_priorization();
+        for issue in issues {
+            let head = self
+                .issue_head(issue, &prios)
+                .ok_or_else(|| Error::from(LibError::from_kind(LibEK::CannotFindIssueHead(issue.id()))))
+                .unwrap_or_abort();
+            self.check_status_transition(issue, head, author, trailers);
+        }
+    }
+
+    fn status_spec(&self) -> TrailerSpec {
+        use libgitdit::trailer::spec::ISSUE_STATUS_SPEC;
+
+        self.trailer_specs()
+            .unwrap_or_abort()
+            .remove("status")
+            .unwrap_or(ISSUE_STATUS_SPEC)
+    }
+
+    fn assignee_spec(&self) -> TrailerSpec {
+        use libgitdit::trailer::accumulation::AccumulationPolicy;
+
+        self.trailer_specs()
+            .unwrap_or_abort()
+            .remove("assignee")
+            .unwrap_or_else(|| TrailerSpec::new("Dit-assignee", AccumulationPolicy::Latest))
+    }
+
+    fn fix_tips(&self, branches: Option<Values>) -> Vec<Oid> {
+        let names: Vec<String> = match branches {
+            Some(values) => values.map(str::to_owned).collect(),
+            None => {
+                let tips = self.configured_fix_tips();
+                if !tips.is_empty() {
+                    return tips;
+                }
+                self.branches(Some(git2::BranchType::Local))
+                    .unwrap_or_abort()
+                    .filter_map(|branch| branch.unwrap_or_abort().0.get().name().map(str::to_owned))
+                    .collect()
+            },
+        };
+
+        names
+            .iter()
+            .map(|name| self.value_to_commit(name).id())
+            .collect()
+    }
+
+    fn configured_fix_tips(&self) -> Vec<Oid> {
+        self.config()
+            .unwrap_or_abort()
+            .entries(Some(r"^dit\.fix-branch$"))
+            .unwrap_or_abort()
+            .into_iter()
+            .filter_map(|entry| entry.unwrap_or_abort().value().map(str::to_owned))
+            .map(|name| self.value_to_commit(&name).id())
+            .collect()
+    }
+
+    fn add_status_message(&'r self,
+                          issue: &Issue<'r>,
+                          author: &git2::Signature,
+                          body: Option<&str>,
+                          trailers: Vec<Trailer>,
+                          references: &[Commit<'r>],
+                          signer: Option<&Program>) -> Oid {
+        let committer = self.signature().unwrap_or_abort();
+        let head = self
+            .issue_head(issue, &self.remote_priorization())
+            .ok_or_else(|| Error::from(LibError::from_kind(LibEK::CannotFindIssueHead(issue.id()))))
+            .unwrap_or_abort();
+        let mut head_commit = self.find_commit(head).unwrap_or_abort();
+        self.check_status_transition(issue, head, author, &trailers);
+
+        // construct the message
+        let message = [head_commit.reply_subject().unwrap_or_default(), String::new()]
+            .to_vec()
+            .into_iter()
+            .chain(body.into_iter().flat_map(|body| vec![body.to_owned(), String::new()]))
+            .chain(trailers.into_iter().map(|t| t.to_string()))
+            .collect_string();
+        let tree = self.empty_tree().unwrap_or_abort();
+        let parent_refs : Vec<&Commit> = Some(&head_commit).into_iter().chain(references.iter()).collect();
+        let new = match signer {
+            Some(signer) => create_signed_commit(self, author, &committer, message.trim(), &tree, &parent_refs, signer)
+                .unwrap_or_abort(),
+            None => self
+                .commit(None, author, &committer, message.trim(), &tree, &parent_refs)
+                .unwrap_or_abort(),
+        };
+
+        // update the head reference
+        issue.update_head(new, true).unwrap_or_abort();
+        new
+    }
+
+    fn change_metadata(&'r self, matches: &ArgMatches, trailers: Vec<Trailer>) {
+        let author = self.cli_author(matches);
+
+        // NOTE: the issues are a required parameter
+        let issues: Vec<_> = self.cli_issues(matches).unwrap().into_iter().collect();
+
+        // We check all the changes before asking for a comment, since we
+        // neither want the user to write a comment in vain nor do we want to
+        // end up with only some of the issues changed.
+        self.check_status_transitions(&issues, &author, &trailers);
+
+        let comment = self.cli_comment(matches);
+        let signer = self.cli_signer(matches);
+        for issue in issues.iter() {
+            self.add_status_message(issue, &author, comment.as_deref(), trailers.clone(), &[], signer.as_ref());
+        }
+    }
+
+    fn push_issues(&self, remote: &str, issues: &[Issue]) {
+        let mut remote = self.find_remote(remote).unwrap_or_abort();
+
+        // accumulate the refspecs to push
+        let refspecs : Vec<String> = issues
+            .iter()
+            .map(|issue| issue.local_refs(IssueRefType::Any))
+            .abort_on_err()
+            .flat_map(|mut refs| {
+                let names: Vec<_> = refs
+                    .names()
+                    .abort_on_err()
+                    .map(String::from)
+                    .collect();
+                names
+            })
+            .collect();
+
+        // set the options for the push
+        let mut fetch_options = git2::PushOptions::new();
+        fetch_options.remote_callbacks(callbacks());
+
+        let refspec_refs : Vec<&str> = refspecs.iter().map(String::as_str).collect();
+        remote.push(refspec_refs.as_ref(), Some(&mut fetch_options))
+              .unwrap_or_abort();
+    }
+
     fn pager(&self) -> Child {
         programs::pager(self.config().unwrap_or_abort()).unwrap_or_abort()
     }
@@ -294,12 +908,108 @@ pub fn message_from_args(matches: &ArgMatches) -> Option<Vec<String>> {
 }
 
 
+/// Get the approximate number of objects in a repository
+///
+/// Visiting all the objects is expensive for large repositories. Hence, we
+/// only read the number of packed objects from the headers of the pack
+/// indices and estimate the number of loose objects from one of the
+/// directories holding them, like git does when deciding whether to pack.
+///
+fn approximate_object_count(repo: &Repository) -> u64 {
+    use std::fs;
+    use std::io::Read;
+
+    let objects = repo.path().join("objects");
+
+    // The last entry of the fan-out table holds the number of objects in the
+    // pack. Version 2 indices start with a magic number and the version.
+    let packed: u64 = fs::read_dir(objects.join("pack"))
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "idx"))
+        .filter_map(|path| {
+            let mut header = [0u8; 8 + 256 * 4];
+            File::open(path)
+                .and_then(|mut file| file.read_exact(&mut header))
+                .ok()?;
+            let offset = if header[..4] == [0xff, b't', b'O', b'c'] { 8 } else { 0 } + 255 * 4;
+            let mut count = [0u8; 4];
+            count.copy_from_slice(&header[offset..offset + 4]);
+            Some(u32::from_be_bytes(count) as u64)
+        })
+        .sum();
+
+    let loose = fs::read_dir(objects.join("17"))
+        .map(|entries| entries.count() as u64)
+        .unwrap_or(0);
+
+    packed + 256 * loose
+}
+
+
+/// Let the user edit a file and read back its lines
+///
+/// An editor will be spawned for editting the file specified by the path
+/// supplied. After editting, the file will be read back and stripped.
+///
+fn edit_lines(repo: &Repository, path: PathBuf) -> Vec<String> {
+    use io::BufRead;
+    use system::programs::run_editor;
+
+    if !run_editor(repo.config().unwrap_or_abort(), &path)
+        .unwrap_or_abort()
+        .wait()
+        .unwrap_or_abort()
+        .success()
+    {
+        Error::from_kind(EK::ChildError).log();
+        ::std::process::exit(1);
+    }
+
+    io::BufReader::new(File::open(path).unwrap_or_abort())
+        .lines()
+        .abort_on_err()
+        .stripped()
+        .collect()
+}
+
+
+/// Get the JSON output format specified on the command line
+///
+/// Returns `None` if no output format was specified via the `"format"` field.
+///
+pub fn json_format_from_args(matches: &ArgMatches) -> Option<JsonFormat> {
+    matches.value_of("format").map(|format| JsonFormat::from_name(format)
+        .ok_or_else(|| Error::from_kind(EK::UnknownFormat(format.to_owned())))
+        .unwrap_or_abort()
+    )
+}
+
+
 /// Get an issue from a string representation
 ///
-/// This function returns an issue from a string representation.
+/// This function returns an issue from a string representation, which may
+/// be an abbreviated id. The program is aborted if the id is ambiguous.
 ///
-fn value_to_issue<'r>(repo: &'r Repository, value: &str) -> Issue<'r> {
-    let id = git2::Oid::from_str(value).unwrap_or_abort();
-    repo.find_issue(id).unwrap_or_abort()
+pub fn value_to_issue<'r>(repo: &'r Repository, value: &str) -> Issue<'r> {
+    repo.find_issue_abbrev(value).unwrap_or_abort()
 }
 
+
+
+/// Check whether an issue is closed
+///
+/// The issue is considered closed if its status, as found in the metadata
+/// supplied under the status key supplied, is one of the status values
+/// supplied.
+///
+pub fn is_closed(metadata: &HashMap<String, ValueAccumulator>, status_key: &str, closed_status: &[String]) -> bool {
+    metadata
+        .get(status_key)
+        .cloned()
+        .into_iter()
+        .flat_map(|values| values.into_iter())
+        .any(|value| closed_status.contains(&value.to_string().trim().to_owned()))
+}
@@ -0,0 +1,503 @@
+//   git-dit - the distributed issue tracker for git
+//   Copyright (C) 2017 Matthias Beyer <mail@beyermatthias.de>
+//   Copyright (C) 2017 Julian Ganz <neither@nut.email>
+//
+//   This program is free software; you can redistribute it and/or modify
+//   it under the terms of the GNU General Public License version 2 as
+//   published by the Free Software Foundation.
+//
+
+//! Web frontend
+//!
+//! This module provides a minimal HTTP server presenting a read-only HTML view
+//! of the issues in a repository. The following pages are served:
+//!
+//!  * `/`: the list of issues, optionally filtered via one or more `filter`
+//!    query parameters holding filter expressions as accepted by `list`
+//!  * `/issue/<id>`: an issue's metadata and its messages as a threaded tree
+//!
+//! Requests are handled one at a time. The server is intended for local use
+//! only and does not support anything but `GET` and `HEAD` requests.
+//!
+
+use chrono::{FixedOffset, TimeZone};
+use git2::{Commit, ObjectType, Oid, Reference, Repository, Time};
+use libgitdit::{Issue, Message, RepositoryExt};
+use libgitdit::cache::MetadataCache;
+use libgitdit::dependency::DependencyGraph;
+use libgitdit::iter::MessagesExt;
+use libgitdit::query::Expr;
+use libgitdit::revision::Revisions;
+use libgitdit::trailer::accumulation::ValueAccumulator;
+use libgitdit::trailer::spec::{ISSUE_BLOCKS_SPEC, ISSUE_DEPENDS_ON_SPEC, TrailerSpec};
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+use error::*;
+use error::ErrorKind as EK;
+use filters::{Dependencies, MetadataFilter};
+use gitext::{RemotePriorization, ReferrencesExt};
+use util::{MetadataAuthorization, is_closed};
+
+
+/// HTTP response
+///
+struct Response {
+    status: &'static str,
+    body: String,
+}
+
+impl Response {
+    /// Create a successful response
+    ///
+    fn ok(body: String) -> Self {
+        Response { status: "200 OK", body }
+    }
+
+    /// Create a response with a status other than "200 OK"
+    ///
+    fn error(status: &'static str, message: &str) -> Self {
+        Response { status, body: page(status, &format!("<p>{}</p>", escape(message))) }
+    }
+}
+
+
+/// Web server for browsing the issues of a repository
+///
+pub struct Server<'r> {
+    repo: &'r Repository,
+    prios: RemotePriorization,
+    cache: Option<MetadataCache>,
+    trailer_specs: HashMap<String, TrailerSpec>,
+    status_key: String,
+    closed_status: Vec<String>,
+    authorization: Option<MetadataAuthorization<'r>>,
+}
+
+impl<'r> Server<'r> {
+    /// Create a new server for a repository
+    ///
+    /// Heads of issues are selected based on the remote priorization supplied.
+    /// Metadata is accumulated using the cache, if one is supplied. Issues
+    /// with one of the status values in `closed_status`, under the trailer key
+    /// `status_key`, are considered closed.
+    ///
+    pub fn new(repo: &'r Repository,
+               prios: RemotePriorization,
+               cache: Option<MetadataCache>,
+               trailer_specs: HashMap<String, TrailerSpec>,
+               status_key: String,
+               closed_status: Vec<String>
+    ) -> Self {
+        Server { repo, prios, cache, trailer_specs, status_key, closed_status, authorization: None }
+    }
+
+    /// Only consider trailers from messages authorized by an authorization
+    ///
+    /// The authorization is supplied along with the key of the assignee
+    /// trailers. The cache is not used for accumulating metadata in this case.
+    ///
+    pub fn with_authorization(mut self, authorization: Option<MetadataAuthorization<'r>>) -> Self {
+        self.authorization = authorization;
+        self
+    }
+
+    /// Serve requests accepted by a listener
+    ///
+    /// This function only returns if the listener fails. Errors occuring while
+    /// handling individual connections are logged.
+    ///
+    pub fn run(&self, listener: &TcpListener) -> Result<()> {
+        for stream in listener.incoming() {
+            let stream = stream.chain_err(|| EK::WrappedIOError)?;
+            if let Err(err) = self.handle(stream) {
+                warn!("Could not handle request: {}", err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Handle a single connection
+    ///
+    fn handle(&self, mut stream: TcpStream) -> ::std::io::Result<()> {
+        stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+
+        let mut request_line = String::new();
+        let mut reader = BufReader::new(stream.try_clone()?);
+        reader.read_line(&mut request_line)?;
+
+        // skip the headers
+        let mut line = String::new();
+        while reader.read_line(&mut line)? > 0 && !line.trim().is_empty() {
+            line.clear();
+        }
+
+        let mut parts = request_line.split_whitespace();
+        let method = parts.next().unwrap_or_default();
+        let target = parts.next().unwrap_or_default();
+        let response = match method {
+            "GET" | "HEAD" => {
+                let (path, query) = match target.find('?') {
+                    Some(pos) => (&target[..pos], &target[pos + 1..]),
+                    None => (target, ""),
+                };
+                self.respond(path, query)
+            },
+            _ => Response::error("405 Method Not Allowed", "Only GET requests are supported."),
+        };
+
+        write!(
+            stream,
+            "HTTP/1.0 {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            response.status,
+            response.body.len()
+        )?;
+        if method != "HEAD" {
+            stream.write_all(response.body.as_bytes())?;
+        }
+        stream.flush()
+    }
+
+    /// Compute the response for a path and query
+    ///
+    fn respond(&self, path: &str, query: &str) -> Response {
+        if path == "/" {
+            return self.issue_list(query);
+        }
+
+        let issue = path
+            .strip_prefix("/issue/")
+            .and_then(|id| Oid::from_str(id).ok())
+            .and_then(|id| self.repo.find_issue(id).ok());
+        match issue.map(|issue| self.issue_page(&issue)) {
+            Some(Ok(body)) => Response::ok(body),
+            Some(Err(err)) => Response::error("500 Internal Server Error", &err.to_string()),
+            None => Response::error("404 Not Found", &format!("Not found: {}", path)),
+        }
+    }
+
+    /// Respond with the list of issues
+    ///
+    /// Malformed filter expressions are reported as a bad request, errors
+    /// occuring during filtering as an internal server error.
+    ///
+    fn issue_list(&self, query: &str) -> Response {
+        let filters: Vec<String> = query_values(query, "filter")
+            .into_iter()
+            .filter(|value| !value.trim().is_empty())
+            .collect();
+        let filter = filters
+            .iter()
+            .map(|value| value.parse::<Expr>())
+            .collect::<::std::result::Result<Vec<_>, _>>()
+            .map_err(Error::from)
+            .and_then(|exprs| MetadataFilter::new(&self.prios, &self.trailer_specs, exprs));
+        let filter = match filter {
+            Ok(filter) => filter
+                .with_cache(self.cache.as_ref())
+                .with_authorization(self.authorization.as_ref()),
+            Err(err) => return Response::error("400 Bad Request", &err.to_string()),
+        };
+        let dependencies = if filter.needs_dependencies() {
+            match self.dependencies() {
+                Ok(dependencies) => Some(dependencies),
+                Err(err) => return Response::error("500 Internal Server Error", &err.to_string()),
+            }
+        } else {
+            None
+        };
+        let filter = filter.with_dependencies(dependencies.as_ref());
+
+        match self.render_issue_list(&filter, &filters) {
+            Ok(body) => Response::ok(body),
+            Err(err) => Response::error("500 Internal Server Error", &err.to_string()),
+        }
+    }
+
+    /// Render the list of issues matching a filter
+    ///
+    fn render_issue_list(&self,
+                         filter: &MetadataFilter,
+                         filters: &[String]
+    ) -> Result<String> {
+        // newest issues first
+        let mut issues: Vec<(Issue, Commit)> = Vec::new();
+        for issue in self.repo.issues()? {
+            if filter.try_filter(&issue)? {
+                let initial = issue.initial_message()?;
+                issues.push((issue, initial));
+            }
+        }
+        issues.sort_by_key(|(_, initial)| Reverse(initial.time()));
+
+        let mut body = String::new();
+        body.push_str(&format!("<form method=\"get\" action=\"/\"><input type=\"text\" name=\"filter\" size=\"60\" value=\"{}\"> \
+                      <input type=\"submit\" value=\"Filter\"></form>\n",
+               escape(&filters.join(" and "))));
+        body.push_str("<table>\n<tr><th>Issue</th><th>Created</th><th>Subject</th><th>Metadata</th></tr>\n");
+        for (issue, initial) in issues {
+            let metadata = match self.head(&issue)? {
+                Some(head) => self.metadata(&issue, head)?,
+                None => HashMap::new(),
+            };
+            body.push_str(&format!("<tr><td><a href=\"/issue/{0}\"><code>{0:.7}</code></a></td><td>{1}</td><td>{2}</td><td>{3}</td></tr>\n",
+                   issue.id(),
+                   format_time(initial.time()),
+                   escape(initial.summary().unwrap_or_default()),
+                   escape(&metadata_summary(&metadata))));
+        }
+        body.push_str("</table>\n");
+
+        Ok(page("Issues", &body))
+    }
+
+    /// Render an issue
+    ///
+    fn issue_page(&self, issue: &Issue) -> Result<String> {
+        let initial = issue.initial_message()?;
+        let head = self.head(issue)?;
+
+        let mut body = String::from("<p><a href=\"/\">All issues</a></p>\n<table>\n");
+        body.push_str(&format!("<tr><th>Issue</th><td><code>{}</code></td></tr>\n", issue.id()));
+        if let Some(head) = head {
+            body.push_str(&format!("<tr><th>Head</th><td><a href=\"#{0}\"><code>{0}</code></a></td></tr>\n", head));
+            let metadata = self.metadata(issue, head)?;
+            let mut names: Vec<&String> = metadata.keys().collect();
+            names.sort();
+            for name in names {
+                if !metadata[name].is_empty() {
+                    body.push_str(&format!("<tr><th>{}</th><td>{}</td></tr>\n",
+                           escape(name),
+                           escape(&metadata[name].join(", "))));
+                }
+            }
+        }
+        body.push_str("</table>\n");
+
+        // Like the "show" subcommand, we display only the latest revision of
+        // each message. Replies to any revision are displayed as replies to
+        // the latest one.
+        let messages: Vec<Commit> = issue.messages()?.collect::<::std::result::Result<_, _>>()?;
+        let revisions = Revisions::new(&messages);
+        let mut replies: HashMap<Oid, Vec<&Commit>> = HashMap::new();
+        for message in messages.iter().rev() {
+            let original = revisions.original(message.id());
+            if original != message.id() || message.id() == issue.id() {
+                continue;
+            }
+            if let Ok(parent) = message.parent_id(0) {
+                replies.entry(revisions.original(parent)).or_default().push(message);
+            }
+        }
+
+        body.push_str("<ul class=\"thread\">\n");
+        self.render_message(&mut body, &initial, &revisions, &replies, head)?;
+        body.push_str("</ul>\n");
+
+        Ok(page(initial.summary().unwrap_or_default(), &body))
+    }
+
+    /// Get the head of an issue
+    ///
+    fn head(&self, issue: &Issue) -> Result<Option<Oid>> {
+        let heads: Vec<Reference> = issue.heads()?.collect::<::std::result::Result<_, _>>()?;
+        heads
+            .select_ref(&self.prios)
+            .map(|head| head.peel(ObjectType::Commit).map(|commit| commit.id()))
+            .transpose()
+            .map_err(Error::from)
+    }
+
+    /// Get the dependencies between the issues of the repository
+    ///
+    fn dependencies(&self) -> Result<Dependencies> {
+        let depends_on = self.trailer_specs.get("depends-on").unwrap_or(&ISSUE_DEPENDS_ON_SPEC);
+        let blocks = self.trailer_specs.get("blocks").unwrap_or(&ISSUE_BLOCKS_SPEC);
+
+        let mut graph = DependencyGraph::new();
+        let mut closed = HashSet::new();
+        for issue in self.repo.issues()? {
+            let metadata = match self.head(&issue)? {
+                Some(head) => self.accumulate(&issue, head)?,
+                None => continue,
+            };
+
+            if is_closed(&metadata, &self.status_key, &self.closed_status) {
+                closed.insert(issue.id());
+            }
+
+            graph.add_issue(self.repo, issue.id(), &metadata, depends_on, blocks);
+        }
+
+        Ok(Dependencies { graph, closed })
+    }
+
+    /// Accumulate the metadata of an issue
+    ///
+    fn accumulate(&self, issue: &Issue, head: Oid) -> Result<HashMap<String, ValueAccumulator>> {
+        let specs = self.trailer_specs.values();
+        match (&self.authorization, &self.cache) {
+            (Some((authorization, assignee_key)), _) => Ok(issue
+                .messages_from(head)?
+                .collect::<::std::result::Result<Vec<_>, _>>()?
+                .into_iter()
+                .accumulate_authorized_trailers(specs, authorization, assignee_key)?),
+            (None, Some(cache)) => cache
+                .accumulate(issue, head, specs)
+                .map_err(Error::from),
+            (None, None) => Ok(issue
+                .messages_from(head)?
+                .collect::<::std::result::Result<Vec<_>, _>>()?
+                .into_iter()
+                .accumulate_trailers(specs)),
+        }
+    }
+
+    /// Get the metadata of an issue as lists of values, keyed by the names of
+    /// the pieces of metadata
+    ///
+    fn metadata(&self, issue: &Issue, head: Oid) -> Result<HashMap<String, Vec<String>>> {
+        let accumulated = self.accumulate(issue, head)?;
+
+        let metadata = self.trailer_specs
+            .iter()
+            .map(|(name, spec)| {
+                let values = match accumulated.get(spec.key.as_ref()) {
+                    Some(acc @ ValueAccumulator::Latest(_)) => acc.clone().into_iter().map(|v| v.to_string()).collect(),
+                    Some(ValueAccumulator::List(values)) => values.iter().map(ToString::to_string).collect(),
+                    Some(acc @ ValueAccumulator::Set(_)) => acc.clone().into_iter().map(|v| v.to_string()).collect(),
+                    None => Vec::new(),
+                };
+                (name.clone(), values)
+            })
+            .collect();
+        Ok(metadata)
+    }
+
+    /// Render a message and its replies as a list item
+    ///
+    fn render_message(&self,
+                      out: &mut String,
+                      message: &Commit,
+                      revisions: &Revisions,
+                      replies: &HashMap<Oid, Vec<&Commit>>,
+                      head: Option<Oid>
+    ) -> Result<()> {
+        let latest = self.repo.find_commit(revisions.latest(message.id()))?;
+        let is_head = head.is_some_and(|head| revisions.revisions(message.id()).contains(&head));
+
+        out.push_str(&format!("<li id=\"{0}\"><div class=\"message\"><div class=\"header\"><a href=\"#{0}\"><code>{0:.7}</code></a>{1} \
+                     <b>{2}</b><br>{3}, {4}</div>\n<pre>{5}</pre></div>\n",
+               latest.id(),
+               if is_head { " (head)" } else { "" },
+               escape(latest.summary().unwrap_or_default()),
+               escape(&latest.author().to_string()),
+               format_time(latest.time()),
+               escape(&latest.body_lines().collect::<Vec<_>>().join("\n"))));
+
+        if let Some(children) = replies.get(&revisions.original(message.id())) {
+            out.push_str("<ul>\n");
+            for child in children {
+                self.render_message(out, child, revisions, replies, head)?;
+            }
+            out.push_str("</ul>\n");
+        }
+        out.push_str("</li>\n");
+        Ok(())
+    }
+}
+
+
+/// Wrap a body into a complete HTML page
+///
+fn page(title: &str, body: &str) -> String {
+    format!(concat!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{0}</title>\n",
+        "<style>body {{ font-family: sans-serif; }} td, th {{ text-align: left; padding: 0 1em 0 0; }} ",
+        "ul.thread, ul.thread ul {{ list-style: none; padding-left: 1.5em; border-left: 1px solid #ccc; }} ",
+        ".message {{ margin: 1em 0; }} pre {{ white-space: pre-wrap; }}</style>\n",
+        "</head><body><h1>{0}</h1>\n{1}</body></html>\n"),
+        escape(title), body)
+}
+
+
+/// Escape text for inclusion in HTML
+///
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+
+/// Format a point in time for display
+///
+fn format_time(time: Time) -> String {
+    FixedOffset::east_opt(time.offset_minutes() * 60)
+        .and_then(|offset| offset.timestamp_opt(time.seconds(), 0).single())
+        .map(|date| date.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_default()
+}
+
+
+/// Summarize metadata in a single line
+///
+fn metadata_summary(metadata: &HashMap<String, Vec<String>>) -> String {
+    let mut names: Vec<&String> = metadata.keys().filter(|name| !metadata[*name].is_empty()).collect();
+    names.sort();
+    names
+        .into_iter()
+        .map(|name| format!("{}: {}", name, metadata[name].join(", ")))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+
+/// Extract the decoded values of a parameter from a query string
+///
+fn query_values(query: &str, key: &str) -> Vec<String> {
+    query
+        .split('&')
+        .filter_map(|pair| {
+            let mut parts = pair.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some(k), Some(value)) if url_decode(k) == key => Some(url_decode(value)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+
+/// Decode a URL-encoded component
+///
+fn url_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut retval = Vec::new();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let byte = match bytes[pos] {
+            b'+' => b' ',
+            b'%' => {
+                let decoded = bytes
+                    .get(pos + 1..pos + 3)
+                    .and_then(|hex| ::std::str::from_utf8(hex).ok())
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                if let Some(decoded) = decoded {
+                    pos += 2;
+                    decoded
+                } else {
+                    b'%'
+                }
+            },
+            byte => byte,
+        };
+        retval.push(byte);
+        pos += 1;
+    }
+    String::from_utf8_lossy(&retval).into_owned()
+}